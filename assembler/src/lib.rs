@@ -0,0 +1,705 @@
+pub mod octo;
+
+use chip8_core::chip8::MEMORY_START_ADDRESS;
+use chip8_core::instruction::Instruction;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Includes nested deeper than this are most likely including each other.
+const MAX_INCLUDE_DEPTH: usize = 16;
+// Constants defined through more constants than this are most likely defined in terms of
+// themselves.
+const MAX_CONSTANT_DEPTH: usize = 64;
+
+/// An error found while assembling, pointing at the offending line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssemblyError {
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AssemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.message)
+    }
+}
+
+impl std::error::Error for AssemblyError {}
+
+/// Assembles source code into a ROM loaded at 0x200. Included files are looked up relative to
+/// the current directory.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AssemblyError> {
+    let mut assembler = Assembler::default();
+    assembler.load_source(source, "<input>", Path::new("."), 0)?;
+    assembler.assemble()
+}
+
+/// Assembles a source file into a ROM loaded at 0x200. Included files are looked up relative to
+/// the file that includes them.
+pub fn assemble_file(path: &Path) -> Result<Vec<u8>, AssemblyError> {
+    let mut assembler = Assembler::default();
+    assembler.load_file(path, None, 0)?;
+    assembler.assemble()
+}
+
+// A line of source code, remembering where it came from for error messages.
+struct SourceLine {
+    file: String,
+    number: usize,
+    text: String,
+}
+
+impl SourceLine {
+    fn error(&self, message: impl Into<String>) -> AssemblyError {
+        AssemblyError {
+            file: self.file.clone(),
+            line: self.number,
+            message: message.into(),
+        }
+    }
+}
+
+enum Item {
+    Instruction {
+        mnemonic: String,
+        operands: Vec<String>,
+    },
+    Bytes(Vec<String>),
+    Words(Vec<String>),
+}
+
+struct Statement {
+    line: usize,
+    item: Item,
+}
+
+#[derive(Default)]
+struct Assembler {
+    lines: Vec<SourceLine>,
+    labels: HashMap<String, u16>,
+    // Constants are kept as expressions, so they may refer to labels defined later on.
+    constants: HashMap<String, (String, usize)>,
+}
+
+impl Assembler {
+    fn load_file(
+        &mut self,
+        path: &Path,
+        included_from: Option<&SourceLine>,
+        depth: usize,
+    ) -> Result<(), AssemblyError> {
+        let name = path.display().to_string();
+        let source = fs::read_to_string(path).map_err(|error| {
+            let message = format!("couldn't read {}: {}", name, error);
+            match included_from {
+                Some(line) => line.error(message),
+                None => AssemblyError {
+                    file: name.clone(),
+                    line: 0,
+                    message,
+                },
+            }
+        })?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+        self.load_source(&source, &name, base_dir, depth)
+    }
+
+    fn load_source(
+        &mut self,
+        source: &str,
+        name: &str,
+        base_dir: &Path,
+        depth: usize,
+    ) -> Result<(), AssemblyError> {
+        for (index, text) in source.lines().enumerate() {
+            let line = SourceLine {
+                file: name.to_string(),
+                number: index + 1,
+                text: text.to_string(),
+            };
+            let code = strip_comment(&line.text).trim();
+            let mut words = code.splitn(2, char::is_whitespace);
+            if !words
+                .next()
+                .unwrap_or_default()
+                .eq_ignore_ascii_case("include")
+            {
+                self.lines.push(line);
+                continue;
+            }
+
+            if depth >= MAX_INCLUDE_DEPTH {
+                return Err(line.error("includes are nested too deeply"));
+            }
+            let argument = words.next().unwrap_or_default().trim();
+            let path = argument
+                .strip_prefix('"')
+                .and_then(|path| path.strip_suffix('"'))
+                .ok_or_else(|| line.error("expected a quoted path after include"))?;
+            let path: PathBuf = base_dir.join(path);
+            self.load_file(&path, Some(&line), depth + 1)?;
+        }
+        Ok(())
+    }
+
+    fn assemble(&mut self) -> Result<Vec<u8>, AssemblyError> {
+        let statements = self.first_pass()?;
+        let mut rom = Vec::new();
+        for statement in &statements {
+            let line = &self.lines[statement.line];
+            let bytes = self.encode(line, statement)?;
+            rom.extend(bytes);
+        }
+        Ok(rom)
+    }
+
+    // Assigns an address to every label and statement.
+    fn first_pass(&mut self) -> Result<Vec<Statement>, AssemblyError> {
+        let mut statements = Vec::new();
+        let mut address = MEMORY_START_ADDRESS as u32;
+        for (index, line) in self.lines.iter().enumerate() {
+            let mut code = strip_comment(&line.text).trim();
+
+            // Any number of labels may precede the statement.
+            while let Some((label, rest)) = split_label(code) {
+                if self.labels.contains_key(label) || self.constants.contains_key(label) {
+                    return Err(line.error(format!("{} is already defined", label)));
+                }
+                self.labels.insert(label.to_string(), address as u16);
+                code = rest.trim();
+            }
+            if code.is_empty() {
+                continue;
+            }
+
+            let (mnemonic, rest) = match code.split_once(char::is_whitespace) {
+                Some((mnemonic, rest)) => (mnemonic, rest.trim()),
+                None => (code, ""),
+            };
+
+            // NAME equ VALUE
+            if let Some((operator, value)) = rest.split_once(char::is_whitespace) {
+                if operator.eq_ignore_ascii_case("equ") {
+                    if !is_identifier(mnemonic) {
+                        return Err(line.error(format!("invalid constant name {}", mnemonic)));
+                    }
+                    if self.labels.contains_key(mnemonic) || self.constants.contains_key(mnemonic) {
+                        return Err(line.error(format!("{} is already defined", mnemonic)));
+                    }
+                    self.constants
+                        .insert(mnemonic.to_string(), (value.trim().to_string(), index));
+                    continue;
+                }
+            }
+
+            let operands: Vec<String> = if rest.is_empty() {
+                Vec::new()
+            } else {
+                rest.split(',')
+                    .map(|operand| operand.trim().to_string())
+                    .collect()
+            };
+            let mnemonic = mnemonic.to_uppercase();
+            let (item, size) = match mnemonic.as_str() {
+                "DB" => (Item::Bytes(operands.clone()), operands.len() as u32),
+                "DW" => (Item::Words(operands.clone()), operands.len() as u32 * 2),
+                _ => {
+                    // F000 NNNN is the only instruction followed by an operand.
+                    let long = operands
+                        .iter()
+                        .any(|operand| operand.to_uppercase().starts_with("LONG "));
+                    let size = if long { 4 } else { 2 };
+                    (Item::Instruction { mnemonic, operands }, size)
+                }
+            };
+            statements.push(Statement { line: index, item });
+            address += size;
+            if address > 0x10000 {
+                return Err(line.error("the program doesn't fit in memory"));
+            }
+        }
+        Ok(statements)
+    }
+
+    fn encode(&self, line: &SourceLine, statement: &Statement) -> Result<Vec<u8>, AssemblyError> {
+        match &statement.item {
+            Item::Bytes(values) => values
+                .iter()
+                .map(|value| {
+                    let value = self.evaluate(line, value, 0)?;
+                    if !(-128..=255).contains(&value) {
+                        return Err(line.error(format!("{} doesn't fit in a byte", value)));
+                    }
+                    Ok(value as u8)
+                })
+                .collect(),
+            Item::Words(values) => {
+                let mut bytes = Vec::new();
+                for value in values {
+                    let value = self.evaluate(line, value, 0)?;
+                    if !(-32768..=65535).contains(&value) {
+                        return Err(line.error(format!("{} doesn't fit in a word", value)));
+                    }
+                    bytes.extend((value as u16).to_be_bytes());
+                }
+                Ok(bytes)
+            }
+            Item::Instruction { mnemonic, operands } => {
+                let (instruction, long) = self.parse_instruction(line, mnemonic, operands)?;
+                let mut bytes = instruction.encode().to_be_bytes().to_vec();
+                if let Some(address) = long {
+                    bytes.extend(address.to_be_bytes());
+                }
+                Ok(bytes)
+            }
+        }
+    }
+
+    // Returns the instruction and, for F000 NNNN, the address that follows it.
+    fn parse_instruction(
+        &self,
+        line: &SourceLine,
+        mnemonic: &str,
+        operands: &[String],
+    ) -> Result<(Instruction, Option<u16>), AssemblyError> {
+        let operands: Vec<&str> = operands.iter().map(String::as_str).collect();
+        let upper: Vec<String> = operands
+            .iter()
+            .map(|operand| operand.to_uppercase())
+            .collect();
+        let upper: Vec<&str> = upper.iter().map(String::as_str).collect();
+        let register = |operand: &str| parse_register(operand);
+        let byte = |operand: &str| {
+            self.value(line, operand, -128, 255)
+                .map(|value| value as u8)
+        };
+        let nibble = |operand: &str| self.value(line, operand, 0, 15).map(|value| value as u8);
+        let address = |operand: &str| {
+            self.value(line, operand, 0, 0xFFF)
+                .map(|value| value as u16)
+        };
+
+        let instruction = match (mnemonic, upper.as_slice()) {
+            ("CLS", []) => Instruction::Clear,
+            ("RET", []) => Instruction::Return,
+            ("SCR", []) => Instruction::ScrollRight,
+            ("SCL", []) => Instruction::ScrollLeft,
+            ("EXIT", []) => Instruction::Exit,
+            ("LOW", []) => Instruction::Lores,
+            ("HIGH", []) => Instruction::Hires,
+            ("AUDIO", []) => Instruction::Audio,
+            ("SCD", [_]) => Instruction::ScrollDown {
+                n: nibble(operands[0])?,
+            },
+            ("SCU", [_]) => Instruction::ScrollUp {
+                n: nibble(operands[0])?,
+            },
+            ("PLANE", [_]) => Instruction::Plane {
+                n: nibble(operands[0])?,
+            },
+            ("SYS", [_]) => Instruction::Sys {
+                addr: address(operands[0])?,
+            },
+            ("JP", ["V0", _]) => Instruction::JumpOffset {
+                addr: address(operands[1])?,
+            },
+            ("JP", [_]) => Instruction::Jump {
+                addr: address(operands[0])?,
+            },
+            ("CALL", [_]) => Instruction::Call {
+                addr: address(operands[0])?,
+            },
+            ("SAVE", [_]) | ("LOAD", [_]) => {
+                let (x, y) = operands[0]
+                    .split_once('-')
+                    .and_then(|(x, y)| Some((register(x.trim())?, register(y.trim())?)))
+                    .ok_or_else(|| line.error("expected a register range like V0 - V3"))?;
+                if mnemonic == "SAVE" {
+                    Instruction::SaveRange { x, y }
+                } else {
+                    Instruction::LoadRange { x, y }
+                }
+            }
+            ("LD", ["I", long]) if long.starts_with("LONG ") => {
+                let value = self.value(line, operands[1][5..].trim(), 0, 0xFFFF)?;
+                return Ok((Instruction::LoadLongI, Some(value as u16)));
+            }
+            ("LD", ["I", _]) => Instruction::LoadI {
+                addr: address(operands[1])?,
+            },
+            ("LD", ["DT", x]) => Instruction::SetDelay {
+                x: self.register(line, x)?,
+            },
+            ("LD", ["ST", x]) => Instruction::SetSound {
+                x: self.register(line, x)?,
+            },
+            ("LD", ["F", x]) => Instruction::LoadFont {
+                x: self.register(line, x)?,
+            },
+            ("LD", ["HF", x]) => Instruction::LoadBigFont {
+                x: self.register(line, x)?,
+            },
+            ("LD", ["B", x]) => Instruction::Bcd {
+                x: self.register(line, x)?,
+            },
+            ("LD", ["[I]", x]) => Instruction::Store {
+                x: self.register(line, x)?,
+            },
+            ("LD", ["R", x]) => Instruction::SaveFlags {
+                x: self.register(line, x)?,
+            },
+            ("LD", [x, "DT"]) => Instruction::LoadDelay {
+                x: self.register(line, x)?,
+            },
+            ("LD", [x, "K"]) => Instruction::WaitKey {
+                x: self.register(line, x)?,
+            },
+            ("LD", [x, "[I]"]) => Instruction::Load {
+                x: self.register(line, x)?,
+            },
+            ("LD", [x, "R"]) => Instruction::LoadFlags {
+                x: self.register(line, x)?,
+            },
+            ("LD", [x, y]) => {
+                let x = self.register(line, x)?;
+                match register(y) {
+                    Some(y) => Instruction::Move { x, y },
+                    None => Instruction::LoadImm {
+                        x,
+                        nn: byte(operands[1])?,
+                    },
+                }
+            }
+            ("ADD", ["I", x]) => Instruction::AddI {
+                x: self.register(line, x)?,
+            },
+            ("ADD", [x, y]) => {
+                let x = self.register(line, x)?;
+                match register(y) {
+                    Some(y) => Instruction::Add { x, y },
+                    None => Instruction::AddImm {
+                        x,
+                        nn: byte(operands[1])?,
+                    },
+                }
+            }
+            ("SE", [x, y]) | ("SNE", [x, y]) => {
+                let x = self.register(line, x)?;
+                let equal = mnemonic == "SE";
+                match (register(y), equal) {
+                    (Some(y), true) => Instruction::SkipEqReg { x, y },
+                    (Some(y), false) => Instruction::SkipNeReg { x, y },
+                    (None, true) => Instruction::SkipEqImm {
+                        x,
+                        nn: byte(operands[1])?,
+                    },
+                    (None, false) => Instruction::SkipNeImm {
+                        x,
+                        nn: byte(operands[1])?,
+                    },
+                }
+            }
+            ("OR", [x, y])
+            | ("AND", [x, y])
+            | ("XOR", [x, y])
+            | ("SUB", [x, y])
+            | ("SUBN", [x, y])
+            | ("SHR", [x, y])
+            | ("SHL", [x, y]) => {
+                let x = self.register(line, x)?;
+                let y = self.register(line, y)?;
+                match mnemonic {
+                    "OR" => Instruction::Or { x, y },
+                    "AND" => Instruction::And { x, y },
+                    "XOR" => Instruction::Xor { x, y },
+                    "SUB" => Instruction::Sub { x, y },
+                    "SUBN" => Instruction::SubReverse { x, y },
+                    "SHR" => Instruction::ShiftRight { x, y },
+                    _ => Instruction::ShiftLeft { x, y },
+                }
+            }
+            // Without a second register, VX is shifted in place regardless of the shift quirk.
+            ("SHR", [x]) => {
+                let x = self.register(line, x)?;
+                Instruction::ShiftRight { x, y: x }
+            }
+            ("SHL", [x]) => {
+                let x = self.register(line, x)?;
+                Instruction::ShiftLeft { x, y: x }
+            }
+            ("RND", [x, _]) => Instruction::Random {
+                x: self.register(line, x)?,
+                nn: byte(operands[1])?,
+            },
+            ("DRW", [x, y, _]) => Instruction::Draw {
+                x: self.register(line, x)?,
+                y: self.register(line, y)?,
+                n: nibble(operands[2])?,
+            },
+            ("SKP", [x]) => Instruction::SkipKeyPressed {
+                x: self.register(line, x)?,
+            },
+            ("SKNP", [x]) => Instruction::SkipKeyNotPressed {
+                x: self.register(line, x)?,
+            },
+            ("PITCH", [x]) => Instruction::Pitch {
+                x: self.register(line, x)?,
+            },
+            _ => {
+                return Err(line.error(format!(
+                    "invalid instruction: {} {}",
+                    mnemonic,
+                    operands.join(", ")
+                )))
+            }
+        };
+        Ok((instruction, None))
+    }
+
+    fn register(&self, line: &SourceLine, operand: &str) -> Result<u8, AssemblyError> {
+        parse_register(operand)
+            .ok_or_else(|| line.error(format!("expected a register, found {}", operand)))
+    }
+
+    fn value(
+        &self,
+        line: &SourceLine,
+        operand: &str,
+        min: i64,
+        max: i64,
+    ) -> Result<i64, AssemblyError> {
+        let value = self.evaluate(line, operand, 0)?;
+        if value < min || value > max {
+            return Err(line.error(format!("{} is out of range ({}..={})", value, min, max)));
+        }
+        Ok(value)
+    }
+
+    fn evaluate(
+        &self,
+        line: &SourceLine,
+        expression: &str,
+        depth: usize,
+    ) -> Result<i64, AssemblyError> {
+        let mut parser = ExpressionParser {
+            assembler: self,
+            line,
+            tokens: tokenize(expression),
+            position: 0,
+            depth,
+        };
+        let value = parser.expression()?;
+        if parser.position != parser.tokens.len() {
+            return Err(line.error(format!("invalid expression: {}", expression)));
+        }
+        Ok(value)
+    }
+
+    fn symbol(&self, line: &SourceLine, name: &str, depth: usize) -> Result<i64, AssemblyError> {
+        if let Some(address) = self.labels.get(name) {
+            return Ok(*address as i64);
+        }
+        match self.constants.get(name) {
+            Some((expression, index)) => {
+                if depth >= MAX_CONSTANT_DEPTH {
+                    return Err(line.error(format!("{} is defined in terms of itself", name)));
+                }
+                self.evaluate(&self.lines[*index], expression, depth + 1)
+            }
+            None => Err(line.error(format!("undefined symbol {}", name))),
+        }
+    }
+}
+
+// A tiny recursive descent parser for + - * / and parentheses over numbers and symbols.
+struct ExpressionParser<'a> {
+    assembler: &'a Assembler,
+    line: &'a SourceLine,
+    tokens: Vec<String>,
+    position: usize,
+    depth: usize,
+}
+
+impl<'a> ExpressionParser<'a> {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.position).map(String::as_str)
+    }
+
+    fn next(&mut self) -> Option<String> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expression(&mut self) -> Result<i64, AssemblyError> {
+        let mut value = self.term()?;
+        while let Some(operator @ ("+" | "-")) = self.peek() {
+            let add = operator == "+";
+            self.position += 1;
+            let rhs = self.term()?;
+            let result = if add {
+                value.checked_add(rhs)
+            } else {
+                value.checked_sub(rhs)
+            };
+            value = result.ok_or_else(|| self.line.error("arithmetic overflow"))?;
+        }
+        Ok(value)
+    }
+
+    fn term(&mut self) -> Result<i64, AssemblyError> {
+        let mut value = self.factor()?;
+        while let Some(operator @ ("*" | "/")) = self.peek() {
+            let multiply = operator == "*";
+            self.position += 1;
+            let rhs = self.factor()?;
+            let result = if multiply {
+                value.checked_mul(rhs)
+            } else if rhs == 0 {
+                return Err(self.line.error("division by zero"));
+            } else {
+                value.checked_div(rhs)
+            };
+            value = result.ok_or_else(|| self.line.error("arithmetic overflow"))?;
+        }
+        Ok(value)
+    }
+
+    fn factor(&mut self) -> Result<i64, AssemblyError> {
+        let token = self
+            .next()
+            .ok_or_else(|| self.line.error("unexpected end of expression"))?;
+        match token.as_str() {
+            "-" => self
+                .factor()?
+                .checked_neg()
+                .ok_or_else(|| self.line.error("arithmetic overflow")),
+            "(" => {
+                let value = self.expression()?;
+                if self.next().as_deref() != Some(")") {
+                    return Err(self.line.error("expected )"));
+                }
+                Ok(value)
+            }
+            _ => match parse_number(&token) {
+                Some(value) => Ok(value),
+                None if is_identifier(&token) => {
+                    self.assembler.symbol(self.line, &token, self.depth)
+                }
+                None => Err(self.line.error(format!("invalid value {}", token))),
+            },
+        }
+    }
+}
+
+fn tokenize(expression: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    for character in expression.chars() {
+        if "+-*/()".contains(character) || character.is_whitespace() {
+            if !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+            if !character.is_whitespace() {
+                tokens.push(character.to_string());
+            }
+        } else {
+            current.push(character);
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+fn strip_comment(line: &str) -> &str {
+    match line.find(';') {
+        Some(index) => &line[..index],
+        None => line,
+    }
+}
+
+// Splits `name: rest` into its label and the rest of the line.
+fn split_label(code: &str) -> Option<(&str, &str)> {
+    let (label, rest) = code.split_once(':')?;
+    if is_identifier(label) {
+        Some((label, rest))
+    } else {
+        None
+    }
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut characters = text.chars();
+    match characters.next() {
+        Some(first) if first.is_ascii_alphabetic() || first == '_' => {}
+        _ => return false,
+    }
+    characters
+        .all(|character| character.is_ascii_alphanumeric() || character == '_' || character == '.')
+}
+
+fn parse_register(operand: &str) -> Option<u8> {
+    let mut characters = operand.trim().chars();
+    match (characters.next(), characters.next(), characters.next()) {
+        (Some('V' | 'v'), Some(digit), None) => digit.to_digit(16).map(|digit| digit as u8),
+        _ => None,
+    }
+}
+
+fn parse_number(token: &str) -> Option<i64> {
+    let lower = token.to_lowercase();
+    if let Some(hex) = lower.strip_prefix("0x").or_else(|| lower.strip_prefix('#')) {
+        i64::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()
+    } else {
+        lower.parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assembles_labels_constants_and_data() {
+        let source = "
+            SPEED equ 2 * 3
+            start:
+                LD V0, SPEED      ; speed of the ball
+                LD I, sprite
+                DRW V0, V0, sprite_end - sprite
+            loop: JP loop
+            sprite:
+                db 0b11110000, 0x90
+                dw #F090
+            sprite_end:
+        ";
+        let rom = assemble(source).unwrap();
+        assert_eq!(
+            rom,
+            [0x60, 0x06, 0xA2, 0x08, 0xD0, 0x04, 0x12, 0x06, 0xF0, 0x90, 0xF0, 0x90]
+        );
+    }
+
+    #[test]
+    fn reports_errors_with_line_numbers() {
+        let error = assemble("CLS\nLD V0, 256").unwrap_err();
+        assert_eq!(error.line, 2);
+        let error = assemble("JP nowhere").unwrap_err();
+        assert_eq!(error.message, "undefined symbol nowhere");
+        for source in [
+            "LD V0, 0x7FFFFFFFFFFFFFFF * 2",
+            "LD V0, (-0x7FFFFFFFFFFFFFFF - 1) / -1",
+            "LD V0, -(-0x7FFFFFFFFFFFFFFF - 1)",
+        ] {
+            let error = assemble(source).unwrap_err();
+            assert_eq!(error.message, "arithmetic overflow", "{}", source);
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use assembler::assemble_file;
+use assembler::octo;
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "Usage: chip8-asm [path_to_source] [-o path_to_rom]";
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 {
+        println!("{}", USAGE);
+        return;
+    }
+
+    let source = Path::new(&args[1]);
+    // Without -o, the ROM is written next to the source with the .ch8 extension.
+    let output = match (args.get(2).map(String::as_str), args.get(3)) {
+        (None, _) => source.with_extension("ch8"),
+        (Some("-o"), Some(path)) => PathBuf::from(path),
+        _ => {
+            println!("{}", USAGE);
+            return;
+        }
+    };
+
+    // Octo sources also get a symbol map next to the ROM.
+    let compiled = if source.extension() == Some("8o".as_ref()) {
+        octo::compile_file(source).map(|program| {
+            let symbol_map = program.symbol_map();
+            (program.rom, Some(symbol_map))
+        })
+    } else {
+        assemble_file(source).map(|rom| (rom, None))
+    };
+    let (rom, symbol_map) = compiled.unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    });
+    if let Some(symbol_map) = symbol_map {
+        let path = output.with_extension("sym");
+        std::fs::write(&path, symbol_map).unwrap_or_else(|error| {
+            eprintln!("Couldn't write {}: {}", path.display(), error);
+            std::process::exit(1);
+        });
+    }
+    std::fs::write(&output, rom).unwrap_or_else(|error| {
+        eprintln!("Couldn't write {}: {}", output.display(), error);
+        std::process::exit(1);
+    });
+}
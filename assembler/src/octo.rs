@@ -0,0 +1,1196 @@
+//! A compiler for Octo, the high level assembly language most modern CHIP-8, SUPER-CHIP and
+//! XO-CHIP programs are written in.
+
+use crate::AssemblyError;
+use chip8_core::chip8::MEMORY_START_ADDRESS;
+use chip8_core::instruction::Instruction;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+// Macros expanding into themselves would otherwise never stop.
+const MAX_MACRO_EXPANSIONS: usize = 65536;
+const FLAG_REGISTER: u8 = 0xF;
+
+/// A compiled Octo program.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OctoProgram {
+    /// The ROM, loaded at 0x200.
+    pub rom: Vec<u8>,
+    /// The address of every label in the program.
+    pub symbols: BTreeMap<String, u16>,
+}
+
+impl OctoProgram {
+    /// Lists the labels ordered by address, one `0xADDR name` pair per line.
+    pub fn symbol_map(&self) -> String {
+        let mut symbols: Vec<(&String, &u16)> = self.symbols.iter().collect();
+        symbols.sort_by_key(|(name, address)| (**address, *name));
+        let mut output = String::new();
+        for (name, address) in symbols {
+            writeln!(output, "0x{:04X} {}", address, name).unwrap();
+        }
+        output
+    }
+}
+
+/// Compiles Octo source code.
+pub fn compile(source: &str) -> Result<OctoProgram, AssemblyError> {
+    Compiler::new("<input>", source).compile()
+}
+
+/// Compiles an Octo source file (usually a `.8o` file).
+pub fn compile_file(path: &Path) -> Result<OctoProgram, AssemblyError> {
+    let name = path.display().to_string();
+    let source = fs::read_to_string(path).map_err(|error| AssemblyError {
+        file: name.clone(),
+        line: 0,
+        message: format!("couldn't read {}: {}", name, error),
+    })?;
+    Compiler::new(&name, &source).compile()
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+    text: String,
+    line: usize,
+}
+
+// How an address that wasn't known yet is patched into the ROM once it is.
+#[derive(Copy, Clone)]
+enum Fixup {
+    // The lower 12 bits of the instruction at the position.
+    Address,
+    // The 16 bit word at the position.
+    Long,
+    // The byte at the position.
+    Byte,
+    // The immediates of the two instructions emitted by `:unpack`, with the nibble to put in
+    // front of the address (or None for `:unpack long`).
+    Unpack(Option<u8>),
+}
+
+struct Reference {
+    position: usize,
+    fixup: Fixup,
+    name: String,
+    line: usize,
+}
+
+struct Macro {
+    parameters: Vec<String>,
+    body: Vec<Token>,
+    calls: usize,
+}
+
+enum Flow {
+    // Position of the jump over the `begin` block, taken when the condition doesn't hold.
+    Begin(usize),
+    // Position of the jump over the `else` block.
+    Else(usize),
+    // Address the loop starts at and positions of the jumps out of it made by `while`.
+    Loop { start: u32, exits: Vec<usize> },
+}
+
+#[derive(Copy, Clone)]
+enum Operand {
+    Register(u8),
+    Value(u8),
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+    LessEqual,
+    GreaterEqual,
+    Key,
+    NotKey,
+}
+
+impl Comparison {
+    fn negate(self) -> Self {
+        match self {
+            Comparison::Equal => Comparison::NotEqual,
+            Comparison::NotEqual => Comparison::Equal,
+            Comparison::Less => Comparison::GreaterEqual,
+            Comparison::GreaterEqual => Comparison::Less,
+            Comparison::Greater => Comparison::LessEqual,
+            Comparison::LessEqual => Comparison::Greater,
+            Comparison::Key => Comparison::NotKey,
+            Comparison::NotKey => Comparison::Key,
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Condition {
+    x: u8,
+    comparison: Comparison,
+    operand: Operand,
+}
+
+impl Condition {
+    fn negate(self) -> Self {
+        Self {
+            comparison: self.comparison.negate(),
+            ..self
+        }
+    }
+}
+
+struct Compiler {
+    file: String,
+    tokens: VecDeque<Token>,
+    last_line: usize,
+    rom: Vec<u8>,
+    here: u32,
+    labels: BTreeMap<String, u16>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    references: Vec<Reference>,
+    flow: Vec<(Flow, usize)>,
+    expansions: usize,
+}
+
+impl Compiler {
+    fn new(file: &str, source: &str) -> Self {
+        let mut tokens = VecDeque::new();
+        for (index, line) in source.lines().enumerate() {
+            let code = match line.find('#') {
+                Some(comment) => &line[..comment],
+                None => line,
+            };
+            tokens.extend(code.split_whitespace().map(|text| Token {
+                text: text.to_string(),
+                line: index + 1,
+            }));
+        }
+        Self {
+            file: file.to_string(),
+            tokens,
+            last_line: source.lines().count(),
+            rom: Vec::new(),
+            here: MEMORY_START_ADDRESS as u32,
+            labels: BTreeMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            references: Vec::new(),
+            flow: Vec::new(),
+            expansions: 0,
+        }
+    }
+
+    fn error(&self, line: usize, message: impl Into<String>) -> AssemblyError {
+        AssemblyError {
+            file: self.file.clone(),
+            line,
+            message: message.into(),
+        }
+    }
+
+    fn compile(mut self) -> Result<OctoProgram, AssemblyError> {
+        // Programs start at `main`, wherever it is.
+        self.reference("main", Fixup::Address, 1);
+        self.instruction(Instruction::Jump { addr: 0 }, 1)?;
+
+        while let Some(token) = self.tokens.pop_front() {
+            self.statement(token)?;
+        }
+        if let Some((flow, line)) = self.flow.last() {
+            let construct = match flow {
+                Flow::Begin(_) | Flow::Else(_) => "begin",
+                Flow::Loop { .. } => "loop",
+            };
+            return Err(self.error(*line, format!("this {} is never closed", construct)));
+        }
+
+        for reference in std::mem::take(&mut self.references) {
+            let address = *self.labels.get(&reference.name).ok_or_else(|| {
+                self.error(reference.line, format!("undefined name {}", reference.name))
+            })?;
+            self.patch(&reference, address)?;
+        }
+        Ok(OctoProgram {
+            rom: self.rom,
+            symbols: self.labels,
+        })
+    }
+
+    fn patch(&mut self, reference: &Reference, address: u16) -> Result<(), AssemblyError> {
+        let position = reference.position;
+        let [high, low] = address.to_be_bytes();
+        match reference.fixup {
+            Fixup::Address => {
+                if address > 0xFFF {
+                    return Err(self.error(
+                        reference.line,
+                        format!(
+                            "{} (0x{:04X}) is out of the 12 bit range",
+                            reference.name, address
+                        ),
+                    ));
+                }
+                self.rom[position] = (self.rom[position] & 0xF0) | high;
+                self.rom[position + 1] = low;
+            }
+            Fixup::Long => {
+                self.rom[position] = high;
+                self.rom[position + 1] = low;
+            }
+            Fixup::Byte => self.rom[position] = low,
+            Fixup::Unpack(nibble) => {
+                self.rom[position + 1] = match nibble {
+                    Some(nibble) => (nibble << 4) | (high & 0xF),
+                    None => high,
+                };
+                self.rom[position + 3] = low;
+            }
+        }
+        Ok(())
+    }
+
+    // Remembers that the bytes about to be emitted refer to `name`.
+    fn reference(&mut self, name: &str, fixup: Fixup, line: usize) {
+        self.references.push(Reference {
+            position: self.position(),
+            fixup,
+            name: name.to_string(),
+            line,
+        });
+    }
+
+    // Index of the next byte in the ROM.
+    fn position(&self) -> usize {
+        (self.here - MEMORY_START_ADDRESS as u32) as usize
+    }
+
+    fn emit(&mut self, byte: u8, line: usize) -> Result<(), AssemblyError> {
+        if self.here > 0xFFFF {
+            return Err(self.error(line, "the program doesn't fit in memory"));
+        }
+        let position = self.position();
+        if position >= self.rom.len() {
+            self.rom.resize(position + 1, 0);
+        }
+        self.rom[position] = byte;
+        self.here += 1;
+        Ok(())
+    }
+
+    fn instruction(&mut self, instruction: Instruction, line: usize) -> Result<(), AssemblyError> {
+        for byte in instruction.encode().to_be_bytes() {
+            self.emit(byte, line)?;
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Token, AssemblyError> {
+        self.tokens
+            .pop_front()
+            .ok_or_else(|| self.error(self.last_line, "unexpected end of file"))
+    }
+
+    fn expect(&mut self, text: &str) -> Result<Token, AssemblyError> {
+        let token = self.next()?;
+        if token.text != text {
+            return Err(self.error(
+                token.line,
+                format!("expected {}, found {}", text, token.text),
+            ));
+        }
+        Ok(token)
+    }
+
+    fn statement(&mut self, token: Token) -> Result<(), AssemblyError> {
+        let line = token.line;
+        match token.text.as_str() {
+            ":" => {
+                let name = self.name()?;
+                self.define_label(&name, self.here, line)?;
+            }
+            ":next" => {
+                let name = self.name()?;
+                self.define_label(&name, self.here + 1, line)?;
+            }
+            ":const" => {
+                let name = self.name()?;
+                let value = self.next()?;
+                let value = self.value(&value)?;
+                self.define_constant(name, value)?;
+            }
+            ":calc" => {
+                let name = self.name()?;
+                let value = self.calculation()?;
+                self.define_constant(name, value)?;
+            }
+            ":alias" => {
+                let name = self.name()?;
+                let register = self.next()?;
+                let register = self.register(&register)?;
+                self.aliases.insert(name.text, register);
+            }
+            ":macro" => self.define_macro(line)?,
+            ":byte" => {
+                let value = self.next()?;
+                let value = if value.text == "{" {
+                    self.tokens.push_front(value);
+                    self.calculation()?.floor()
+                } else {
+                    self.address_or_value(&value, Fixup::Byte)?
+                };
+                let byte = check_range(value, -128.0, 255.0)
+                    .ok_or_else(|| self.error(line, format!("{} doesn't fit in a byte", value)))?;
+                self.emit(byte as u8, line)?;
+            }
+            ":pointer" => {
+                let value = self.next()?;
+                let address = self.address_or_value(&value, Fixup::Long)?;
+                let address = check_range(address, 0.0, 0xFFFF as f64)
+                    .ok_or_else(|| self.error(line, format!("{} isn't an address", address)))?;
+                for byte in (address as u16).to_be_bytes() {
+                    self.emit(byte, line)?;
+                }
+            }
+            ":org" => {
+                let value = self.next()?;
+                let address = self.value(&value)?;
+                if !(MEMORY_START_ADDRESS as f64..=0xFFFF as f64).contains(&address) {
+                    return Err(self.error(line, format!("can't place code at {}", address)));
+                }
+                self.here = address as u32;
+            }
+            ":unpack" => {
+                let nibble = self.next()?;
+                let nibble = if nibble.text == "long" {
+                    None
+                } else {
+                    Some(self.nibble(&nibble)?)
+                };
+                let label = self.next()?;
+                let address = self.address_or_value(&label, Fixup::Unpack(nibble))? as u16;
+                let [high, low] = address.to_be_bytes();
+                let high = match nibble {
+                    Some(nibble) => (nibble << 4) | (high & 0xF),
+                    None => high,
+                };
+                self.instruction(Instruction::LoadImm { x: 0, nn: high }, line)?;
+                self.instruction(Instruction::LoadImm { x: 1, nn: low }, line)?;
+            }
+            ":call" => {
+                let target = self.next()?;
+                let addr = self.address(&target)?;
+                self.instruction(Instruction::Call { addr }, line)?;
+            }
+            ":breakpoint" => {
+                self.next()?;
+            }
+            ":monitor" => {
+                self.next()?;
+                self.next()?;
+            }
+            "return" | ";" => self.instruction(Instruction::Return, line)?,
+            "clear" => self.instruction(Instruction::Clear, line)?,
+            "hires" => self.instruction(Instruction::Hires, line)?,
+            "lores" => self.instruction(Instruction::Lores, line)?,
+            "exit" => self.instruction(Instruction::Exit, line)?,
+            "scroll-right" => self.instruction(Instruction::ScrollRight, line)?,
+            "scroll-left" => self.instruction(Instruction::ScrollLeft, line)?,
+            "audio" => self.instruction(Instruction::Audio, line)?,
+            "scroll-down" | "scroll-up" | "plane" => {
+                let value = self.next()?;
+                let n = self.nibble(&value)?;
+                let instruction = match token.text.as_str() {
+                    "scroll-down" => Instruction::ScrollDown { n },
+                    "scroll-up" => Instruction::ScrollUp { n },
+                    _ => Instruction::Plane { n },
+                };
+                self.instruction(instruction, line)?;
+            }
+            "bcd" | "saveflags" | "loadflags" => {
+                let register = self.next()?;
+                let x = self.register(&register)?;
+                let instruction = match token.text.as_str() {
+                    "bcd" => Instruction::Bcd { x },
+                    "saveflags" => Instruction::SaveFlags { x },
+                    _ => Instruction::LoadFlags { x },
+                };
+                self.instruction(instruction, line)?;
+            }
+            "save" | "load" => {
+                let register = self.next()?;
+                let x = self.register(&register)?;
+                let save = token.text == "save";
+                let instruction =
+                    if self.tokens.front().map(|token| token.text.as_str()) == Some("-") {
+                        self.next()?;
+                        let register = self.next()?;
+                        let y = self.register(&register)?;
+                        if save {
+                            Instruction::SaveRange { x, y }
+                        } else {
+                            Instruction::LoadRange { x, y }
+                        }
+                    } else if save {
+                        Instruction::Store { x }
+                    } else {
+                        Instruction::Load { x }
+                    };
+                self.instruction(instruction, line)?;
+            }
+            "sprite" => {
+                let x = self.next()?;
+                let x = self.register(&x)?;
+                let y = self.next()?;
+                let y = self.register(&y)?;
+                let n = self.next()?;
+                let n = self.nibble(&n)?;
+                self.instruction(Instruction::Draw { x, y, n }, line)?;
+            }
+            "jump" | "jump0" | "native" => {
+                let target = self.next()?;
+                let addr = self.address(&target)?;
+                let instruction = match token.text.as_str() {
+                    "jump" => Instruction::Jump { addr },
+                    "jump0" => Instruction::JumpOffset { addr },
+                    _ => Instruction::Sys { addr },
+                };
+                self.instruction(instruction, line)?;
+            }
+            "i" => self.index_statement()?,
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let register = self.next()?;
+                let x = self.register(&register)?;
+                let instruction = match token.text.as_str() {
+                    "delay" => Instruction::SetDelay { x },
+                    "buzzer" => Instruction::SetSound { x },
+                    _ => Instruction::Pitch { x },
+                };
+                self.instruction(instruction, line)?;
+            }
+            "if" => {
+                let condition = self.condition()?;
+                let keyword = self.next()?;
+                match keyword.text.as_str() {
+                    "then" => self.skip_unless(condition, line)?,
+                    "begin" => {
+                        self.skip_unless(condition.negate(), line)?;
+                        let position = self.position();
+                        self.instruction(Instruction::Jump { addr: 0 }, line)?;
+                        self.flow.push((Flow::Begin(position), line));
+                    }
+                    _ => {
+                        return Err(self.error(
+                            keyword.line,
+                            format!("expected then or begin, found {}", keyword.text),
+                        ))
+                    }
+                }
+            }
+            "else" => match self.flow.pop() {
+                Some((Flow::Begin(skip), _)) => {
+                    let position = self.position();
+                    self.instruction(Instruction::Jump { addr: 0 }, line)?;
+                    self.patch_jump(skip, line)?;
+                    self.flow.push((Flow::Else(position), line));
+                }
+                _ => return Err(self.error(line, "else without a matching begin")),
+            },
+            "end" => match self.flow.pop() {
+                Some((Flow::Begin(position), _)) | Some((Flow::Else(position), _)) => {
+                    self.patch_jump(position, line)?
+                }
+                _ => return Err(self.error(line, "end without a matching begin")),
+            },
+            "loop" => self.flow.push((
+                Flow::Loop {
+                    start: self.here,
+                    exits: Vec::new(),
+                },
+                line,
+            )),
+            "while" => {
+                let condition = self.condition()?;
+                self.skip_unless(condition.negate(), line)?;
+                let position = self.position();
+                self.instruction(Instruction::Jump { addr: 0 }, line)?;
+                match self.flow.iter_mut().rev().find_map(|(flow, _)| match flow {
+                    Flow::Loop { exits, .. } => Some(exits),
+                    _ => None,
+                }) {
+                    Some(exits) => exits.push(position),
+                    None => return Err(self.error(line, "while outside of a loop")),
+                }
+            }
+            "again" => match self.flow.pop() {
+                Some((Flow::Loop { start, exits }, _)) => {
+                    if start > 0xFFF {
+                        return Err(
+                            self.error(line, "the start of the loop is out of the 12 bit range")
+                        );
+                    }
+                    self.instruction(Instruction::Jump { addr: start as u16 }, line)?;
+                    for position in exits {
+                        self.patch_jump(position, line)?;
+                    }
+                }
+                _ => return Err(self.error(line, "again without a matching loop")),
+            },
+            _ if self.macros.contains_key(&token.text) => self.expand_macro(&token)?,
+            _ if self.parse_register(&token.text).is_some() => self.register_statement(&token)?,
+            _ => {
+                // Numbers are emitted as raw bytes, while names call the subroutine they label.
+                if let Some(value) = parse_number(&token.text) {
+                    let byte = check_range(value, -128.0, 255.0).ok_or_else(|| {
+                        self.error(line, format!("{} doesn't fit in a byte", token.text))
+                    })?;
+                    self.emit(byte as u8, line)?;
+                } else if is_identifier(&token.text) {
+                    let addr = self.address(&token)?;
+                    self.instruction(Instruction::Call { addr }, line)?;
+                } else {
+                    return Err(self.error(line, format!("unexpected {}", token.text)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // i := NNN, i := long NNNN, i := hex VX, i := bighex VX and i += VX.
+    fn index_statement(&mut self) -> Result<(), AssemblyError> {
+        let operator = self.next()?;
+        let line = operator.line;
+        match operator.text.as_str() {
+            "+=" => {
+                let register = self.next()?;
+                let x = self.register(&register)?;
+                self.instruction(Instruction::AddI { x }, line)
+            }
+            ":=" => {
+                let value = self.next()?;
+                match value.text.as_str() {
+                    "hex" | "bighex" => {
+                        let register = self.next()?;
+                        let x = self.register(&register)?;
+                        let instruction = if value.text == "hex" {
+                            Instruction::LoadFont { x }
+                        } else {
+                            Instruction::LoadBigFont { x }
+                        };
+                        self.instruction(instruction, line)
+                    }
+                    "long" => {
+                        self.instruction(Instruction::LoadLongI, line)?;
+                        let target = self.next()?;
+                        let address = self.address_or_value(&target, Fixup::Long)?;
+                        if !(0.0..=0xFFFF as f64).contains(&address) {
+                            return Err(self.error(line, format!("{} isn't an address", address)));
+                        }
+                        for byte in (address as u16).to_be_bytes() {
+                            self.emit(byte, line)?;
+                        }
+                        Ok(())
+                    }
+                    _ => {
+                        let addr = self.address(&value)?;
+                        self.instruction(Instruction::LoadI { addr }, line)
+                    }
+                }
+            }
+            _ => Err(self.error(line, format!("unexpected {} after i", operator.text))),
+        }
+    }
+
+    fn register_statement(&mut self, token: &Token) -> Result<(), AssemblyError> {
+        let x = self.register(token)?;
+        let operator = self.next()?;
+        let line = operator.line;
+        let source = self.next()?;
+        let y = self.parse_register(&source.text);
+        let instruction = match (operator.text.as_str(), y) {
+            (":=", Some(y)) => Instruction::Move { x, y },
+            (":=", None) => match source.text.as_str() {
+                "key" => Instruction::WaitKey { x },
+                "delay" => Instruction::LoadDelay { x },
+                "random" => {
+                    let mask = self.next()?;
+                    let nn = self.byte(&mask)?;
+                    Instruction::Random { x, nn }
+                }
+                _ => {
+                    let nn = self.byte(&source)?;
+                    Instruction::LoadImm { x, nn }
+                }
+            },
+            ("+=", Some(y)) => Instruction::Add { x, y },
+            ("+=", None) => {
+                let nn = self.byte(&source)?;
+                Instruction::AddImm { x, nn }
+            }
+            ("-=", Some(y)) => Instruction::Sub { x, y },
+            // There is no instruction subtracting an immediate, so its negation is added.
+            ("-=", None) => {
+                let nn = self.byte(&source)?;
+                Instruction::AddImm {
+                    x,
+                    nn: nn.wrapping_neg(),
+                }
+            }
+            ("=-", Some(y)) => Instruction::SubReverse { x, y },
+            ("|=", Some(y)) => Instruction::Or { x, y },
+            ("&=", Some(y)) => Instruction::And { x, y },
+            ("^=", Some(y)) => Instruction::Xor { x, y },
+            (">>=", Some(y)) => Instruction::ShiftRight { x, y },
+            ("<<=", Some(y)) => Instruction::ShiftLeft { x, y },
+            _ => {
+                return Err(self.error(
+                    line,
+                    format!(
+                        "invalid operation: {} {} {}",
+                        token.text, operator.text, source.text
+                    ),
+                ))
+            }
+        };
+        self.instruction(instruction, line)
+    }
+
+    fn condition(&mut self) -> Result<Condition, AssemblyError> {
+        let register = self.next()?;
+        let x = self.register(&register)?;
+        let operator = self.next()?;
+        let comparison = match operator.text.as_str() {
+            "key" => Comparison::Key,
+            "-key" => Comparison::NotKey,
+            "==" => Comparison::Equal,
+            "!=" => Comparison::NotEqual,
+            "<" => Comparison::Less,
+            ">" => Comparison::Greater,
+            "<=" => Comparison::LessEqual,
+            ">=" => Comparison::GreaterEqual,
+            _ => {
+                return Err(self.error(
+                    operator.line,
+                    format!("invalid comparison {}", operator.text),
+                ))
+            }
+        };
+        let operand = match comparison {
+            Comparison::Key | Comparison::NotKey => Operand::Value(0),
+            _ => {
+                let operand = self.next()?;
+                match self.parse_register(&operand.text) {
+                    Some(y) => Operand::Register(y),
+                    None => Operand::Value(self.byte(&operand)?),
+                }
+            }
+        };
+        Ok(Condition {
+            x,
+            comparison,
+            operand,
+        })
+    }
+
+    // Emits the instructions that skip the next one when the condition doesn't hold.
+    fn skip_unless(&mut self, condition: Condition, line: usize) -> Result<(), AssemblyError> {
+        let x = condition.x;
+        let instruction = match (condition.comparison, condition.operand) {
+            (Comparison::Equal, Operand::Value(nn)) => Instruction::SkipNeImm { x, nn },
+            (Comparison::Equal, Operand::Register(y)) => Instruction::SkipNeReg { x, y },
+            (Comparison::NotEqual, Operand::Value(nn)) => Instruction::SkipEqImm { x, nn },
+            (Comparison::NotEqual, Operand::Register(y)) => Instruction::SkipEqReg { x, y },
+            (Comparison::Key, _) => Instruction::SkipKeyNotPressed { x },
+            (Comparison::NotKey, _) => Instruction::SkipKeyPressed { x },
+            // The ordering comparisons subtract into VF, which ends up as 1 when the minuend
+            // is greater than or equal to the subtrahend.
+            (Comparison::Less, operand) | (Comparison::GreaterEqual, operand) => {
+                self.compare(Operand::Register(x), operand, line)?;
+                self.flag_skip(condition.comparison == Comparison::Less)
+            }
+            (Comparison::Greater, operand) | (Comparison::LessEqual, operand) => {
+                self.compare(operand, Operand::Register(x), line)?;
+                self.flag_skip(condition.comparison == Comparison::Greater)
+            }
+        };
+        self.instruction(instruction, line)
+    }
+
+    // Skips unless VF is 0 (`when_clear`) or 1.
+    fn flag_skip(&self, when_clear: bool) -> Instruction {
+        if when_clear {
+            Instruction::SkipNeImm {
+                x: FLAG_REGISTER,
+                nn: 0,
+            }
+        } else {
+            Instruction::SkipEqImm {
+                x: FLAG_REGISTER,
+                nn: 0,
+            }
+        }
+    }
+
+    // Sets VF to 1 when `minuend` >= `subtrahend`, and to 0 otherwise.
+    fn compare(
+        &mut self,
+        minuend: Operand,
+        subtrahend: Operand,
+        line: usize,
+    ) -> Result<(), AssemblyError> {
+        let vf = FLAG_REGISTER;
+        match (minuend, subtrahend) {
+            (minuend, Operand::Register(y)) => {
+                let load = match minuend {
+                    Operand::Register(x) => Instruction::Move { x: vf, y: x },
+                    Operand::Value(nn) => Instruction::LoadImm { x: vf, nn },
+                };
+                self.instruction(load, line)?;
+                self.instruction(Instruction::Sub { x: vf, y }, line)
+            }
+            (Operand::Register(x), Operand::Value(nn)) => {
+                self.instruction(Instruction::LoadImm { x: vf, nn }, line)?;
+                self.instruction(Instruction::SubReverse { x: vf, y: x }, line)
+            }
+            (Operand::Value(_), Operand::Value(_)) => {
+                Err(self.error(line, "comparisons need at least one register"))
+            }
+        }
+    }
+
+    fn patch_jump(&mut self, position: usize, line: usize) -> Result<(), AssemblyError> {
+        if self.here > 0xFFF {
+            return Err(self.error(line, "the end of the block is out of the 12 bit range"));
+        }
+        let [high, low] = (self.here as u16).to_be_bytes();
+        self.rom[position] = 0x10 | high;
+        self.rom[position + 1] = low;
+        Ok(())
+    }
+
+    fn define_label(
+        &mut self,
+        name: &Token,
+        address: u32,
+        line: usize,
+    ) -> Result<(), AssemblyError> {
+        self.check_undefined(name)?;
+        if address > 0xFFFF {
+            return Err(self.error(line, "the program doesn't fit in memory"));
+        }
+        self.labels.insert(name.text.clone(), address as u16);
+        Ok(())
+    }
+
+    fn define_constant(&mut self, name: Token, value: f64) -> Result<(), AssemblyError> {
+        self.check_undefined(&name)?;
+        self.constants.insert(name.text, value);
+        Ok(())
+    }
+
+    fn check_undefined(&self, name: &Token) -> Result<(), AssemblyError> {
+        if self.labels.contains_key(&name.text) || self.constants.contains_key(&name.text) {
+            return Err(self.error(name.line, format!("{} is already defined", name.text)));
+        }
+        Ok(())
+    }
+
+    fn name(&mut self) -> Result<Token, AssemblyError> {
+        let token = self.next()?;
+        if !is_identifier(&token.text) || self.parse_register(&token.text).is_some() {
+            return Err(self.error(token.line, format!("invalid name {}", token.text)));
+        }
+        Ok(token)
+    }
+
+    // :macro name parameters... { body }
+    fn define_macro(&mut self, line: usize) -> Result<(), AssemblyError> {
+        let name = self.name()?;
+        let mut parameters = Vec::new();
+        loop {
+            let token = self.next()?;
+            if token.text == "{" {
+                break;
+            }
+            parameters.push(token.text);
+        }
+        let mut body = Vec::new();
+        let mut depth = 1;
+        loop {
+            let token = self.next()?;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                break;
+            }
+            body.push(token);
+        }
+        if self.macros.contains_key(&name.text) {
+            return Err(self.error(line, format!("{} is already defined", name.text)));
+        }
+        self.macros.insert(
+            name.text,
+            Macro {
+                parameters,
+                body,
+                calls: 0,
+            },
+        );
+        Ok(())
+    }
+
+    fn expand_macro(&mut self, token: &Token) -> Result<(), AssemblyError> {
+        self.expansions += 1;
+        if self.expansions > MAX_MACRO_EXPANSIONS {
+            return Err(self.error(token.line, "too many macro expansions"));
+        }
+        let count = self.macros[&token.text].parameters.len();
+        let mut arguments = HashMap::new();
+        for index in 0..count {
+            let argument = self.next()?;
+            let parameter = self.macros[&token.text].parameters[index].clone();
+            arguments.insert(parameter, argument.text);
+        }
+
+        let definition = self.macros.get_mut(&token.text).unwrap();
+        // CALLS counts the expansions of the macro, so each one can define unique labels.
+        arguments.insert("CALLS".to_string(), definition.calls.to_string());
+        definition.calls += 1;
+        for body_token in definition.body.iter().rev() {
+            let text = arguments.get(&body_token.text).unwrap_or(&body_token.text);
+            self.tokens.push_front(Token {
+                text: text.clone(),
+                line: token.line,
+            });
+        }
+        Ok(())
+    }
+
+    // { expression }, evaluated right to left as Octo does, unless parenthesized.
+    fn calculation(&mut self) -> Result<f64, AssemblyError> {
+        let open = self.expect("{")?;
+        let mut expression = Vec::new();
+        loop {
+            let token = self.next()?;
+            if token.text == "}" {
+                break;
+            }
+            expression.push(token);
+        }
+        let mut position = 0;
+        let value = self.expression(&expression, &mut position, open.line)?;
+        if position != expression.len() {
+            return Err(self.error(open.line, "invalid expression"));
+        }
+        Ok(value)
+    }
+
+    fn expression(
+        &self,
+        tokens: &[Token],
+        position: &mut usize,
+        line: usize,
+    ) -> Result<f64, AssemblyError> {
+        let lhs = self.term(tokens, position, line)?;
+        let operator = match tokens.get(*position) {
+            Some(token) if token.text != ")" => token.text.clone(),
+            _ => return Ok(lhs),
+        };
+        *position += 1;
+        let rhs = self.expression(tokens, position, line)?;
+        let boolean = |value: bool| if value { 1.0 } else { 0.0 };
+        let value = match operator.as_str() {
+            "+" => lhs + rhs,
+            "-" => lhs - rhs,
+            "*" => lhs * rhs,
+            "/" => lhs / rhs,
+            "%" => lhs % rhs,
+            "pow" => lhs.powf(rhs),
+            "min" => lhs.min(rhs),
+            "max" => lhs.max(rhs),
+            "&" => ((lhs as i64) & (rhs as i64)) as f64,
+            "|" => ((lhs as i64) | (rhs as i64)) as f64,
+            "^" => ((lhs as i64) ^ (rhs as i64)) as f64,
+            "<<" => ((lhs as i64) << (rhs as i64 & 63)) as f64,
+            ">>" => ((lhs as i64) >> (rhs as i64 & 63)) as f64,
+            "<" => boolean(lhs < rhs),
+            ">" => boolean(lhs > rhs),
+            "<=" => boolean(lhs <= rhs),
+            ">=" => boolean(lhs >= rhs),
+            "==" => boolean(lhs == rhs),
+            "!=" => boolean(lhs != rhs),
+            _ => return Err(self.error(line, format!("invalid operator {}", operator))),
+        };
+        Ok(value)
+    }
+
+    fn term(
+        &self,
+        tokens: &[Token],
+        position: &mut usize,
+        line: usize,
+    ) -> Result<f64, AssemblyError> {
+        let token = tokens
+            .get(*position)
+            .ok_or_else(|| self.error(line, "unexpected end of expression"))?;
+        *position += 1;
+        let unary: fn(f64) -> f64 = match token.text.as_str() {
+            "(" => {
+                let value = self.expression(tokens, position, line)?;
+                match tokens.get(*position) {
+                    Some(token) if token.text == ")" => *position += 1,
+                    _ => return Err(self.error(line, "expected )")),
+                }
+                return Ok(value);
+            }
+            "HERE" => return Ok(self.here as f64),
+            "PI" => return Ok(std::f64::consts::PI),
+            "E" => return Ok(std::f64::consts::E),
+            "-" => |value| -value,
+            "~" => |value| !(value as i64) as f64,
+            "!" => |value| if value == 0.0 { 1.0 } else { 0.0 },
+            "sin" => f64::sin,
+            "cos" => f64::cos,
+            "tan" => f64::tan,
+            "exp" => f64::exp,
+            "log" => f64::ln,
+            "abs" => f64::abs,
+            "sqrt" => f64::sqrt,
+            "sign" => f64::signum,
+            "ceil" => f64::ceil,
+            "floor" => f64::floor,
+            _ => return self.value(token),
+        };
+        let value = self.term(tokens, position, line)?;
+        Ok(unary(value))
+    }
+
+    fn value(&self, token: &Token) -> Result<f64, AssemblyError> {
+        if let Some(value) = parse_number(&token.text) {
+            return Ok(value);
+        }
+        if let Some(value) = self.constants.get(&token.text) {
+            return Ok(*value);
+        }
+        if let Some(address) = self.labels.get(&token.text) {
+            return Ok(*address as f64);
+        }
+        Err(self.error(token.line, format!("undefined name {}", token.text)))
+    }
+
+    // A value that may be a label defined later on, in which case it's patched in once known.
+    fn address_or_value(&mut self, token: &Token, fixup: Fixup) -> Result<f64, AssemblyError> {
+        match self.value(token) {
+            Ok(value) => Ok(value.floor()),
+            Err(_) if is_identifier(&token.text) && self.parse_register(&token.text).is_none() => {
+                self.reference(&token.text, fixup, token.line);
+                Ok(0.0)
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    fn address(&mut self, token: &Token) -> Result<u16, AssemblyError> {
+        let address = self.address_or_value(token, Fixup::Address)?;
+        check_range(address, 0.0, 0xFFF as f64)
+            .map(|address| address as u16)
+            .ok_or_else(|| {
+                self.error(
+                    token.line,
+                    format!("{} is out of the 12 bit range", token.text),
+                )
+            })
+    }
+
+    fn byte(&self, token: &Token) -> Result<u8, AssemblyError> {
+        let value = self.value(token)?.floor();
+        check_range(value, -128.0, 255.0)
+            .map(|value| value as i64 as u8)
+            .ok_or_else(|| self.error(token.line, format!("{} doesn't fit in a byte", token.text)))
+    }
+
+    fn nibble(&self, token: &Token) -> Result<u8, AssemblyError> {
+        let value = self.value(token)?.floor();
+        check_range(value, 0.0, 15.0)
+            .map(|value| value as u8)
+            .ok_or_else(|| {
+                self.error(
+                    token.line,
+                    format!("{} doesn't fit in a nibble", token.text),
+                )
+            })
+    }
+
+    fn register(&self, token: &Token) -> Result<u8, AssemblyError> {
+        self.parse_register(&token.text).ok_or_else(|| {
+            self.error(
+                token.line,
+                format!("expected a register, found {}", token.text),
+            )
+        })
+    }
+
+    fn parse_register(&self, text: &str) -> Option<u8> {
+        if let Some(register) = self.aliases.get(text) {
+            return Some(*register);
+        }
+        let mut characters = text.chars();
+        match (characters.next(), characters.next(), characters.next()) {
+            (Some('v' | 'V'), Some(digit), None) => digit.to_digit(16).map(|digit| digit as u8),
+            _ => None,
+        }
+    }
+}
+
+fn check_range(value: f64, min: f64, max: f64) -> Option<f64> {
+    if value < min || value > max {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut characters = text.chars();
+    match characters.next() {
+        Some(first) if first.is_ascii_alphabetic() || first == '_' => {}
+        _ => return false,
+    }
+    characters.all(|character| character.is_ascii_alphanumeric() || "_-.".contains(character))
+}
+
+fn parse_number(text: &str) -> Option<f64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()? as f64
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()? as f64
+    } else if digits.starts_with(|character: char| character.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compiles_labels_and_forward_references() {
+        let program = compile(
+            ": sprite 0xF0 0x90
+             : main
+                i := sprite
+                v0 := 10
+                v1 += -1
+                sprite v0 v1 2
+                draw
+                jump main
+             : draw ;",
+        )
+        .unwrap();
+        assert_eq!(
+            program.rom,
+            [
+                0x12, 0x04, 0xF0, 0x90, 0xA2, 0x02, 0x60, 0x0A, 0x71, 0xFF, 0xD0, 0x12, 0x22, 0x10,
+                0x12, 0x04, 0x00, 0xEE
+            ]
+        );
+        assert_eq!(
+            program.symbol_map(),
+            "0x0202 sprite\n0x0204 main\n0x0210 draw\n"
+        );
+    }
+
+    #[test]
+    fn compiles_control_flow() {
+        let program = compile(
+            ": main
+                loop
+                    while v0 != 5
+                    if v1 key then v2 += 1
+                    if v0 < 3 begin v3 := 1 else v3 := 2 end
+                    v0 += 1
+                again",
+        )
+        .unwrap();
+        let expected = [
+            0x12, 0x02, // jump main
+            0x40, 0x05, // while v0 != 5: skip the exit while v0 != 5
+            0x12, 0x1C, // exit the loop
+            0xE1, 0xA1, // if v1 key then
+            0x72, 0x01, // v2 += 1
+            0x6F, 0x03, // vf := 3
+            0x8F, 0x07, // vf =- v0, so VF is 0 when v0 < 3
+            0x3F, 0x00, // skip the jump to else when v0 < 3
+            0x12, 0x16, // jump to else
+            0x63, 0x01, // v3 := 1
+            0x12, 0x18, // jump to end
+            0x63, 0x02, // v3 := 2
+            0x70, 0x01, // v0 += 1
+            0x12, 0x02, // again
+        ];
+        assert_eq!(program.rom, expected);
+    }
+
+    #[test]
+    fn expands_macros_constants_and_calculations() {
+        let program = compile(
+            ":const SPEED 3
+             :alias ball-x v4
+             :calc DOUBLE { SPEED * 2 }
+             :macro move register amount { register += amount }
+             : main
+                move ball-x DOUBLE
+                :byte { 1 + 2 * 3 }
+                :unpack 0xA main
+                hires plane 3 audio pitch := v1
+                i := long main",
+        )
+        .unwrap();
+        assert_eq!(
+            program.rom,
+            [
+                0x12, 0x02, 0x74, 0x06, 0x07, 0x60, 0xA2, 0x61, 0x02, 0x00, 0xFF, 0xF3, 0x01, 0xF0,
+                0x02, 0xF1, 0x3A, 0xF0, 0x00, 0x02, 0x02
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_errors() {
+        let error = compile(": main\n  v0 := 256").unwrap_err();
+        assert_eq!(error.line, 2);
+        let error = compile(": main\n  jump nowhere").unwrap_err();
+        assert_eq!(error.message, "undefined name nowhere");
+        let error = compile(": main\n  loop").unwrap_err();
+        assert_eq!(error.message, "this loop is never closed");
+        // JP only reaches the first 4 KiB.
+        let error = compile(": main :org 0x1000 loop v0 += 1 again").unwrap_err();
+        assert_eq!(
+            error.message,
+            "the start of the loop is out of the 12 bit range"
+        );
+        let error = compile(": main\n  :pointer 65536").unwrap_err();
+        assert_eq!(
+            (error.line, error.message.as_str()),
+            (2, "65536 isn't an address")
+        );
+        let error = compile(": main :pointer -1").unwrap_err();
+        assert_eq!(error.message, "-1 isn't an address");
+    }
+}
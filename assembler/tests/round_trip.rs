@@ -0,0 +1,74 @@
+use assembler::{assemble, assemble_file};
+use chip8_core::platform::Platform;
+use disasm::disassemble;
+
+const PLATFORMS: [Platform; 3] = [Platform::Chip8, Platform::SuperChip, Platform::XoChip];
+
+fn assert_round_trip(rom: &[u8], platform: Platform) {
+    let source = disassemble(rom, platform).unwrap();
+    let assembled =
+        assemble(&source).unwrap_or_else(|error| panic!("{:?}: {}\n{}", platform, error, source));
+    assert_eq!(assembled, rom, "{:?}\n{}", platform, source);
+}
+
+// Deterministic pseudo-random bytes, so failures can be reproduced.
+fn pseudo_random_rom(seed: u64, length: usize) -> Vec<u8> {
+    let mut state = seed;
+    (0..length)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 56) as u8
+        })
+        .collect()
+}
+
+#[test]
+fn round_trips_handwritten_programs() {
+    let roms: [&[u8]; 3] = [
+        // LD I, sprite; DRW V0, V1, 5; CALL sub; JP loop; sub: SHR V2; RET; sprite
+        &[
+            0xA2, 0x0C, 0xD0, 0x15, 0x22, 0x08, 0x12, 0x06, 0x82, 0x26, 0x00, 0xEE, 0xF0, 0x90,
+            0xF0, 0x90, 0xF0,
+        ],
+        // HIGH; SCD 4; LD HF, V3; LD R, V5; EXIT
+        &[0x00, 0xFF, 0x00, 0xC4, 0xF3, 0x30, 0xF5, 0x75, 0x00, 0xFD],
+        // SE V0, 1; LD I, LONG 0x0208; PLANE 3; SAVE V1 - V4; AUDIO; data
+        &[
+            0x30, 0x01, 0xF0, 0x00, 0x02, 0x0E, 0xF3, 0x01, 0x51, 0x42, 0xF0, 0x02, 0x12, 0x0C,
+            0xAA, 0x55,
+        ],
+    ];
+    for rom in roms {
+        for platform in PLATFORMS {
+            assert_round_trip(rom, platform);
+        }
+    }
+}
+
+#[test]
+fn round_trips_pseudo_random_roms() {
+    for seed in 0..200 {
+        let rom = pseudo_random_rom(seed, 64 + seed as usize);
+        for platform in PLATFORMS {
+            assert_round_trip(&rom, platform);
+        }
+    }
+}
+
+#[test]
+fn assembles_included_files() {
+    let directory = std::env::temp_dir().join(format!("chip8-asm-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    std::fs::write(directory.join("sprites.asm"), "ball: db 0x80\n").unwrap();
+    std::fs::write(
+        directory.join("main.asm"),
+        "LD I, ball\nJP 0x200\ninclude \"sprites.asm\"\n",
+    )
+    .unwrap();
+
+    let rom = assemble_file(&directory.join("main.asm"));
+    std::fs::remove_dir_all(&directory).unwrap();
+    assert_eq!(rom.unwrap(), [0xA2, 0x04, 0x12, 0x00, 0x80]);
+}
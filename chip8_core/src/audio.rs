@@ -0,0 +1,42 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Number of bytes in an XO-CHIP audio pattern. Each bit is one sample, so a pattern holds 128
+/// samples.
+pub const AUDIO_PATTERN_SIZE: usize = 16;
+/// The pitch XO-CHIP starts with. It plays the pattern at 4000 samples per second.
+pub const DEFAULT_PITCH: u8 = 64;
+
+/// The XO-CHIP audio state: a 1-bit sample buffer loaded by F002 and the pitch set by FX3A.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AudioPattern {
+    pub pattern: [u8; AUDIO_PATTERN_SIZE],
+    pub pitch: u8,
+}
+
+impl AudioPattern {
+    /// Number of samples (bits) of the pattern played per second.
+    pub fn playback_rate(&self) -> f32 {
+        // `libm` rather than `f32::powf`, which needs the standard library.
+        4000.0 * libm::exp2f((self.pitch as f32 - 64.0) / 48.0)
+    }
+
+    /// Returns whether the sample at `index` (wrapped to the pattern length) is set.
+    pub fn sample(&self, index: usize) -> bool {
+        let index = index % (AUDIO_PATTERN_SIZE * 8);
+        (self.pattern[index / 8] >> (7 - index % 8)) & 1 == 1
+    }
+}
+
+/// Where a frontend plays the buzzer. `Runner` calls it once per frame, after the timers tick.
+pub trait AudioSink {
+    /// Turns the buzzer on or off. `pattern` is the XO-CHIP pattern to play instead of the
+    /// frontend's own tone, if the ROM loaded one.
+    fn set_buzzer(&mut self, on: bool, pattern: Option<AudioPattern>);
+}
+
+/// Plays nothing.
+impl AudioSink for () {
+    fn set_buzzer(&mut self, _on: bool, _pattern: Option<AudioPattern>) {}
+}
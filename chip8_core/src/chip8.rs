@@ -1,21 +1,54 @@
-use rand::distributions::Uniform;
-use rand::Rng;
-use serde::de::{Error, MapAccess, Visitor};
+#[cfg(feature = "serde")]
+use serde::de::{Error, MapAccess, Unexpected, Visitor};
+#[cfg(feature = "serde")]
 use serde::ser::SerializeStruct;
+#[cfg(feature = "serde")]
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
-use std::fmt;
-use std::fmt::Formatter;
+use alloc::boxed::Box;
+#[cfg(feature = "serde")]
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+#[cfg(feature = "serde")]
+use core::fmt;
+#[cfg(feature = "serde")]
+use core::fmt::Formatter;
+use core::ops::Range;
+#[cfg(feature = "json")]
 use std::fs;
-use std::prelude::rust_2015::Result::Err;
 
-const MEMORY_SIZE: usize = 4096;
+use crate::audio::{AudioPattern, AUDIO_PATTERN_SIZE, DEFAULT_PITCH};
+use crate::debugger::Access;
+use crate::display::{DirtyRect, Framebuffer};
+use crate::error::Chip8Error;
+use crate::instruction::Instruction;
+use crate::platform::Platform;
+use crate::quirks::Quirks;
+use crate::random::{RandomSource, SplitMix64};
+use crate::trace::{Registers, TraceEvent, Tracer, TracerSlot};
+
 const STACK_SIZE: usize = 16;
 const NUMBER_OF_REGISTERS: usize = 16;
 const FONT_SIZE: usize = 80;
+const BIG_FONT_ADDRESS: usize = FONT_SIZE;
+const BIG_FONT_SIZE: usize = 160;
 pub const SCREEN_WIDTH: usize = 64;
 pub const SCREEN_HEIGHT: usize = 32;
-const MEMORY_START_ADDRESS: u16 = 512;
+pub const HIRES_SCREEN_WIDTH: usize = 128;
+pub const HIRES_SCREEN_HEIGHT: usize = 64;
+const NUMBER_OF_FLAGS: usize = 16;
+pub const MEMORY_START_ADDRESS: u16 = 512;
 const NUMBER_OF_KEYS: usize = 16;
+// The whole screen buffer, as a dirty rectangle of the high resolution mode.
+const WHOLE_SCREEN: DirtyRect = DirtyRect {
+    x: 0,
+    y: 0,
+    width: HIRES_SCREEN_WIDTH,
+    height: HIRES_SCREEN_HEIGHT,
+};
+/// The speed ROMs run at unless told otherwise: 8 instructions per frame at 60 frames per
+/// second.
+pub const DEFAULT_INSTRUCTIONS_PER_SECOND: u32 = 480;
 const FONTS: [u8; FONT_SIZE] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
     0x20, 0x60, 0x20, 0x20, 0x70, // 1
@@ -34,10 +67,56 @@ const FONTS: [u8; FONT_SIZE] = [
     0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
+// SUPER-CHIP 8x10 digits, used by FX30.
+const BIG_FONTS: [u8; BIG_FONT_SIZE] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+/// What happened during a frame run by `Chip8::run_frame`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FrameReport {
+    /// Instructions executed. Fewer than due when the CPU stalls or an instruction fails.
+    pub instructions: u32,
+    /// Whether an instruction drew, cleared or scrolled the screen, or switched resolution.
+    pub screen_changed: bool,
+    /// Whether the buzzer sounds until the next frame. Always off when the timers didn't tick.
+    pub buzzer: bool,
+    /// Whether the program is waiting for a key with FX0A.
+    pub waiting_for_key: bool,
+    /// The error of the instruction that failed, if one did.
+    pub error: Option<Chip8Error>,
+}
+
+/// What `Chip8::run_frame_with` does after a step.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Step {
+    /// Goes on with the next instruction of the frame.
+    Continue,
+    /// Skips the rest of the instructions of the frame, but still ticks the timers.
+    EndFrame,
+    /// Leaves the frame where it is, without ticking the timers.
+    Abort,
+}
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub struct Chip8 {
-    memory: [u8; MEMORY_SIZE],
+    // Sized according to the platform: 4 KiB, or 64 KiB on XO-CHIP.
+    memory: Vec<u8>,
     i: u16,
     stack: [u16; STACK_SIZE],
     pc: u16,
@@ -46,17 +125,50 @@ pub struct Chip8 {
     sound_timer: u8,
     keys: [bool; NUMBER_OF_KEYS],
     v: [u8; NUMBER_OF_REGISTERS],
-    screen: [bool; SCREEN_WIDTH * SCREEN_HEIGHT],
+    // Sized for the high resolution mode. In low resolution only the first
+    // SCREEN_WIDTH * SCREEN_HEIGHT pixels are used. Each pixel holds one bit per
+    // bitplane: bit 0 for the first plane and bit 1 for the second (XO-CHIP only).
+    screen: [u8; HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT],
     fps: u32,
+    // Instructions executed by `run_frame`, spread evenly over the frames of a second.
+    instructions_per_second: u32,
+    // Instructions owed to the next frames, when the speed isn't a multiple of the frame rate.
+    instruction_credit: u32,
+    quirks: Quirks,
+    platform: Platform,
+    hires: bool,
+    // Set by 00FD. A halted interpreter doesn't execute any more instructions.
+    halted: bool,
+    // SUPER-CHIP RPL user flags, written by FX75 and read by FX85.
+    flags: [u8; NUMBER_OF_FLAGS],
+    // Bitmask of the planes affected by 00E0, DXYN and the scroll instructions (FN01).
+    planes: u8,
+    // XO-CHIP audio buffer loaded by F002. None until a ROM loads one.
+    audio_pattern: Option<[u8; AUDIO_PATTERN_SIZE]>,
+    pitch: u8,
+    // Set by DXYN when the display wait quirk is enabled. The CPU stalls until the next timer tick.
+    waiting_for_vblank: bool,
+    // Set while FX0A finds no key pressed, so it runs again until one is.
+    waiting_for_key: bool,
+    // Set whenever an instruction changes the screen. Cleared at the start of every frame.
+    screen_changed: bool,
+    // What changed on the screen since `clear_dirty`. New machines start with the whole screen.
+    dirty: Option<DirtyRect>,
+    dirty_rows: u64,
+    // Used by CXNN. Its state is part of save states, so loading one replays the same numbers.
+    rng: Box<dyn RandomSource>,
+    // Observes every executed instruction. Not part of save states, and clones start without one.
+    tracer: TracerSlot,
 }
 
+#[cfg(feature = "serde")]
 impl Serialize for Chip8 {
     fn serialize<S>(&self, serializer: S) -> Result<<S as Serializer>::Ok, <S as Serializer>::Error>
     where
         S: Serializer,
     {
         let mut state = serializer
-            .serialize_struct("Chip8", 11)
+            .serialize_struct("Chip8", 21)
             .expect("Couldn't serialize Chip8.");
         state.serialize_field("memory", &self.memory.to_vec())?;
         state.serialize_field("stack", &self.stack.to_vec())?;
@@ -67,13 +179,24 @@ impl Serialize for Chip8 {
         state.serialize_field("pc", &self.pc)?;
         state.serialize_field("sp", &self.sp)?;
         state.serialize_field("fps", &self.fps)?;
+        state.serialize_field("instructions_per_second", &self.instructions_per_second)?;
         state.serialize_field("sound_timer", &self.sound_timer)?;
         state.serialize_field("delay_timer", &self.delay_timer)?;
+        state.serialize_field("quirks", &self.quirks)?;
+        state.serialize_field("platform", &self.platform)?;
+        state.serialize_field("hires", &self.hires)?;
+        state.serialize_field("halted", &self.halted)?;
+        state.serialize_field("flags", &self.flags.to_vec())?;
+        state.serialize_field("planes", &self.planes)?;
+        state.serialize_field("audio_pattern", &self.audio_pattern)?;
+        state.serialize_field("pitch", &self.pitch)?;
+        state.serialize_field("rng_state", &self.rng.state())?;
 
         state.end()
     }
 }
 
+#[cfg(feature = "serde")]
 impl<'de> Deserialize<'de> for Chip8 {
     fn deserialize<D>(deserializer: D) -> Result<Self, <D as Deserializer<'de>>::Error>
     where
@@ -89,8 +212,18 @@ impl<'de> Deserialize<'de> for Chip8 {
             Pc,
             Sp,
             Fps,
+            InstructionsPerSecond,
             SoundTimer,
             DelayTimer,
+            Quirks,
+            Platform,
+            Hires,
+            Halted,
+            Flags,
+            Planes,
+            AudioPattern,
+            Pitch,
+            RngState,
         }
 
         impl<'de> Deserialize<'de> for Field {
@@ -111,6 +244,7 @@ impl<'de> Deserialize<'de> for Chip8 {
                             "pc" => Ok(Field::Pc),
                             "i" => Ok(Field::I),
                             "fps" => Ok(Field::Fps),
+                            "instructions_per_second" => Ok(Field::InstructionsPerSecond),
                             "sp" => Ok(Field::Sp),
                             "delay_timer" => Ok(Field::DelayTimer),
                             "sound_timer" => Ok(Field::SoundTimer),
@@ -119,6 +253,15 @@ impl<'de> Deserialize<'de> for Chip8 {
                             "memory" => Ok(Field::Memory),
                             "screen" => Ok(Field::Screen),
                             "keys" => Ok(Field::Keys),
+                            "quirks" => Ok(Field::Quirks),
+                            "platform" => Ok(Field::Platform),
+                            "hires" => Ok(Field::Hires),
+                            "halted" => Ok(Field::Halted),
+                            "flags" => Ok(Field::Flags),
+                            "planes" => Ok(Field::Planes),
+                            "audio_pattern" => Ok(Field::AudioPattern),
+                            "pitch" => Ok(Field::Pitch),
+                            "rng_state" => Ok(Field::RngState),
                             _ => Err(de::Error::unknown_field(v, FIELDS)),
                         }
                     }
@@ -152,8 +295,18 @@ impl<'de> Deserialize<'de> for Chip8 {
                 let mut pc = None;
                 let mut sp = None;
                 let mut fps = None;
+                let mut instructions_per_second = None;
                 let mut sound_timer = None;
                 let mut delay_timer = None;
+                let mut quirks = None;
+                let mut platform = None;
+                let mut hires = None;
+                let mut halted = None;
+                let mut flags = None;
+                let mut planes = None;
+                let mut audio_pattern = None;
+                let mut pitch = None;
+                let mut rng_state = None;
                 while let Some(key) = map.next_key()? {
                     match key {
                         Field::Memory => {
@@ -222,20 +375,121 @@ impl<'de> Deserialize<'de> for Chip8 {
                             }
                             fps = Some(map.next_value()?);
                         }
+                        Field::InstructionsPerSecond => {
+                            if instructions_per_second.is_some() {
+                                return Err(de::Error::duplicate_field("instructions_per_second"));
+                            }
+                            instructions_per_second = Some(map.next_value()?);
+                        }
+                        Field::Quirks => {
+                            if quirks.is_some() {
+                                return Err(de::Error::duplicate_field("quirks"));
+                            }
+                            quirks = Some(map.next_value()?);
+                        }
+                        Field::Platform => {
+                            if platform.is_some() {
+                                return Err(de::Error::duplicate_field("platform"));
+                            }
+                            platform = Some(map.next_value()?);
+                        }
+                        Field::Hires => {
+                            if hires.is_some() {
+                                return Err(de::Error::duplicate_field("hires"));
+                            }
+                            hires = Some(map.next_value()?);
+                        }
+                        Field::Halted => {
+                            if halted.is_some() {
+                                return Err(de::Error::duplicate_field("halted"));
+                            }
+                            halted = Some(map.next_value()?);
+                        }
+                        Field::Flags => {
+                            if flags.is_some() {
+                                return Err(de::Error::duplicate_field("flags"));
+                            }
+                            flags = Some(map.next_value()?);
+                        }
+                        Field::Planes => {
+                            if planes.is_some() {
+                                return Err(de::Error::duplicate_field("planes"));
+                            }
+                            planes = Some(map.next_value()?);
+                        }
+                        Field::AudioPattern => {
+                            if audio_pattern.is_some() {
+                                return Err(de::Error::duplicate_field("audio_pattern"));
+                            }
+                            audio_pattern = Some(map.next_value()?);
+                        }
+                        Field::Pitch => {
+                            if pitch.is_some() {
+                                return Err(de::Error::duplicate_field("pitch"));
+                            }
+                            pitch = Some(map.next_value()?);
+                        }
+                        Field::RngState => {
+                            if rng_state.is_some() {
+                                return Err(de::Error::duplicate_field("rng_state"));
+                            }
+                            rng_state = Some(map.next_value()?);
+                        }
                     }
                 }
 
                 let memory = memory.ok_or_else(|| de::Error::missing_field("memory"))?;
-                let stack = stack.ok_or_else(|| de::Error::missing_field("stack"))?;
-                let v = v.ok_or_else(|| de::Error::missing_field("v"))?;
-                let keys = keys.ok_or_else(|| de::Error::missing_field("keys"))?;
-                let screen = screen.ok_or_else(|| de::Error::missing_field("screen"))?;
+                let stack: Vec<u16> = stack.ok_or_else(|| de::Error::missing_field("stack"))?;
+                let v: Vec<u8> = v.ok_or_else(|| de::Error::missing_field("v"))?;
+                let keys: Vec<bool> = keys.ok_or_else(|| de::Error::missing_field("keys"))?;
+                let screen: Vec<u8> = screen.ok_or_else(|| de::Error::missing_field("screen"))?;
                 let i = i.ok_or_else(|| de::Error::missing_field("i"))?;
                 let pc = pc.ok_or_else(|| de::Error::missing_field("pc"))?;
                 let sp = sp.ok_or_else(|| de::Error::missing_field("sp"))?;
                 let fps = fps.ok_or_else(|| de::Error::missing_field("fps"))?;
+                // Save states created before the speed was configurable ran at the default one.
+                let instructions_per_second = instructions_per_second.unwrap_or(DEFAULT_INSTRUCTIONS_PER_SECOND);
                 let sound_timer = sound_timer.ok_or_else(|| de::Error::missing_field("sound_timer"))?;
                 let delay_timer = delay_timer.ok_or_else(|| de::Error::missing_field("delay_timer"))?;
+                // Save states created before quirks existed were always run with the default profile.
+                let quirks = quirks.unwrap_or_default();
+                // The same goes for the SUPER-CHIP state; those save states belong to a CHIP-8 in
+                // low resolution mode.
+                let platform = platform.unwrap_or_default();
+                let hires = hires.unwrap_or_default();
+                let halted = halted.unwrap_or_default();
+                let flags = flags.unwrap_or_default();
+                let planes = planes.unwrap_or(1);
+                let audio_pattern = audio_pattern.unwrap_or_default();
+                let pitch = pitch.unwrap_or(DEFAULT_PITCH);
+                let rng_state = rng_state.unwrap_or_else(|| SplitMix64::default().state());
+
+                // Save states are files users can edit or corrupt. Anything that doesn't fit the
+                // CPU is rejected here, as it would make loading or running the state panic.
+                expect_length(stack.len(), STACK_SIZE)?;
+                expect_length(v.len(), NUMBER_OF_REGISTERS)?;
+                expect_length(keys.len(), NUMBER_OF_KEYS)?;
+                if sp as usize > STACK_SIZE {
+                    return Err(de::Error::invalid_value(
+                        Unexpected::Unsigned(sp as u64),
+                        &"a stack pointer from 0 to 16",
+                    ));
+                }
+                if fps == 0 {
+                    return Err(de::Error::invalid_value(Unexpected::Unsigned(0), &"a positive frame rate"));
+                }
+                if planes > 0b11 {
+                    return Err(de::Error::invalid_value(
+                        Unexpected::Unsigned(planes as u64),
+                        &"a bitmask of the two planes",
+                    ));
+                }
+                if let Some(pixel) = screen.iter().find(|pixel| **pixel > 0b11) {
+                    return Err(de::Error::invalid_value(
+                        Unexpected::Unsigned(*pixel as u64),
+                        &"a pixel made of two planes",
+                    ));
+                }
 
                 Ok(Chip8::from_deserialized_data(
                     memory,
@@ -249,11 +503,29 @@ impl<'de> Deserialize<'de> for Chip8 {
                     v,
                     screen,
                     fps,
+                    instructions_per_second,
+                    quirks,
+                    platform,
+                    hires,
+                    halted,
+                    flags,
+                    planes,
+                    audio_pattern,
+                    pitch,
+                    rng_state,
                 ))
             }
         }
 
-        const FIELDS: &'static [&'static str] = &[
+        fn expect_length<E: de::Error>(length: usize, expected: usize) -> Result<(), E> {
+            if length == expected {
+                Ok(())
+            } else {
+                Err(E::invalid_length(length, &format!("{} values", expected).as_str()))
+            }
+        }
+
+        const FIELDS: &[&str] = &[
             "memory",
             "stack",
             "v",
@@ -265,15 +537,39 @@ impl<'de> Deserialize<'de> for Chip8 {
             "sound_timer",
             "delay_timer",
             "fps",
+            "instructions_per_second",
+            "quirks",
+            "platform",
+            "hires",
+            "halted",
+            "flags",
+            "planes",
+            "audio_pattern",
+            "pitch",
+            "rng_state",
         ];
         deserializer.deserialize_struct("Chip8", FIELDS, Chip8Visitor)
     }
 }
 
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Chip8 {
     pub fn new() -> Self {
+        Self::with_quirks(Quirks::default())
+    }
+
+    pub fn with_quirks(quirks: Quirks) -> Self {
+        Self::with_platform(Platform::Chip8, quirks)
+    }
+
+    pub fn with_platform(platform: Platform, quirks: Quirks) -> Self {
         let mut new_chip8 = Self {
-            memory: [0; MEMORY_SIZE],
+            memory: vec![0; platform.memory_size()],
             i: 0,
             stack: [0; STACK_SIZE],
             pc: MEMORY_START_ADDRESS,
@@ -282,47 +578,310 @@ impl Chip8 {
             sound_timer: 0,
             keys: [false; NUMBER_OF_KEYS],
             v: [0; NUMBER_OF_REGISTERS],
-            screen: [false; SCREEN_WIDTH * SCREEN_HEIGHT],
+            screen: [0; HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT],
             fps: 60,
+            instructions_per_second: DEFAULT_INSTRUCTIONS_PER_SECOND,
+            instruction_credit: 0,
+            quirks,
+            waiting_for_vblank: false,
+            waiting_for_key: false,
+            screen_changed: false,
+            dirty: Some(WHOLE_SCREEN),
+            dirty_rows: u64::MAX,
+            platform,
+            hires: false,
+            halted: false,
+            flags: [0; NUMBER_OF_FLAGS],
+            planes: 1,
+            audio_pattern: None,
+            pitch: DEFAULT_PITCH,
+            rng: Box::new(SplitMix64::default()),
+            tracer: TracerSlot::default(),
         };
         new_chip8.memory[..FONT_SIZE].copy_from_slice(&FONTS);
+        new_chip8.memory[BIG_FONT_ADDRESS..BIG_FONT_ADDRESS + BIG_FONT_SIZE].copy_from_slice(&BIG_FONTS);
         new_chip8
     }
 
-    pub fn load_file(&mut self, data: &[u8]) {
+    pub fn load_file(&mut self, data: &[u8]) -> Result<(), Chip8Error> {
         let start = MEMORY_START_ADDRESS as usize;
         let end = (MEMORY_START_ADDRESS as usize) + data.len();
-        self.memory[start..end].copy_from_slice(&data);
+        if end > self.memory.len() {
+            return Err(Chip8Error::RomTooLarge {
+                size: data.len(),
+                max: self.memory.len() - start,
+            });
+        }
+        self.memory[start..end].copy_from_slice(data);
+        Ok(())
     }
 
+    #[cfg(feature = "json")]
     pub fn save_state(&self, rom_name: &str) {
         let serialized_state = serde_json::to_string(&self).expect("Couldn't serialize the current state of the CPU.");
         fs::write(format!("{}.json", rom_name),serialized_state).expect("Couldn't create save state!");
     }
 
+    #[cfg(feature = "json")]
     pub fn load_state(&self, rom_name: &str) -> Option<Self> {
         let serialized_state = fs::read_to_string(format!("{}.json", rom_name));
         if serialized_state.is_err() {
             return None;
         }
-        let cpu: Chip8 = serde_json::from_str(&serialized_state.unwrap()).unwrap_or_else(|_| *self);
+        let mut cpu: Chip8 = serde_json::from_str(&serialized_state.unwrap()).unwrap_or_else(|_| self.clone());
+        // Keeps using the same kind of random source, picking up where the save state left it.
+        let mut rng = self.rng.clone();
+        rng.set_state(cpu.rng.state());
+        cpu.rng = rng;
         Some(cpu)
     }
 
-    pub fn tick(&mut self) {
-        let opcode = self.fetch();
-        self.decode(opcode);
+    pub fn tick(&mut self) -> Result<(), Chip8Error> {
+        if self.waiting_for_vblank || self.halted {
+            return Ok(());
+        }
+        let opcode = self.fetch()?;
+        self.decode(opcode)
+    }
+
+    /// Runs a frame: executes the instructions due at the configured speed, then ticks the
+    /// timers. When an instruction fails, the frame stops there and the timers don't tick.
+    pub fn run_frame(&mut self) -> FrameReport {
+        self.run_frame_with(|chip8| chip8.tick().map(|()| Step::Continue))
+    }
+
+    /// Runs a frame like `run_frame`, executing each instruction with `step` instead of `tick`,
+    /// e.g. through a debugger. `step` is called for every instruction due, even while the CPU
+    /// is stalled, and tells how the frame goes on.
+    pub fn run_frame_with(
+        &mut self,
+        mut step: impl FnMut(&mut Chip8) -> Result<Step, Chip8Error>,
+    ) -> FrameReport {
+        let mut report = FrameReport::default();
+        self.screen_changed = false;
+        let due = self.instruction_credit as u64 + self.instructions_per_second as u64;
+        let instructions = due / self.fps as u64;
+        self.instruction_credit = (due % self.fps as u64) as u32;
+        let mut tick_timers = true;
+        for _ in 0..instructions {
+            // Nothing is executed until the next timer tick, or ever again once halted.
+            let stalled = self.waiting_for_vblank || self.halted;
+            match step(self) {
+                Ok(Step::Continue) => report.instructions += !stalled as u32,
+                Ok(Step::EndFrame) => break,
+                Ok(Step::Abort) => {
+                    tick_timers = false;
+                    break;
+                }
+                Err(error) => {
+                    report.error = Some(error);
+                    tick_timers = false;
+                    break;
+                }
+            }
+        }
+        if tick_timers {
+            report.buzzer = self.tick_timers();
+        }
+        report.screen_changed = self.screen_changed;
+        report.waiting_for_key = self.waiting_for_key;
+        report
+    }
+
+    /// Returns the whole screen buffer. Rows are `get_resolution().0` pixels wide, so in low
+    /// resolution mode only the first SCREEN_WIDTH * SCREEN_HEIGHT pixels are meaningful.
+    /// Each pixel is a palette index from 0 to 3, made of one bit per bitplane.
+    pub fn get_display(&self) -> &[u8; HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT] {
+        &self.screen
+    }
+
+    /// Borrows the screen of the active resolution, with what changed since `clear_dirty`.
+    pub fn get_framebuffer(&self) -> Framebuffer<'_> {
+        let (width, height) = self.get_resolution();
+        // Changes made before switching to low resolution may lie past its edges.
+        let dirty = self.dirty.map(|dirty| DirtyRect {
+            width: dirty.width.min(width - dirty.x.min(width)),
+            height: dirty.height.min(height - dirty.y.min(height)),
+            ..dirty
+        });
+        Framebuffer {
+            pixels: &self.screen[..width * height],
+            width,
+            height,
+            dirty: dirty.filter(|dirty| dirty.width > 0 && dirty.height > 0),
+            dirty_rows: self.dirty_rows & Self::rows_mask(0, height),
+        }
+    }
+
+    /// Forgets what changed on the screen, once a frontend has drawn it.
+    pub fn clear_dirty(&mut self) {
+        self.dirty = None;
+        self.dirty_rows = 0;
+    }
+
+    /// Marks the whole screen as changed, e.g. when a frontend has to draw it all again.
+    pub fn invalidate_screen(&mut self) {
+        self.dirty = Some(WHOLE_SCREEN);
+        self.dirty_rows = u64::MAX;
+    }
+
+    /// Returns the XO-CHIP audio pattern and pitch, if the ROM loaded a pattern with F002.
+    /// Otherwise the buzzer should play its usual tone.
+    pub fn get_audio_pattern(&self) -> Option<AudioPattern> {
+        self.audio_pattern.map(|pattern| AudioPattern {
+            pattern,
+            pitch: self.pitch,
+        })
     }
 
-    pub fn get_display(&self) -> [bool; SCREEN_WIDTH * SCREEN_HEIGHT] {
-        self.screen
+    /// The active resolution as (width, height).
+    pub fn get_resolution(&self) -> (usize, usize) {
+        if self.hires {
+            (HIRES_SCREEN_WIDTH, HIRES_SCREEN_HEIGHT)
+        } else {
+            (SCREEN_WIDTH, SCREEN_HEIGHT)
+        }
+    }
+
+    pub fn get_platform(&self) -> Platform {
+        self.platform
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
     }
 
     pub fn get_fps(&self) -> u32 {
         self.fps
     }
 
+    pub fn get_instructions_per_second(&self) -> u32 {
+        self.instructions_per_second
+    }
+
+    /// Sets how many instructions `run_frame` executes per second of emulated time.
+    pub fn set_instructions_per_second(&mut self, rate: u32) {
+        self.instructions_per_second = rate;
+        self.instruction_credit = 0;
+    }
+
+    pub fn get_quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    /// Replaces the random source used by CXNN.
+    pub fn set_random_source(&mut self, rng: Box<dyn RandomSource>) {
+        self.rng = rng;
+    }
+
+    /// Restarts the default random source from `seed`, so the same seed gives the same numbers.
+    pub fn seed_random(&mut self, seed: u64) {
+        self.rng = Box::new(SplitMix64::new(seed));
+    }
+
+    /// Attaches a tracer that observes every executed instruction, or detaches it with None.
+    pub fn set_tracer(&mut self, tracer: Option<Box<dyn Tracer>>) {
+        self.tracer = TracerSlot(tracer);
+    }
+
+    /// Detaches the current tracer and returns it, e.g. to flush what it wrote.
+    pub fn take_tracer(&mut self) -> Option<Box<dyn Tracer>> {
+        self.tracer.0.take()
+    }
+
+    /// The registers V0 to VF.
+    pub fn get_registers(&self) -> [u8; NUMBER_OF_REGISTERS] {
+        self.v
+    }
+
+    pub fn get_i(&self) -> u16 {
+        self.i
+    }
+
+    pub fn get_pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn get_sp(&self) -> u8 {
+        self.sp
+    }
+
+    /// The return addresses currently on the stack, from the oldest to the newest.
+    pub fn get_stack(&self) -> &[u16] {
+        &self.stack[..(self.sp as usize).min(STACK_SIZE)]
+    }
+
+    pub fn get_delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn get_sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    pub fn get_memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    pub fn get_memory_mut(&mut self) -> &mut [u8] {
+        &mut self.memory
+    }
+
+    /// Moves the memory into the buffer of `previous`, which gets this machine's buffer in
+    /// return. Frontends that hand out a pointer to the memory call it when swapping machines,
+    /// e.g. on a reset, so the pointer stays valid as long as the buffer has the capacity.
+    pub fn adopt_memory_buffer(&mut self, previous: &mut Chip8) {
+        previous.memory.clear();
+        previous.memory.extend_from_slice(&self.memory);
+        core::mem::swap(&mut self.memory, &mut previous.memory);
+    }
+
+    /// Sets VX. Only the lower nibble of `x` selects the register.
+    pub fn set_register(&mut self, x: u8, value: u8) {
+        self.v[(x & 0xF) as usize] = value;
+    }
+
+    pub fn set_i(&mut self, i: u16) {
+        self.i = i;
+    }
+
+    pub fn set_pc(&mut self, pc: u16) {
+        self.pc = pc;
+    }
+
+    /// Sets the stack pointer, up to the size of the stack.
+    pub fn set_sp(&mut self, sp: u8) {
+        self.sp = sp.min(STACK_SIZE as u8);
+    }
+
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.delay_timer = value;
+    }
+
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.sound_timer = value;
+    }
+
+    /// Whether the CPU is stalled until the next timer tick by the display wait quirk.
+    pub fn is_waiting_for_vblank(&self) -> bool {
+        self.waiting_for_vblank
+    }
+
+    /// Whether the program is waiting for a key to be pressed with FX0A.
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.waiting_for_key
+    }
+
+    /// Decodes the instruction at PC without executing it. None if it isn't valid on the
+    /// current platform.
+    pub fn get_current_instruction(&self) -> Option<Instruction> {
+        let opcode = self.read_word(self.pc).ok()?;
+        Instruction::decode(opcode).filter(|instruction| instruction.platform() <= self.platform)
+    }
+
     pub fn tick_timers(&mut self) -> bool {
+        self.waiting_for_vblank = false;
+
         if self.delay_timer > 0 {
             self.delay_timer -= 1;
         }
@@ -339,10 +898,12 @@ impl Chip8 {
 
     pub fn key_down(&mut self, key: Option<u8>, is_down: bool) {
         if let Some(key_index) = key {
-            self.keys[key_index as usize] = is_down;
+            self.keys[(key_index & 0xF) as usize] = is_down;
         }
     }
 
+    #[cfg(feature = "serde")]
+    #[allow(clippy::too_many_arguments)]
     fn from_deserialized_data(
         memory: Vec<u8>,
         i: u16,
@@ -353,11 +914,22 @@ impl Chip8 {
         sound_timer: u8,
         keys: Vec<bool>,
         v: Vec<u8>,
-        screen: Vec<bool>,
+        screen: Vec<u8>,
         fps: u32,
+        instructions_per_second: u32,
+        quirks: Quirks,
+        platform: Platform,
+        hires: bool,
+        halted: bool,
+        flags: Vec<u8>,
+        planes: u8,
+        audio_pattern: Option<[u8; AUDIO_PATTERN_SIZE]>,
+        pitch: u8,
+        rng_state: u64,
     ) -> Self {
-        let mut memory_buf = [0; MEMORY_SIZE];
-        memory_buf[0..].copy_from_slice(&memory);
+        let mut memory_buf = vec![0; platform.memory_size()];
+        let memory_len = memory.len().min(memory_buf.len());
+        memory_buf[..memory_len].copy_from_slice(&memory[..memory_len]);
 
         let mut stack_buf:[u16; STACK_SIZE] = [0; STACK_SIZE];
         stack_buf[0..STACK_SIZE].copy_from_slice(&stack);
@@ -365,8 +937,15 @@ impl Chip8 {
         let mut v_buf: [u8; NUMBER_OF_REGISTERS] = [0; NUMBER_OF_REGISTERS];
         v_buf[0..NUMBER_OF_REGISTERS].copy_from_slice(&v);
 
-        let mut screen_buf: [bool; SCREEN_HEIGHT * SCREEN_WIDTH] = [false; SCREEN_HEIGHT * SCREEN_WIDTH];
-        screen_buf[0..(SCREEN_WIDTH * SCREEN_HEIGHT)].copy_from_slice(&screen);
+        // Save states created before the high resolution mode existed only hold the
+        // SCREEN_WIDTH * SCREEN_HEIGHT low resolution pixels.
+        let mut screen_buf = [0; HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT];
+        let screen_len = screen.len().min(screen_buf.len());
+        screen_buf[..screen_len].copy_from_slice(&screen[..screen_len]);
+
+        let mut flags_buf = [0; NUMBER_OF_FLAGS];
+        let flags_len = flags.len().min(NUMBER_OF_FLAGS);
+        flags_buf[..flags_len].copy_from_slice(&flags[..flags_len]);
 
         let mut keys_buf: [bool; NUMBER_OF_KEYS] = [false; NUMBER_OF_KEYS];
         keys_buf[0..NUMBER_OF_KEYS].copy_from_slice(&keys);
@@ -382,229 +961,524 @@ impl Chip8 {
             sound_timer,
             i,
             fps,
+            instructions_per_second,
+            instruction_credit: 0,
+            quirks,
+            waiting_for_vblank: false,
+            waiting_for_key: false,
+            screen_changed: false,
+            dirty: Some(WHOLE_SCREEN),
+            dirty_rows: u64::MAX,
+            platform,
+            hires,
+            halted,
+            flags: flags_buf,
+            planes,
+            audio_pattern,
+            pitch,
+            rng: Box::new(SplitMix64::new(rng_state)),
+            tracer: TracerSlot::default(),
         }
+    }
 
+    // The memory an instruction reads or writes as data when executed in the current state,
+    // as the kind of access and its address range.
+    pub(crate) fn memory_access(&self, instruction: Instruction) -> Option<(Access, Range<usize>)> {
+        let i = self.i as usize;
+        let (access, length) = match instruction {
+            Instruction::Draw { n: 0, .. } if self.platform >= Platform::SuperChip => {
+                (Access::Read, 32 * self.planes.count_ones() as usize)
+            }
+            Instruction::Draw { n, .. } => (Access::Read, n as usize * self.planes.count_ones() as usize),
+            Instruction::Audio => (Access::Read, AUDIO_PATTERN_SIZE),
+            Instruction::Load { x } => (Access::Read, x as usize + 1),
+            Instruction::LoadRange { x, y } => (Access::Read, Self::register_range(x, y).count()),
+            Instruction::Bcd { .. } => (Access::Write, 3),
+            Instruction::Store { x } => (Access::Write, x as usize + 1),
+            Instruction::SaveRange { x, y } => (Access::Write, Self::register_range(x, y).count()),
+            _ => return None,
+        };
+        Some((access, i..i + length))
     }
 
-    fn push(&mut self, instruction: u16) {
+    fn push(&mut self, instruction: u16) -> Result<(), Chip8Error> {
+        if self.sp as usize >= STACK_SIZE {
+            return Err(Chip8Error::StackOverflow);
+        }
         self.stack[self.sp as usize] = instruction;
         self.sp += 1;
+        Ok(())
     }
 
-    fn pop(&mut self) -> u16 {
+    fn pop(&mut self) -> Result<u16, Chip8Error> {
+        if self.sp == 0 {
+            return Err(Chip8Error::StackUnderflow);
+        }
         self.sp -= 1;
-        self.stack[self.sp as usize]
+        Ok(self.stack[self.sp as usize])
+    }
+
+    fn fetch(&mut self) -> Result<u16, Chip8Error> {
+        let opcode = self.read_word(self.pc)?;
+        self.pc = self.pc.wrapping_add(2);
+        Ok(opcode)
     }
 
-    fn fetch(&mut self) -> u16 {
+    fn read_memory(&self, address: usize) -> Result<u8, Chip8Error> {
+        self.memory
+            .get(address)
+            .copied()
+            .ok_or(Chip8Error::MemoryOutOfBounds { address })
+    }
+
+    fn write_memory(&mut self, address: usize, value: u8) -> Result<(), Chip8Error> {
+        let byte = self
+            .memory
+            .get_mut(address)
+            .ok_or(Chip8Error::MemoryOutOfBounds { address })?;
+        *byte = value;
+        Ok(())
+    }
+
+    fn read_word(&self, address: u16) -> Result<u16, Chip8Error> {
         // Two successive byte stored one after the other.
-        let higher_byte = self.memory[self.pc as usize] as u16;
-        let lower_byte = self.memory[(self.pc + 1) as usize] as u16;
-        let opcode = (higher_byte << 8) | lower_byte;
-        self.pc += 2;
-        opcode
-    }
-
-    fn decode(&mut self, opcode: u16) {
-        let nnn = opcode & 0x0FFF;
-        let kk = opcode & 0x00FF;
-        let n = opcode & 0x000F;
-
-        let first_nibble = (opcode & 0xF000) >> 12;
-        // Getting the lower 4 bits of the high byte of the instruction. EX:
-        // instruction = 0x34ff -> in this example 4 represents the lower four bits of the high
-        // byte. If we shift 8 bits (f -> 4, f -> 4) we get 0x0034.
-        // if we perform bitwise and on 0x0034 and 0x00f we'll end with the lower 4 bytes:
-        // 0x0034 & 0x000f = 0x0004.
-        let x = (opcode & 0x0F00) >> 8;
-        // Getting the higher 4 bits of the low byte instruction. We apply the same principle
-        // explained above.
-        let y = (opcode & 0x00F0) >> 4;
-
-        println!(
-            "Current instruction: {:#04x} {:#04x} {:#04x} {:#04x}",
-            first_nibble, x, y, n
-        );
-        match (first_nibble, x, y, n) {
+        let higher_byte = self.read_memory(address as usize)? as u16;
+        let lower_byte = self.read_memory(address as usize + 1)? as u16;
+        Ok((higher_byte << 8) | lower_byte)
+    }
+
+    // Skips the next instruction. On XO-CHIP the long load F000 NNNN is four bytes wide, so it
+    // has to be skipped as a whole.
+    fn skip_instruction(&mut self) {
+        if self.platform >= Platform::XoChip && self.read_word(self.pc) == Ok(0xF000) {
+            self.pc = self.pc.wrapping_add(4);
+        } else {
+            self.pc = self.pc.wrapping_add(2);
+        }
+    }
+
+    fn draw_sprite(
+        &mut self,
+        x: usize,
+        y: usize,
+        sprite_width: usize,
+        number_of_rows: usize,
+    ) -> Result<(), Chip8Error> {
+        let (width, height) = self.get_resolution();
+        let bytes_per_row = sprite_width / 8;
+
+        // Getting the coordinates out of their respective registers. The starting position
+        // always wraps around the screen, even when clipping.
+        let x_coordinate = self.v[x] as usize % width;
+        let y_coordinate = self.v[y] as usize % height;
+
+        // Will allow us to check if any pixels were flipped.
+        let mut flipped = false;
+        // The pixels drawn, as the corners of the rectangle holding them and their rows.
+        let mut drawn: Option<(usize, usize, usize, usize)> = None;
+        let mut drawn_rows = 0;
+
+        // When both XO-CHIP planes are selected, the sprite data of the second plane
+        // follows the data of the first one.
+        let mut address = self.i as usize;
+        for plane in [1u8, 2u8] {
+            if self.planes & plane == 0 {
+                continue;
+            }
+
+            // Here we are iterating over each row in the sprite.
+            for y_line in 0..number_of_rows {
+                for x_line in 0..sprite_width {
+                    // Here we determine which memory address our rows data is
+                    // stored in. Sprites are stored row by row, beginning at the
+                    // address stored in I. So if we were to draw a 3px tall sprite
+                    // the first row's data is stored in I, followed by I + 1, I + 2, etc.
+                    // That's the main reason why all the sprites are 8 pixels wide; each
+                    // row is assigned a byte, which is 8-bits. 16x16 sprites use two bytes per row.
+                    let pixels = self.read_memory(address + y_line * bytes_per_row + x_line / 8)?;
+                    // Here we basically get the pixel located at a specific row.
+                    // In this case, we want any value different than 0, since 0
+                    // means the space is empty.
+                    if (pixels & (0b10000000 >> (x_line % 8))) != 0 {
+                        let x = x_coordinate + x_line;
+                        let y = y_coordinate + y_line;
+
+                        // Pixels past the edges are either dropped (clipping quirk) or
+                        // wrapped around the screen.
+                        if self.quirks.clipping && (x >= width || y >= height) {
+                            continue;
+                        }
+                        let x = x % width;
+                        let y = y % height;
+
+                        // Getting the index of the pixel from our 1D array.
+                        let pixel_index = x + width * y;
+
+                        // Checking if we are about to flip the pixel.
+                        flipped |= self.screen[pixel_index] & plane != 0;
+
+                        // Set.
+                        self.screen[pixel_index] ^= plane;
+                        drawn = Some(drawn.map_or((x, y, x, y), |(left, top, right, bottom)| {
+                            (left.min(x), top.min(y), right.max(x), bottom.max(y))
+                        }));
+                        drawn_rows |= 1 << y;
+                    }
+                }
+            }
+            address += number_of_rows * bytes_per_row;
+        }
+
+        if let Some((left, top, right, bottom)) = drawn {
+            let rect = DirtyRect { x: left, y: top, width: right - left + 1, height: bottom - top + 1 };
+            self.mark_dirty(rect, drawn_rows);
+        }
+
+        // Populating the VF register.
+        if flipped {
+            self.v[0xF] = 1;
+        } else {
+            self.v[0xF] = 0;
+        }
+
+        if self.quirks.display_wait {
+            self.waiting_for_vblank = true;
+        }
+        Ok(())
+    }
+
+    // Records that the pixels of `rect` changed, in the rows of `rows`.
+    fn mark_dirty(&mut self, rect: DirtyRect, rows: u64) {
+        self.screen_changed = true;
+        self.dirty = Some(self.dirty.map_or(rect, |dirty| dirty.union(rect)));
+        self.dirty_rows |= rows;
+    }
+
+    fn mark_screen_dirty(&mut self) {
+        let (width, height) = self.get_resolution();
+        let rect = DirtyRect { x: 0, y: 0, width, height };
+        self.mark_dirty(rect, Self::rows_mask(0, height));
+    }
+
+    // One bit per row from `y` to `y + rows`, as in `Framebuffer::dirty_rows`.
+    fn rows_mask(y: usize, rows: usize) -> u64 {
+        let mask = if rows >= 64 { u64::MAX } else { (1 << rows) - 1 };
+        mask << y
+    }
+
+    // Moves the pixel at `from` to `to`, only for the selected planes. None clears the
+    // selected planes of `to`.
+    fn move_pixel(&mut self, from: Option<usize>, to: usize) {
+        let moved = from.map_or(0, |from| self.screen[from] & self.planes);
+        self.screen[to] = (self.screen[to] & !self.planes) | moved;
+    }
+
+    fn scroll_up(&mut self, rows: usize) {
+        let (width, height) = self.get_resolution();
+        for y in 0..height {
+            for x in 0..width {
+                let from = (y + rows < height).then(|| x + width * (y + rows));
+                self.move_pixel(from, x + width * y);
+            }
+        }
+    }
+
+    fn scroll_down(&mut self, rows: usize) {
+        let (width, height) = self.get_resolution();
+        for y in (0..height).rev() {
+            for x in 0..width {
+                let from = (y >= rows).then(|| x + width * (y - rows));
+                self.move_pixel(from, x + width * y);
+            }
+        }
+    }
+
+    fn scroll_right(&mut self, columns: usize) {
+        let (width, height) = self.get_resolution();
+        for y in 0..height {
+            for x in (0..width).rev() {
+                let from = (x >= columns).then(|| x - columns + width * y);
+                self.move_pixel(from, x + width * y);
+            }
+        }
+    }
+
+    fn scroll_left(&mut self, columns: usize) {
+        let (width, height) = self.get_resolution();
+        for y in 0..height {
+            for x in 0..width {
+                let from = (x + columns < width).then(|| x + columns + width * y);
+                self.move_pixel(from, x + width * y);
+            }
+        }
+    }
+
+    // Registers touched by 5XY2/5XY3, in the order they are stored in memory.
+    fn register_range(x: u8, y: u8) -> Box<dyn Iterator<Item = usize>> {
+        let (x, y) = (x as usize, y as usize);
+        if x <= y {
+            Box::new(x..=y)
+        } else {
+            Box::new((y..=x).rev())
+        }
+    }
+
+    fn decode(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        // SUPER-CHIP and XO-CHIP instructions are only available on the platforms that
+        // support them.
+        let instruction = Instruction::decode(opcode)
+            .filter(|instruction| instruction.platform() <= self.platform)
+            .ok_or(Chip8Error::InvalidOpcode {
+                pc: self.pc.wrapping_sub(2),
+                opcode,
+            })?;
+
+        if self.tracer.0.is_none() {
+            return self.execute(instruction);
+        }
+        let pc = self.pc.wrapping_sub(2);
+        let before = self.trace_registers();
+        let result = self.execute(instruction);
+        let event = TraceEvent {
+            pc,
+            opcode,
+            instruction,
+            before,
+            after: self.trace_registers(),
+        };
+        if let Some(tracer) = self.tracer.0.as_mut() {
+            tracer.trace(&event);
+        }
+        result
+    }
+
+    fn trace_registers(&self) -> Registers {
+        Registers {
+            v: self.v,
+            i: self.i,
+            sp: self.sp,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+        }
+    }
+
+    fn execute(&mut self, instruction: Instruction) -> Result<(), Chip8Error> {
+        let schip = self.platform >= Platform::SuperChip;
+
+        match instruction {
             // No OP opcode.
-            (0, 0, 0, 0) => return,
-            (0, 0, 0xE, 0) => {
-                self.screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+            Instruction::Sys { addr: 0 } => {}
+            Instruction::Clear => {
+                // Only the selected planes are cleared.
+                for pixel in self.screen.iter_mut() {
+                    *pixel &= !self.planes;
+                }
+                self.mark_screen_dirty();
+            }
+            Instruction::ScrollUp { n } => {
+                self.scroll_up(n as usize);
+                self.mark_screen_dirty();
+            }
+            Instruction::ScrollDown { n } => {
+                self.scroll_down(n as usize);
+                self.mark_screen_dirty();
+            }
+            Instruction::ScrollRight => {
+                self.scroll_right(4);
+                self.mark_screen_dirty();
+            }
+            Instruction::ScrollLeft => {
+                self.scroll_left(4);
+                self.mark_screen_dirty();
+            }
+            Instruction::Exit => {
+                self.halted = true;
             }
-            (1, _, _, _) => {
-                self.pc = nnn;
+            Instruction::Lores => {
+                self.hires = false;
+                self.screen = [0; HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT];
+                self.mark_screen_dirty();
             }
-            (2, _, _, _) => {
-                self.push(self.pc);
-                self.pc = nnn;
+            Instruction::Hires => {
+                self.hires = true;
+                self.screen = [0; HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT];
+                self.mark_screen_dirty();
             }
-            (0, 0, 0xE, 0xE) => {
-                let subroutine_value = self.pop();
+            Instruction::Jump { addr } => {
+                self.pc = addr;
+            }
+            Instruction::Call { addr } => {
+                self.push(self.pc)?;
+                self.pc = addr;
+            }
+            Instruction::Return => {
+                let subroutine_value = self.pop()?;
                 self.pc = subroutine_value;
             }
-            (3, _, _, _) => {
-                if self.v[x as usize] == (kk as u8) {
-                    self.pc += 2;
+            Instruction::SkipEqImm { x, nn } => {
+                if self.v[x as usize] == (nn) {
+                    self.skip_instruction();
                 }
             }
-            (4, _, _, _) => {
-                if self.v[x as usize] != (kk as u8) {
-                    self.pc += 2;
+            Instruction::SkipNeImm { x, nn } => {
+                if self.v[x as usize] != (nn) {
+                    self.skip_instruction();
                 }
             }
-            (5, _, _, 0) => {
+            Instruction::SkipEqReg { x, y } => {
                 if self.v[x as usize] == self.v[y as usize] {
-                    self.pc += 2;
+                    self.skip_instruction();
+                }
+            }
+            Instruction::SaveRange { x, y } => {
+                // Saves the range VX..VY (in either direction) to memory, leaving I untouched.
+                for (offset, register) in Self::register_range(x, y).enumerate() {
+                    self.write_memory(self.i as usize + offset, self.v[register])?;
+                }
+            }
+            Instruction::LoadRange { x, y } => {
+                for (offset, register) in Self::register_range(x, y).enumerate() {
+                    self.v[register] = self.read_memory(self.i as usize + offset)?;
                 }
             }
-            (6, _, _, _) => {
-                self.v[x as usize] = kk as u8;
+            Instruction::LoadImm { x, nn } => {
+                self.v[x as usize] = nn;
             }
-            (7, _, _, _) => {
+            Instruction::AddImm { x, nn } => {
                 // wrapping_add automatically performs wrap in case of overflow.
                 // EX: 255 + 1 (u8) = 0.
-                self.v[x as usize] = self.v[x as usize].wrapping_add(kk as u8);
+                self.v[x as usize] = self.v[x as usize].wrapping_add(nn);
             }
-            (8, _, _, 0) => {
+            Instruction::Move { x, y } => {
                 self.v[x as usize] = self.v[y as usize];
             }
-            (8, _, _, 1) => {
+            Instruction::Or { x, y } => {
                 self.v[x as usize] |= self.v[y as usize];
+                if self.quirks.vf_reset {
+                    self.v[0xF] = 0;
+                }
             }
-            (8, _, _, 2) => {
+            Instruction::And { x, y } => {
                 self.v[x as usize] &= self.v[y as usize];
+                if self.quirks.vf_reset {
+                    self.v[0xF] = 0;
+                }
             }
-            (8, _, _, 3) => {
+            Instruction::Xor { x, y } => {
                 self.v[x as usize] ^= self.v[y as usize];
+                if self.quirks.vf_reset {
+                    self.v[0xF] = 0;
+                }
             }
-            (8, _, _, 4) => {
+            Instruction::Add { x, y } => {
                 let (current_x, overflow) = self.v[x as usize].overflowing_add(self.v[y as usize]);
                 let new_vf = if overflow { 1 } else { 0 };
                 self.v[x as usize] = current_x;
                 self.v[0xF] = new_vf;
             }
-            (8, _, _, 5) => {
+            Instruction::Sub { x, y } => {
                 let (current_x, borrow) = self.v[x as usize].overflowing_sub(self.v[y as usize]);
                 let new_vf: u8 = if borrow { 0 } else { 1 };
                 self.v[x as usize] = current_x;
                 self.v[0xF] = new_vf;
             }
-            (8, _, _, 6) => {
+            Instruction::ShiftRight { x, y } => {
+                // The COSMAC VIP shifts VY and stores the result in VX.
+                if !self.quirks.shift {
+                    self.v[x as usize] = self.v[y as usize];
+                }
                 let shifted_bit = self.v[x as usize] & 1;
                 self.v[x as usize] >>= 1;
                 self.v[0xF] = shifted_bit;
             }
-            (8, _, _, 7) => {
+            Instruction::SubReverse { x, y } => {
                 let (current_x, borrow) = self.v[y as usize].overflowing_sub(self.v[x as usize]);
                 let new_vf: u8 = if borrow { 0 } else { 1 };
 
                 self.v[x as usize] = current_x;
                 self.v[0xF] = new_vf;
             }
-            (8, _, _, 0xE) => {
+            Instruction::ShiftLeft { x, y } => {
+                if !self.quirks.shift {
+                    self.v[x as usize] = self.v[y as usize];
+                }
                 let shifted_bit = (self.v[x as usize] >> 7) & 0x1;
                 self.v[x as usize] <<= 1;
                 self.v[0xF] = shifted_bit;
             }
-            (9, _, _, 0) => {
+            Instruction::SkipNeReg { x, y } => {
                 if self.v[x as usize] != self.v[y as usize] {
-                    self.pc += 2;
+                    self.skip_instruction();
                 }
             }
-            (0xA, _, _, _) => {
-                self.i = nnn;
+            Instruction::LoadI { addr } => {
+                self.i = addr;
             }
-            (0xB, _, _, _) => {
-                self.pc = (self.v[0] as u16) + nnn;
+            Instruction::JumpOffset { addr } => {
+                // CHIP-48 and SUPER-CHIP read the register from the highest nibble of NNN (BXNN).
+                let register = if self.quirks.jump { addr >> 8 } else { 0 };
+                self.pc = (self.v[register as usize] as u16) + addr;
             }
-            (0xC, _, _, _) => {
-                let mut rng = rand::thread_rng();
-                let range = Uniform::from(0..kk);
-                let random_bit = rng.sample(range);
-                self.v[x as usize] = (random_bit & kk) as u8;
+            Instruction::Random { x, nn } => {
+                self.v[x as usize] = self.rng.next_byte() & nn;
             }
-            (0xD, _, _, _) => {
-                // Getting the coordinates out of their respective
-                // registers.
-                let x_coordinate = self.v[x as usize] as u16;
-                let y_coordinate = self.v[y as usize] as u16;
-
-                // The value of n represents the number of rows of our sprite.
-                let number_of_rows = n;
-
-                // Will allow us to check if any pixels were flipped.
-                let mut flipped = false;
-
-                // Here we are iterating over each row in the sprite.
-                for y_line in 0..number_of_rows {
-                    // Here we determine which memory address our rows data is
-                    // stored in. Sprites are stored row by row, beginning at the
-                    // address stored in I. So if we were to draw a 3px tall sprite
-                    // the first row's data is stored in I, followed by I + 1, I + 2, etc.
-                    // That's the main reason why all the sprites are 8 pixels wide; each
-                    // row is assigned a byte, which is 8-bits.
-                    let address = (self.i + y_line) as u16;
-                    let pixels = self.memory[address as usize];
-                    for x_line in 0..8 {
-                        // Here we basically get the pixel located at a specific row.
-                        // In this case, we want any value different than 0, since 0
-                        // means the space is empty.
-                        if (pixels & (0b10000000 >> x_line)) != 0 {
-                            // Sprites wrap around the screen. We should use modulo division
-                            // because of that.
-                            let x = (x_coordinate + x_line) as usize % SCREEN_WIDTH;
-                            let y = (y_coordinate + y_line) as usize % SCREEN_HEIGHT;
-
-                            // Getting the index of the pixel from our 1D array.
-                            let pixel_index = x + SCREEN_WIDTH * y;
-
-                            // Checking if we are about to flip the pixel.
-                            flipped |= self.screen[pixel_index];
-
-                            // Set.
-                            self.screen[pixel_index] ^= true;
-                        }
-                    }
-                }
-
-                // Populating the VF register.
-                if flipped {
-                    self.v[0xF] = 1;
-                } else {
-                    self.v[0xF] = 0;
-                }
+            Instruction::Draw { x, y, n: 0 } if schip => {
+                // DXY0 draws a 16x16 sprite, made of two bytes per row.
+                self.draw_sprite(x as usize, y as usize, 16, 16)?;
             }
-            (0xE, _, 9, 0xE) => {
-                let key_index = self.v[x as usize];
+            Instruction::Draw { x, y, n } => {
+                self.draw_sprite(x as usize, y as usize, 8, n as usize)?;
+            }
+            Instruction::SkipKeyPressed { x } => {
+                // Only the lower nibble of VX selects a key.
+                let key_index = self.v[x as usize] & 0xF;
                 let is_pressed = self.keys[key_index as usize];
                 if is_pressed {
-                    self.pc += 2;
+                    self.skip_instruction();
                 }
             }
-            (0xE, _, 0xA, 1) => {
-                let key_index = self.v[x as usize];
+            Instruction::SkipKeyNotPressed { x } => {
+                let key_index = self.v[x as usize] & 0xF;
                 let is_pressed = self.keys[key_index as usize];
                 if !is_pressed {
-                    self.pc += 2;
+                    self.skip_instruction();
+                }
+            }
+            Instruction::LoadLongI => {
+                // F000 NNNN: loads the 16 bit address stored after the instruction into I.
+                self.i = self.read_word(self.pc)?;
+                self.pc = self.pc.wrapping_add(2);
+            }
+            Instruction::Plane { n } => {
+                self.planes = n & 0b11;
+            }
+            Instruction::Audio => {
+                let mut pattern = [0; AUDIO_PATTERN_SIZE];
+                for (offset, sample) in pattern.iter_mut().enumerate() {
+                    *sample = self.read_memory(self.i as usize + offset)?;
                 }
+                self.audio_pattern = Some(pattern);
             }
-            (0xF, _, 0, 7) => {
+            Instruction::Pitch { x } => {
+                self.pitch = self.v[x as usize];
+            }
+            Instruction::LoadDelay { x } => {
                 self.v[x as usize] = self.delay_timer;
             }
-            (0xF, _, 1, 5) => {
+            Instruction::SetDelay { x } => {
                 self.delay_timer = self.v[x as usize];
             }
-            (0xF, _, 1, 8) => {
+            Instruction::SetSound { x } => {
                 self.sound_timer = self.v[x as usize];
             }
-            (0xF, _, 1, 0xE) => {
+            Instruction::AddI { x } => {
                 let (current_i, overflow) = self.i.overflowing_add(self.v[x as usize] as u16);
                 self.i = current_i;
                 if overflow {
                     self.v[0xF] = 1;
                 }
             }
-            (0xF, _, 0, 0xA) => {
+            Instruction::WaitKey { x } => {
                 let mut pressed = false;
                 for i in 0..self.keys.len() {
                     if self.keys[i] {
@@ -614,34 +1488,433 @@ impl Chip8 {
                     }
                 }
                 if !pressed {
-                    self.pc -= 2;
+                    self.pc = self.pc.wrapping_sub(2);
                 };
+                self.waiting_for_key = !pressed;
             }
-            (0xF, _, 2, 9) => {
-                let vx = self.v[x as usize];
-                self.i = (vx * 5) as u16;
+            Instruction::LoadFont { x } => {
+                let vx = self.v[x as usize] & 0xF;
+                self.i = (vx as u16) * 5;
             }
-            (0xF, _, 3, 3) => {
+            Instruction::LoadBigFont { x } => {
+                let vx = self.v[x as usize] & 0xF;
+                self.i = (BIG_FONT_ADDRESS as u16) + (vx as u16) * 10;
+            }
+            Instruction::Bcd { x } => {
                 let vx = self.v[x as usize];
-                let hundreds = ((vx as f32) / 100.0).floor() as u8;
-                let tens = (((vx as f32) / 10.0) % 10.0).floor() as u8;
-                let ones = ((vx as f32) % 10.0) as u8;
+                let hundreds = vx / 100;
+                let tens = vx / 10 % 10;
+                let ones = vx % 10;
 
-                self.memory[(self.i) as usize] = hundreds;
-                self.memory[(self.i + 1) as usize] = tens;
-                self.memory[(self.i + 2) as usize] = ones;
+                self.write_memory(self.i as usize, hundreds)?;
+                self.write_memory(self.i as usize + 1, tens)?;
+                self.write_memory(self.i as usize + 2, ones)?;
             }
-            (0xF, _, 5, 5) => {
+            Instruction::Store { x } => {
                 for i in 0..=x {
-                    self.memory[(self.i + i) as usize] = self.v[i as usize];
+                    self.write_memory(self.i as usize + i as usize, self.v[i as usize])?;
+                }
+                if !self.quirks.load_store {
+                    self.i = self.i.wrapping_add(x as u16 + 1);
                 }
             }
-            (0xF, _, 6, 5) => {
+            Instruction::Load { x } => {
                 for i in 0..=x {
-                    self.v[i as usize] = self.memory[(self.i + i) as usize];
+                    self.v[i as usize] = self.read_memory(self.i as usize + i as usize)?;
+                }
+                if !self.quirks.load_store {
+                    self.i = self.i.wrapping_add(x as u16 + 1);
+                }
+            }
+            Instruction::SaveFlags { x } => {
+                for i in 0..=(x as usize).min(NUMBER_OF_FLAGS - 1) {
+                    self.flags[i] = self.v[i];
                 }
             }
-            (_, _, _, _) => unimplemented!("Unimplemented opcode. Opcode: {}", opcode),
+            Instruction::LoadFlags { x } => {
+                for i in 0..=(x as usize).min(NUMBER_OF_FLAGS - 1) {
+                    self.v[i] = self.flags[i];
+                }
+            }
+            // Machine code routines can't be executed.
+            Instruction::Sys { .. } => {
+                return Err(Chip8Error::InvalidOpcode {
+                    pc: self.pc.wrapping_sub(2),
+                    opcode: instruction.encode(),
+                })
+            }
         };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(quirks: Quirks, rom: &[u8], ticks: usize) -> Chip8 {
+        let mut chip8 = Chip8::with_quirks(quirks);
+        chip8.load_file(rom).unwrap();
+        for _ in 0..ticks {
+            chip8.tick().unwrap();
+        }
+        chip8
+    }
+
+    #[test]
+    fn shift_quirk_selects_source_register() {
+        // V1 := 0x04, V2 := 0x81, V1 >>= V2
+        let rom = [0x61, 0x04, 0x62, 0x81, 0x81, 0x26];
+        assert_eq!(run(Quirks::default(), &rom, 3).v[1], 0x02);
+        let vip = run(Quirks::cosmac_vip(), &rom, 3);
+        assert_eq!(vip.v[1], 0x40);
+        assert_eq!(vip.v[0xF], 1);
+    }
+
+    #[test]
+    fn load_store_quirk_increments_i() {
+        // I := 0x300, V0..V2 -> [I]
+        let rom = [0xA3, 0x00, 0xF2, 0x55];
+        assert_eq!(run(Quirks::default(), &rom, 2).i, 0x300);
+        assert_eq!(run(Quirks::cosmac_vip(), &rom, 2).i, 0x303);
+    }
+
+    #[test]
+    fn jump_quirk_uses_vx() {
+        // V0 := 0x02, V3 := 0x10, jump to 0x300 + V0 or V3
+        let rom = [0x60, 0x02, 0x63, 0x10, 0xB3, 0x00];
+        assert_eq!(run(Quirks::default(), &rom, 3).pc, 0x302);
+        assert_eq!(run(Quirks::chip48(), &rom, 3).pc, 0x310);
+    }
+
+    #[test]
+    fn vf_reset_quirk_clears_flag() {
+        // VF := 1, V0 |= V1
+        let rom = [0x6F, 0x01, 0x80, 0x11];
+        assert_eq!(run(Quirks::default(), &rom, 2).v[0xF], 1);
+        assert_eq!(run(Quirks::cosmac_vip(), &rom, 2).v[0xF], 0);
+    }
+
+    #[test]
+    fn clipping_quirk_drops_pixels_past_the_edge() {
+        // V0 := 60, I := 0x208, draw a 0xFF row at (60, 0)
+        let rom = [0x60, 0x3C, 0xA2, 0x08, 0xD0, 0x11, 0x12, 0x06, 0xFF];
+        let wrapped = run(Quirks::default(), &rom, 3);
+        assert_eq!(wrapped.screen[0], 1);
+        let clipped = run(Quirks::chip48(), &rom, 3);
+        assert_eq!(clipped.screen[0], 0);
+        assert_eq!(clipped.screen[60], 1);
+    }
+
+    #[test]
+    fn display_wait_quirk_stalls_until_timer_tick() {
+        // Draw, then V0 := 1
+        let rom = [0xD0, 0x01, 0x60, 0x01];
+        let mut chip8 = run(Quirks::cosmac_vip(), &rom, 2);
+        assert_eq!(chip8.v[0], 0);
+        chip8.tick_timers();
+        chip8.tick().unwrap();
+        assert_eq!(chip8.v[0], 1);
+    }
+
+    fn run_schip(rom: &[u8], ticks: usize) -> Chip8 {
+        let mut chip8 = Chip8::with_platform(Platform::SuperChip, Quirks::superchip());
+        chip8.load_file(rom).unwrap();
+        for _ in 0..ticks {
+            chip8.tick().unwrap();
+        }
+        chip8
+    }
+
+    #[test]
+    fn schip_switches_resolution() {
+        let chip8 = run_schip(&[0x00, 0xFF], 1);
+        assert_eq!(chip8.get_resolution(), (HIRES_SCREEN_WIDTH, HIRES_SCREEN_HEIGHT));
+        let chip8 = run_schip(&[0x00, 0xFF, 0x00, 0xFE], 2);
+        assert_eq!(chip8.get_resolution(), (SCREEN_WIDTH, SCREEN_HEIGHT));
+    }
+
+    #[test]
+    fn schip_draws_16x16_sprites_in_hires() {
+        // hires, V0 := 0x7F, I := big font "0", draw a 16x16 sprite at (127, 0)
+        let rom = [0x00, 0xFF, 0x60, 0x7F, 0xA0, 0x50, 0xD0, 0x10];
+        let chip8 = run_schip(&rom, 4);
+        // The first byte of the big "0" is 0xFF and the second (right half) is 0xFF too.
+        assert_eq!(chip8.screen[127], 1);
+        // Clipping keeps the sprite from wrapping to the left edge.
+        assert_eq!(chip8.screen[0], 0);
+    }
+
+    #[test]
+    fn schip_scrolls_the_screen() {
+        // I := font "0", draw at (0, 0), scroll down 2, scroll right 4
+        let rom = [0xA0, 0x00, 0xD0, 0x01, 0x00, 0xC2, 0x00, 0xFB];
+        let chip8 = run_schip(&rom, 4);
+        assert_eq!(chip8.screen[0], 0);
+        assert_eq!(chip8.screen[4 + SCREEN_WIDTH * 2], 1);
+        assert_eq!(chip8.screen[7 + SCREEN_WIDTH * 2], 1);
+        assert_eq!(chip8.screen[8 + SCREEN_WIDTH * 2], 0);
+    }
+
+    #[test]
+    fn schip_points_i_at_big_font() {
+        let chip8 = run_schip(&[0x60, 0x03, 0xF0, 0x30], 2);
+        assert_eq!(chip8.i, (BIG_FONT_ADDRESS + 30) as u16);
+    }
+
+    #[test]
+    fn schip_saves_and_restores_flags() {
+        // V0 := 1, V1 := 2, save flags, V0 := 0, V1 := 0, load flags
+        let rom = [0x60, 0x01, 0x61, 0x02, 0xF1, 0x75, 0x60, 0x00, 0x61, 0x00, 0xF1, 0x85];
+        let chip8 = run_schip(&rom, 6);
+        assert_eq!(chip8.v[..2], [1, 2]);
+    }
+
+    #[test]
+    fn schip_exit_halts_the_interpreter() {
+        let chip8 = run_schip(&[0x00, 0xFD, 0x60, 0x01], 2);
+        assert!(chip8.is_halted());
+        assert_eq!(chip8.v[0], 0);
+    }
+
+    fn run_xo(rom: &[u8], ticks: usize) -> Chip8 {
+        let mut chip8 = Chip8::with_platform(Platform::XoChip, Quirks::xochip());
+        chip8.load_file(rom).unwrap();
+        for _ in 0..ticks {
+            chip8.tick().unwrap();
+        }
+        chip8
+    }
+
+    #[test]
+    fn xo_loads_long_addresses_and_skips_them() {
+        // I := long 0xABCD, V0 := 0, skip if V0 == 0 over another long load, V1 := 1
+        let rom = [0xF0, 0x00, 0xAB, 0xCD, 0x30, 0x00, 0xF0, 0x00, 0x12, 0x34, 0x61, 0x01];
+        let chip8 = run_xo(&rom, 3);
+        assert_eq!(chip8.i, 0xABCD);
+        assert_eq!(chip8.v[1], 1);
+    }
+
+    #[test]
+    fn xo_saves_and_loads_register_ranges() {
+        // V1 := 1, V2 := 2, V3 := 3, I := 0x300, save V3 - V1, load V4 - V6
+        let rom = [
+            0x61, 0x01, 0x62, 0x02, 0x63, 0x03, 0xA3, 0x00, 0x53, 0x12, 0x54, 0x63,
+        ];
+        let chip8 = run_xo(&rom, 6);
+        assert_eq!(chip8.memory[0x300..0x303], [3, 2, 1]);
+        assert_eq!(chip8.v[4..7], [3, 2, 1]);
+        assert_eq!(chip8.i, 0x300);
+    }
+
+    #[test]
+    fn xo_draws_to_the_selected_planes() {
+        // plane 3, I := 0x20A, draw a 1 row sprite per plane at (0, 0)
+        let rom = [0xF3, 0x01, 0xA2, 0x0A, 0xD0, 0x01, 0x12, 0x06, 0x00, 0x00, 0x80, 0xC0];
+        let chip8 = run_xo(&rom, 3);
+        assert_eq!(chip8.screen[0], 0b11);
+        assert_eq!(chip8.screen[1], 0b10);
+
+        // plane 2, clear: only the second plane is cleared.
+        let rom = [
+            0xF3, 0x01, 0xA2, 0x0E, 0xD0, 0x01, 0xF2, 0x01, 0x00, 0xE0, 0x12, 0x0A, 0x00, 0x00,
+            0x80, 0xC0,
+        ];
+        let chip8 = run_xo(&rom, 5);
+        assert_eq!(chip8.screen[0], 0b01);
+        assert_eq!(chip8.screen[1], 0);
+    }
+
+    #[test]
+    fn xo_loads_audio_pattern_and_pitch() {
+        // I := 0x300, audio, V0 := 112, pitch := V0
+        let rom = [0xA3, 0x00, 0xF0, 0x02, 0x60, 0x70, 0xF0, 0x3A];
+        let chip8 = run_xo(&rom, 4);
+        let audio = chip8.get_audio_pattern().unwrap();
+        assert_eq!(audio.pitch, 112);
+        assert_eq!(audio.playback_rate(), 8000.0);
+        assert!(Chip8::new().get_audio_pattern().is_none());
+    }
+
+    #[test]
+    fn errors_instead_of_panicking() {
+        let mut chip8 = Chip8::new();
+        chip8.load_file(&[0x00, 0xEE]).unwrap();
+        assert_eq!(chip8.tick(), Err(Chip8Error::StackUnderflow));
+
+        // A subroutine calling itself forever.
+        let mut chip8 = Chip8::new();
+        chip8.load_file(&[0x22, 0x00]).unwrap();
+        let result = (0..=STACK_SIZE).try_for_each(|_| chip8.tick());
+        assert_eq!(result, Err(Chip8Error::StackOverflow));
+
+        // I := 0xFFF, V2 -> [I]
+        let mut chip8 = Chip8::new();
+        chip8.load_file(&[0xAF, 0xFF, 0xF2, 0x55]).unwrap();
+        chip8.tick().unwrap();
+        assert_eq!(chip8.tick(), Err(Chip8Error::MemoryOutOfBounds { address: 0x1000 }));
+
+        let mut chip8 = Chip8::new();
+        chip8.load_file(&[0x00, 0xFF]).unwrap();
+        let error = Chip8Error::InvalidOpcode {
+            pc: 0x200,
+            opcode: 0x00FF,
+        };
+        assert_eq!(chip8.tick(), Err(error));
+
+        let mut chip8 = Chip8::new();
+        let error = Chip8Error::RomTooLarge { size: 3585, max: 3584 };
+        assert_eq!(chip8.load_file(&[0; 3585]), Err(error));
+    }
+
+    #[test]
+    fn frames_run_at_the_configured_speed() {
+        // JP 0x200.
+        let mut chip8 = Chip8::new();
+        chip8.load_file(&[0x12, 0x00]).unwrap();
+        assert_eq!(chip8.run_frame().instructions, 8);
+        // 100 instructions per second are 1.67 per frame: the remainder adds up to an extra
+        // instruction every few frames.
+        chip8.set_instructions_per_second(100);
+        let frames: Vec<u32> = (0..6).map(|_| chip8.run_frame().instructions).collect();
+        assert_eq!(frames, [1, 2, 2, 1, 2, 2]);
+    }
+
+    #[test]
+    fn frame_reports_tell_what_happened() {
+        // LD V0, 3; LD ST, V0; LD V1, K; LD F, V1; DRW V2, V2, 5; an invalid opcode.
+        let rom = [0x60, 0x03, 0xF0, 0x18, 0xF1, 0x0A, 0xF1, 0x29, 0xD2, 0x25, 0xFF, 0xFF];
+        let mut chip8 = Chip8::new();
+        chip8.load_file(&rom).unwrap();
+        let report = chip8.run_frame();
+        assert_eq!(report, FrameReport {
+            instructions: 8,
+            screen_changed: false,
+            buzzer: true,
+            waiting_for_key: true,
+            error: None,
+        });
+
+        chip8.key_down(Some(0x7), true);
+        let report = chip8.run_frame();
+        assert_eq!(report, FrameReport {
+            instructions: 3,
+            screen_changed: true,
+            buzzer: false,
+            waiting_for_key: false,
+            error: Some(Chip8Error::InvalidOpcode { pc: 0x20A, opcode: 0xFFFF }),
+        });
+        // The timers don't tick in a failed frame.
+        assert_eq!(chip8.get_sound_timer(), 2);
+    }
+
+    #[test]
+    fn framebuffer_tracks_what_changed() {
+        // LD F, V2; LD V0, 62; LD V1, 30; DRW V0, V1, 5; CLS.
+        let rom = [0xF2, 0x29, 0x60, 0x3E, 0x61, 0x1E, 0xD0, 0x15, 0x00, 0xE0];
+        let mut chip8 = run(Quirks::default(), &rom, 3);
+        // New machines have to be drawn whole.
+        assert_eq!(chip8.get_framebuffer().dirty_rows, 0xFFFF_FFFF);
+        chip8.clear_dirty();
+        assert!(!chip8.get_framebuffer().is_dirty());
+
+        // The "0" wraps around the bottom right corner: its rectangle spans the whole screen,
+        // but only its own rows changed.
+        chip8.tick().unwrap();
+        let frame = chip8.get_framebuffer();
+        assert_eq!(frame.dirty, Some(DirtyRect { x: 0, y: 0, width: 64, height: 32 }));
+        assert_eq!(frame.dirty_rows, 0b111 | 0b11 << 30);
+        assert!(frame.is_row_dirty(31) && !frame.is_row_dirty(3));
+        assert_eq!(frame.row(30)[62..], [1, 1]);
+
+        chip8.clear_dirty();
+        chip8.tick().unwrap();
+        assert_eq!(chip8.get_framebuffer().dirty_rows, 0xFFFF_FFFF);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn quirks_survive_save_states() {
+        let chip8 = Chip8::with_quirks(Quirks::cosmac_vip());
+        let serialized = serde_json::to_string(&chip8).unwrap();
+        let restored: Chip8 = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(restored.get_quirks(), Quirks::cosmac_vip());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn speed_survives_save_states() {
+        let mut chip8 = Chip8::new();
+        chip8.set_instructions_per_second(1000);
+        let restored: Chip8 = serde_json::from_value(serde_json::to_value(&chip8).unwrap()).unwrap();
+        assert_eq!(restored.get_instructions_per_second(), 1000);
+
+        // Save states from before the speed was saved run at the default speed.
+        let mut state = serde_json::to_value(&chip8).unwrap();
+        state.as_object_mut().unwrap().remove("instructions_per_second");
+        let restored: Chip8 = serde_json::from_value(state).unwrap();
+        assert_eq!(restored.get_instructions_per_second(), DEFAULT_INSTRUCTIONS_PER_SECOND);
+    }
+
+    #[test]
+    fn random_masks_a_full_byte() {
+        // RND V0, 0x00 used to panic on an empty range.
+        let chip8 = run(Quirks::default(), &[0xC0, 0x00], 1);
+        assert_eq!(chip8.v[0], 0);
+
+        // RND V0, 0x0F; JP 0x200: every value up to and including NN shows up.
+        let mut chip8 = Chip8::new();
+        chip8.load_file(&[0xC0, 0x0F, 0x12, 0x00]).unwrap();
+        let mut seen = [false; 16];
+        for _ in 0..500 {
+            chip8.tick().unwrap();
+            chip8.tick().unwrap();
+            seen[chip8.v[0] as usize] = true;
+        }
+        assert!(seen.iter().all(|seen| *seen));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn random_numbers_are_reproducible_and_saved() {
+        let rom = [0xC0, 0xFF, 0x12, 0x00];
+        let mut first = Chip8::new();
+        first.seed_random(7);
+        first.load_file(&rom).unwrap();
+        let mut second = first.clone();
+        for _ in 0..10 {
+            first.tick().unwrap();
+            second.tick().unwrap();
+            assert_eq!(first.v[0], second.v[0]);
+        }
+
+        let serialized = serde_json::to_string(&first).unwrap();
+        let mut restored: Chip8 = serde_json::from_str(&serialized).unwrap();
+        for _ in 0..10 {
+            first.tick().unwrap();
+            restored.tick().unwrap();
+            assert_eq!(first.v[0], restored.v[0]);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn malformed_save_states_are_rejected() {
+        let state = serde_json::to_value(Chip8::new()).unwrap();
+        let corruptions = [
+            ("stack", serde_json::json!([1, 2, 3])),
+            ("v", serde_json::json!(vec![0; 17])),
+            ("keys", serde_json::json!([])),
+            ("sp", serde_json::json!(200)),
+            ("fps", serde_json::json!(0)),
+            ("planes", serde_json::json!(7)),
+            ("screen", serde_json::json!([9])),
+        ];
+        for (field, value) in corruptions {
+            let mut corrupted = state.clone();
+            corrupted[field] = value;
+            assert!(serde_json::from_value::<Chip8>(corrupted).is_err(), "{}", field);
+        }
+        assert!(serde_json::from_value::<Chip8>(state).is_ok());
     }
 }
@@ -0,0 +1,455 @@
+use alloc::collections::BTreeSet;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::fmt::Write;
+
+use crate::chip8::Chip8;
+use crate::error::Chip8Error;
+use crate::instruction::Instruction;
+
+/// How an instruction touches memory.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// A register whose changes can be watched.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Register {
+    V(u8),
+    I,
+}
+
+impl Register {
+    /// Parses `v0` to `vf` or `i`, in any case.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let mut characters = name.chars();
+        match (characters.next(), characters.next(), characters.next()) {
+            (Some('v' | 'V'), Some(digit), None) => {
+                digit.to_digit(16).map(|x| Register::V(x as u8))
+            }
+            (Some('i' | 'I'), None, None) => Some(Register::I),
+            _ => None,
+        }
+    }
+
+    fn value(&self, chip8: &Chip8) -> u16 {
+        match self {
+            Register::V(x) => chip8.get_registers()[*x as usize] as u16,
+            Register::I => chip8.get_i(),
+        }
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Register::V(x) => write!(f, "V{:X}", x),
+            Register::I => write!(f, "I"),
+        }
+    }
+}
+
+/// Why the debugger stopped the program. Every address is the PC at the time of the stop.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    /// `Debugger::pause` was called.
+    Paused { pc: u16 },
+    /// A breakpoint was hit, before executing the instruction at `pc`.
+    Breakpoint { pc: u16 },
+    /// A step, step over, step out or run to address finished.
+    Step { pc: u16 },
+    /// The instruction before `pc` read or wrote a watched address.
+    Watchpoint {
+        pc: u16,
+        access: Access,
+        address: u16,
+    },
+    /// The instruction before `pc` changed a watched register.
+    RegisterChanged {
+        pc: u16,
+        register: Register,
+        old: u16,
+        new: u16,
+    },
+    /// The instruction at `pc` failed.
+    Error { pc: u16, error: Chip8Error },
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::Paused { pc } => write!(f, "paused at 0x{:03X}", pc),
+            StopReason::Breakpoint { pc } => write!(f, "breakpoint at 0x{:03X}", pc),
+            StopReason::Step { pc } => write!(f, "stopped at 0x{:03X}", pc),
+            StopReason::Watchpoint {
+                pc,
+                access,
+                address,
+            } => {
+                let access = match access {
+                    Access::Read => "read",
+                    Access::Write => "write",
+                };
+                write!(
+                    f,
+                    "{} of 0x{:03X}, stopped at 0x{:03X}",
+                    access, address, pc
+                )
+            }
+            StopReason::RegisterChanged {
+                pc,
+                register,
+                old,
+                new,
+            } => write!(
+                f,
+                "{} changed from 0x{:02X} to 0x{:02X}, stopped at 0x{:03X}",
+                register, old, new, pc
+            ),
+            StopReason::Error { pc, error } => write!(f, "{} at 0x{:03X}", error, pc),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Mode {
+    Running,
+    Pause,
+    // Stops after the next instruction.
+    Step,
+    // Stops before executing `pc`, as long as the stack is `sp` deep (if given).
+    RunTo { pc: u16, sp: Option<u8> },
+    // Stops once the stack is shallower than `sp`, right after a return.
+    StepOut { sp: u8 },
+}
+
+/// Runs a `Chip8` instruction by instruction, stopping at breakpoints and watchpoints.
+/// Frontends call `Debugger::tick` instead of `Chip8::tick`, and hand control to the user
+/// whenever it returns a `StopReason`.
+#[derive(Clone, Debug)]
+pub struct Debugger {
+    breakpoints: BTreeSet<u16>,
+    read_watchpoints: BTreeSet<u16>,
+    write_watchpoints: BTreeSet<u16>,
+    register_watchpoints: BTreeSet<Register>,
+    mode: Mode,
+    // PC the program stopped at. Breakpoints there are ignored until PC moves, otherwise
+    // resuming would stop again right away.
+    resume_pc: Option<u16>,
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self {
+            breakpoints: BTreeSet::new(),
+            read_watchpoints: BTreeSet::new(),
+            write_watchpoints: BTreeSet::new(),
+            register_watchpoints: BTreeSet::new(),
+            mode: Mode::Running,
+            resume_pc: None,
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.breakpoints.insert(address);
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    pub fn add_watchpoint(&mut self, access: Access, address: u16) {
+        match access {
+            Access::Read => self.read_watchpoints.insert(address),
+            Access::Write => self.write_watchpoints.insert(address),
+        };
+    }
+
+    pub fn remove_watchpoint(&mut self, access: Access, address: u16) -> bool {
+        match access {
+            Access::Read => self.read_watchpoints.remove(&address),
+            Access::Write => self.write_watchpoints.remove(&address),
+        }
+    }
+
+    pub fn watch_register(&mut self, register: Register) {
+        self.register_watchpoints.insert(register);
+    }
+
+    pub fn unwatch_register(&mut self, register: Register) -> bool {
+        self.register_watchpoints.remove(&register)
+    }
+
+    /// Stops before the next instruction.
+    pub fn pause(&mut self) {
+        self.mode = Mode::Pause;
+    }
+
+    /// Runs until a breakpoint or watchpoint is hit.
+    pub fn resume(&mut self) {
+        self.mode = Mode::Running;
+    }
+
+    /// Executes a single instruction.
+    pub fn step_into(&mut self) {
+        self.mode = Mode::Step;
+    }
+
+    /// Executes a single instruction, running whole subroutines called by 2NNN.
+    pub fn step_over(&mut self, chip8: &Chip8) {
+        self.mode = match chip8.get_current_instruction() {
+            Some(instruction @ Instruction::Call { .. }) => Mode::RunTo {
+                pc: chip8.get_pc().wrapping_add(instruction.size()),
+                sp: Some(chip8.get_sp()),
+            },
+            _ => Mode::Step,
+        };
+    }
+
+    /// Runs until the current subroutine returns with 00EE.
+    pub fn step_out(&mut self, chip8: &Chip8) {
+        self.mode = match chip8.get_sp() {
+            0 => Mode::Step,
+            sp => Mode::StepOut { sp },
+        };
+    }
+
+    /// Runs until PC reaches `address`.
+    pub fn run_to(&mut self, address: u16) {
+        self.mode = Mode::RunTo {
+            pc: address,
+            sp: None,
+        };
+    }
+
+    /// Executes the next instruction, unless the debugger has to stop before it. Returns why
+    /// the program stopped, if it did.
+    pub fn tick(&mut self, chip8: &mut Chip8) -> Option<StopReason> {
+        // Nothing is executed while the CPU is stalled.
+        if chip8.is_halted() || chip8.is_waiting_for_vblank() {
+            return None;
+        }
+
+        let pc = chip8.get_pc();
+        if self.resume_pc != Some(pc) {
+            self.resume_pc = None;
+            if self.breakpoints.contains(&pc) {
+                return self.stop(chip8, StopReason::Breakpoint { pc });
+            }
+        }
+        match self.mode {
+            Mode::Pause => return self.stop(chip8, StopReason::Paused { pc }),
+            Mode::RunTo { pc: target, sp }
+                if target == pc && sp.is_none_or(|sp| sp == chip8.get_sp()) =>
+            {
+                return self.stop(chip8, StopReason::Step { pc });
+            }
+            _ => {}
+        }
+
+        // Watchpoints are checked against the accesses the instruction is about to make.
+        let access = chip8
+            .get_current_instruction()
+            .and_then(|instruction| chip8.memory_access(instruction));
+        let registers: Vec<(Register, u16)> = self
+            .register_watchpoints
+            .iter()
+            .map(|register| (*register, register.value(chip8)))
+            .collect();
+
+        if let Err(error) = chip8.tick() {
+            return self.stop(chip8, StopReason::Error { pc, error });
+        }
+        let pc = chip8.get_pc();
+
+        if let Some((access, range)) = access {
+            let watchpoints = match access {
+                Access::Read => &self.read_watchpoints,
+                Access::Write => &self.write_watchpoints,
+            };
+            if let Some(address) = range
+                .map(|address| address as u16)
+                .find(|address| watchpoints.contains(address))
+            {
+                return self.stop(
+                    chip8,
+                    StopReason::Watchpoint {
+                        pc,
+                        access,
+                        address,
+                    },
+                );
+            }
+        }
+        for (register, old) in registers {
+            let new = register.value(chip8);
+            if new != old {
+                return self.stop(
+                    chip8,
+                    StopReason::RegisterChanged {
+                        pc,
+                        register,
+                        old,
+                        new,
+                    },
+                );
+            }
+        }
+
+        match self.mode {
+            Mode::Step => self.stop(chip8, StopReason::Step { pc }),
+            Mode::StepOut { sp } if chip8.get_sp() < sp => {
+                self.stop(chip8, StopReason::Step { pc })
+            }
+            _ => None,
+        }
+    }
+
+    fn stop(&mut self, chip8: &Chip8, reason: StopReason) -> Option<StopReason> {
+        self.mode = Mode::Running;
+        self.resume_pc = Some(chip8.get_pc());
+        Some(reason)
+    }
+}
+
+/// Describes the registers, stack and timers of the CPU, along with the next instruction.
+pub fn dump_state(chip8: &Chip8) -> String {
+    let mut output = String::new();
+    for (index, value) in chip8.get_registers().iter().enumerate() {
+        let separator = if index % 8 == 7 { "\n" } else { "  " };
+        write!(output, "V{:X}: 0x{:02X}{}", index, value, separator).unwrap();
+    }
+    let instruction = chip8.get_current_instruction().map_or_else(
+        || "invalid instruction".to_string(),
+        |instruction| instruction.to_string(),
+    );
+    writeln!(
+        output,
+        "I: 0x{:03X}  PC: 0x{:03X} ({})  SP: {}",
+        chip8.get_i(),
+        chip8.get_pc(),
+        instruction,
+        chip8.get_sp()
+    )
+    .unwrap();
+    let stack: Vec<String> = chip8
+        .get_stack()
+        .iter()
+        .map(|address| format!("0x{:03X}", address))
+        .collect();
+    writeln!(output, "Stack: [{}]", stack.join(", ")).unwrap();
+    writeln!(
+        output,
+        "DT: {}  ST: {}",
+        chip8.get_delay_timer(),
+        chip8.get_sound_timer()
+    )
+    .unwrap();
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // LD V0, 1; CALL 0x208; JP 0x206; JP 0x206 (loop); LD I, 0x300; LD [I], V1; RET
+    const ROM: [u8; 14] = [
+        0x60, 0x01, 0x22, 0x08, 0x12, 0x06, 0x12, 0x06, 0xA3, 0x00, 0xF1, 0x55, 0x00, 0xEE,
+    ];
+
+    fn chip8() -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.load_file(&ROM).unwrap();
+        chip8
+    }
+
+    fn run(debugger: &mut Debugger, chip8: &mut Chip8) -> Option<StopReason> {
+        (0..100).find_map(|_| debugger.tick(chip8))
+    }
+
+    #[test]
+    fn stops_at_breakpoints_and_resumes_past_them() {
+        let mut chip8 = chip8();
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0x20A);
+        assert_eq!(
+            run(&mut debugger, &mut chip8),
+            Some(StopReason::Breakpoint { pc: 0x20A })
+        );
+        debugger.resume();
+        assert_eq!(debugger.tick(&mut chip8), None);
+        assert_eq!(chip8.get_pc(), 0x20C);
+    }
+
+    #[test]
+    fn steps_into_over_and_out_of_subroutines() {
+        let mut chip8 = chip8();
+        let mut debugger = Debugger::new();
+        debugger.step_into();
+        assert_eq!(
+            debugger.tick(&mut chip8),
+            Some(StopReason::Step { pc: 0x202 })
+        );
+
+        debugger.step_over(&chip8);
+        assert_eq!(
+            run(&mut debugger, &mut chip8),
+            Some(StopReason::Step { pc: 0x204 })
+        );
+        assert_eq!(chip8.get_i(), 0x300);
+
+        let mut chip8 = self::chip8();
+        debugger.run_to(0x20A);
+        assert_eq!(
+            run(&mut debugger, &mut chip8),
+            Some(StopReason::Step { pc: 0x20A })
+        );
+        debugger.step_out(&chip8);
+        assert_eq!(
+            run(&mut debugger, &mut chip8),
+            Some(StopReason::Step { pc: 0x204 })
+        );
+    }
+
+    #[test]
+    fn stops_at_watchpoints() {
+        let mut chip8 = chip8();
+        let mut debugger = Debugger::new();
+        debugger.add_watchpoint(Access::Write, 0x301);
+        assert_eq!(
+            run(&mut debugger, &mut chip8),
+            Some(StopReason::Watchpoint {
+                pc: 0x20C,
+                access: Access::Write,
+                address: 0x301
+            })
+        );
+
+        let mut chip8 = self::chip8();
+        let mut debugger = Debugger::new();
+        debugger.watch_register(Register::from_name("I").unwrap());
+        assert_eq!(
+            run(&mut debugger, &mut chip8),
+            Some(StopReason::RegisterChanged {
+                pc: 0x20A,
+                register: Register::I,
+                old: 0,
+                new: 0x300
+            })
+        );
+    }
+}
@@ -1,4 +1,157 @@
-pub trait Display {
-    fn clear(self);
-    fn draw_at_xy(&mut self, x: u8, y: u8);
+use crate::error::Chip8Error;
+
+/// A part of the screen, in pixels of the active resolution.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DirtyRect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl DirtyRect {
+    /// The smallest rectangle holding both rectangles.
+    pub fn union(self, other: DirtyRect) -> DirtyRect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        DirtyRect {
+            x,
+            y,
+            width: (self.x + self.width).max(other.x + other.width) - x,
+            height: (self.y + self.height).max(other.y + other.height) - y,
+        }
+    }
+}
+
+/// The screen of the active resolution, borrowed from the machine, along with the parts that
+/// changed since the last frame was presented.
+#[derive(Copy, Clone, Debug)]
+pub struct Framebuffer<'a> {
+    /// `width * height` palette indices, row by row.
+    pub pixels: &'a [u8],
+    pub width: usize,
+    pub height: usize,
+    /// The rectangle holding every changed pixel, or None if the screen didn't change.
+    pub dirty: Option<DirtyRect>,
+    /// One bit per changed row, starting with row 0 in the lowest bit. Sprites drawn at the top
+    /// and the bottom of the screen only mark their own rows, unlike `dirty`.
+    pub dirty_rows: u64,
+}
+
+impl Framebuffer<'_> {
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.is_some()
+    }
+
+    pub fn is_row_dirty(&self, y: usize) -> bool {
+        self.dirty_rows & (1 << y) != 0
+    }
+
+    /// The pixels of row `y`.
+    pub fn row(&self, y: usize) -> &[u8] {
+        &self.pixels[y * self.width..(y + 1) * self.width]
+    }
+
+    /// Paints the dirty rows into `buffer`, whose rows hold `width` pixels of `N` elements,
+    /// packing the palette colour of each pixel with `pack`. The other rows are left alone, so
+    /// they keep the colours painted for earlier frames.
+    pub fn paint_dirty_rows<T, const N: usize>(
+        &self,
+        palette: &Palette,
+        buffer: &mut [T],
+        pack: impl Fn(u8, u8, u8) -> [T; N],
+    ) where
+        T: Copy,
+    {
+        let rows = buffer.chunks_exact_mut(self.width * N).take(self.height);
+        for (y, row) in rows.enumerate() {
+            if !self.is_row_dirty(y) {
+                continue;
+            }
+            for (pixel, color) in self.row(y).iter().zip(row.chunks_exact_mut(N)) {
+                let (r, g, b) = palette.colors[(*pixel & 3) as usize];
+                color.copy_from_slice(&pack(r, g, b));
+            }
+        }
+    }
+}
+
+/// Where a frontend draws the screen. `Runner` calls it once per frame.
+pub trait VideoSink {
+    /// Draws a frame. Sinks that keep what they drew may only redraw the dirty parts, or
+    /// nothing at all when the frame isn't dirty.
+    fn present(&mut self, frame: &Framebuffer);
+
+    /// Shows the error that stopped the ROM, or None once it runs again, e.g. after loading a
+    /// save state. Called whenever it changes, before `present`.
+    fn show_error(&mut self, _error: Option<&Chip8Error>) {}
+}
+
+/// Draws nothing, for frontends that only look at the machine, e.g. to run tests.
+impl VideoSink for () {
+    fn present(&mut self, _frame: &Framebuffer) {}
+}
+
+/// The colours used to draw the XO-CHIP bitplanes. Index 0 is the background, 1 the first plane,
+/// 2 the second plane and 3 the pixels where both planes are set.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Palette {
+    pub colors: [(u8, u8, u8); 4],
+}
+
+impl Palette {
+    /// Parses four comma separated hex colours, e.g. `000000,FFFFFF,AAAAAA,555555`.
+    pub fn from_hex_list(list: &str) -> Option<Self> {
+        let mut colors = [(0, 0, 0); 4];
+        let mut parts = list.split(',');
+        for color in colors.iter_mut() {
+            let hex = parts.next()?.trim().trim_start_matches('#');
+            if hex.len() != 6 {
+                return None;
+            }
+            let value = u32::from_str_radix(hex, 16).ok()?;
+            *color = ((value >> 16) as u8, (value >> 8) as u8, value as u8);
+        }
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Self { colors })
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            colors: [(0, 0, 0), (255, 255, 255), (170, 170, 170), (85, 85, 85)],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paints_only_the_dirty_rows() {
+        let pixels = [0, 1, 2, 3];
+        let frame = Framebuffer {
+            pixels: &pixels,
+            width: 2,
+            height: 2,
+            dirty: Some(DirtyRect {
+                x: 0,
+                y: 1,
+                width: 2,
+                height: 1,
+            }),
+            dirty_rows: 0b10,
+        };
+        let mut buffer = [0xFFu8; 2 * 2 * 3];
+        frame.paint_dirty_rows(&Palette::default(), &mut buffer, |r, g, b| [r, g, b]);
+        // The first row is left alone and the second gets the colours of indices 2 and 3.
+        assert_eq!(
+            buffer,
+            [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 170, 170, 170, 85, 85, 85]
+        );
+    }
 }
@@ -0,0 +1,38 @@
+use core::fmt;
+
+/// Errors raised while loading or running a ROM. None of them is fatal for the host process;
+/// the interpreter just can't continue executing the current program.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Chip8Error {
+    /// The instruction at `pc` isn't part of the instruction set of the current platform.
+    InvalidOpcode { pc: u16, opcode: u16 },
+    /// A subroutine call (2NNN) was made with all the stack entries in use.
+    StackOverflow,
+    /// A return (00EE) was executed outside of a subroutine.
+    StackUnderflow,
+    /// An instruction tried to access an address past the end of memory.
+    MemoryOutOfBounds { address: usize },
+    /// The ROM doesn't fit between the start address (0x200) and the end of memory.
+    RomTooLarge { size: usize, max: usize },
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Chip8Error::InvalidOpcode { pc, opcode } => {
+                write!(f, "invalid opcode {:#06x} at {:#05x}", opcode, pc)
+            }
+            Chip8Error::StackOverflow => write!(f, "stack overflow"),
+            Chip8Error::StackUnderflow => write!(f, "stack underflow"),
+            Chip8Error::MemoryOutOfBounds { address } => {
+                write!(f, "memory access out of bounds at {:#x}", address)
+            }
+            Chip8Error::RomTooLarge { size, max } => {
+                write!(f, "ROM too large: {} bytes (at most {} bytes fit in memory)", size, max)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Chip8Error {}
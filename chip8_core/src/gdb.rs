@@ -0,0 +1,515 @@
+//! A stub speaking the GDB Remote Serial Protocol, so ROMs can be debugged from GDB or any
+//! other front end supporting it (`target remote localhost:PORT`).
+//!
+//! Registers are numbered V0 to VF (0 to 15), then I, PC, SP, DT and ST (16 to 20), and sent in
+//! little endian. Memory addresses are the addresses of the CHIP-8 memory.
+
+use std::fmt::Write as _;
+use std::io;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+use crate::chip8::{Chip8, Step};
+use crate::debugger::{Access, Debugger, StopReason};
+use crate::error::Chip8Error;
+
+// Instructions executed between checks for an interrupt from the client.
+const INSTRUCTIONS_PER_POLL: u32 = 1024;
+const NUMBER_OF_REGISTERS: usize = 21;
+const PACKET_SIZE: usize = 0x4000;
+const INTERRUPT: u8 = 0x03;
+
+/// Serves a `Chip8` to a single GDB client at a time.
+pub struct GdbStub {
+    chip8: Chip8,
+    debugger: Debugger,
+}
+
+impl GdbStub {
+    pub fn new(chip8: Chip8) -> Self {
+        Self {
+            chip8,
+            debugger: Debugger::new(),
+        }
+    }
+
+    pub fn chip8(&self) -> &Chip8 {
+        &self.chip8
+    }
+
+    pub fn into_inner(self) -> Chip8 {
+        self.chip8
+    }
+
+    /// Waits for a client on `address` and serves it until it detaches or disconnects.
+    pub fn listen(&mut self, address: impl ToSocketAddrs) -> io::Result<()> {
+        let listener = TcpListener::bind(address)?;
+        let (stream, _) = listener.accept()?;
+        self.serve(stream)
+    }
+
+    /// Serves a connected client until it detaches or disconnects.
+    pub fn serve(&mut self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_nodelay(true)?;
+        while let Some(packet) = read_packet(&mut stream)? {
+            stream.write_all(b"+")?;
+            let reply = match packet.as_str() {
+                "D" | "k" => {
+                    send_packet(&mut stream, "OK")?;
+                    return Ok(());
+                }
+                "c" => self.resume(&mut stream, false)?,
+                "s" => self.resume(&mut stream, true)?,
+                _ => self.handle(&packet),
+            };
+            send_packet(&mut stream, &reply)?;
+        }
+        Ok(())
+    }
+
+    // Handles every packet that doesn't run the program. Unsupported packets get an empty
+    // reply, as the protocol requires.
+    fn handle(&mut self, packet: &str) -> String {
+        let reply = match packet.as_bytes().first() {
+            Some(b'?') => Some("S05".to_string()),
+            Some(b'g') => Some(self.read_registers()),
+            Some(b'G') => self.write_registers(&packet[1..]),
+            Some(b'p') => usize::from_str_radix(&packet[1..], 16)
+                .ok()
+                .and_then(|register| self.read_register(register)),
+            Some(b'P') => self.write_register(&packet[1..]),
+            Some(b'm') => self.read_memory(&packet[1..]),
+            Some(b'M') => self.write_memory(&packet[1..]),
+            Some(b'Z') => self.set_breakpoint(&packet[1..], true),
+            Some(b'z') => self.set_breakpoint(&packet[1..], false),
+            Some(b'H') => Some("OK".to_string()),
+            Some(b'q') => self.query(packet),
+            _ => Some(String::new()),
+        };
+        reply.unwrap_or_else(|| "E01".to_string())
+    }
+
+    fn query(&self, packet: &str) -> Option<String> {
+        if packet.starts_with("qSupported") {
+            return Some(format!(
+                "PacketSize={:X};qXfer:features:read+;swbreak+",
+                PACKET_SIZE
+            ));
+        }
+        if let Some(request) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let (offset, length) = request.split_once(',')?;
+            let offset = usize::from_str_radix(offset, 16).ok()?;
+            let length = usize::from_str_radix(length, 16).ok()?;
+            let xml = target_xml();
+            let start = offset.min(xml.len());
+            let end = (start + length).min(xml.len());
+            // `l` marks the last chunk, `m` tells the client to ask for more.
+            let marker = if end == xml.len() { 'l' } else { 'm' };
+            return Some(format!("{}{}", marker, &xml[start..end]));
+        }
+        let reply = match packet {
+            "qAttached" => "1",
+            "qC" => "QC1",
+            "qfThreadInfo" => "m1",
+            "qsThreadInfo" => "l",
+            _ => "",
+        };
+        Some(reply.to_string())
+    }
+
+    fn register_bytes(&self, register: usize) -> Option<Vec<u8>> {
+        let chip8 = &self.chip8;
+        let bytes = match register {
+            0..=15 => vec![chip8.get_registers()[register]],
+            16 => chip8.get_i().to_le_bytes().to_vec(),
+            17 => chip8.get_pc().to_le_bytes().to_vec(),
+            18 => vec![chip8.get_sp()],
+            19 => vec![chip8.get_delay_timer()],
+            20 => vec![chip8.get_sound_timer()],
+            _ => return None,
+        };
+        Some(bytes)
+    }
+
+    // Writes `bytes` to the register, returning how many bytes it takes.
+    fn set_register(&mut self, register: usize, bytes: &[u8]) -> Option<usize> {
+        let word = || Some(u16::from_le_bytes([*bytes.first()?, *bytes.get(1)?]));
+        let byte = *bytes.first()?;
+        let chip8 = &mut self.chip8;
+        match register {
+            0..=15 => chip8.set_register(register as u8, byte),
+            16 => chip8.set_i(word()?),
+            17 => chip8.set_pc(word()?),
+            18 => chip8.set_sp(byte),
+            19 => chip8.set_delay_timer(byte),
+            20 => chip8.set_sound_timer(byte),
+            _ => return None,
+        }
+        Some(if register == 16 || register == 17 {
+            2
+        } else {
+            1
+        })
+    }
+
+    fn read_registers(&self) -> String {
+        let bytes: Vec<u8> = (0..NUMBER_OF_REGISTERS)
+            .flat_map(|register| self.register_bytes(register).unwrap_or_default())
+            .collect();
+        encode_hex(&bytes)
+    }
+
+    fn write_registers(&mut self, data: &str) -> Option<String> {
+        let bytes = decode_hex(data)?;
+        let mut offset = 0;
+        for register in 0..NUMBER_OF_REGISTERS {
+            offset += self.set_register(register, bytes.get(offset..)?)?;
+        }
+        Some("OK".to_string())
+    }
+
+    fn read_register(&self, register: usize) -> Option<String> {
+        self.register_bytes(register)
+            .map(|bytes| encode_hex(&bytes))
+    }
+
+    fn write_register(&mut self, data: &str) -> Option<String> {
+        let (register, value) = data.split_once('=')?;
+        let register = usize::from_str_radix(register, 16).ok()?;
+        self.set_register(register, &decode_hex(value)?)?;
+        Some("OK".to_string())
+    }
+
+    fn read_memory(&self, data: &str) -> Option<String> {
+        let (address, length) = parse_range(data)?;
+        let memory = self.chip8.get_memory();
+        let end = address.checked_add(length)?;
+        Some(encode_hex(memory.get(address..end)?))
+    }
+
+    fn write_memory(&mut self, data: &str) -> Option<String> {
+        let (range, bytes) = data.split_once(':')?;
+        let (address, length) = parse_range(range)?;
+        let bytes = decode_hex(bytes)?;
+        if bytes.len() != length {
+            return None;
+        }
+        let memory = self.chip8.get_memory_mut();
+        let end = address.checked_add(length)?;
+        memory.get_mut(address..end)?.copy_from_slice(&bytes);
+        Some("OK".to_string())
+    }
+
+    // Z0/z0 (software breakpoints) and Z2/Z3/Z4 (write, read and access watchpoints).
+    fn set_breakpoint(&mut self, data: &str, insert: bool) -> Option<String> {
+        let (kind, range) = data.split_once(',')?;
+        let (address, length) = match range.split_once(',') {
+            Some((address, length)) => (address, usize::from_str_radix(length, 16).ok()?),
+            None => (range, 1),
+        };
+        let address = u16::from_str_radix(address, 16).ok()?;
+        let accesses: &[Access] = match kind {
+            "0" => {
+                if insert {
+                    self.debugger.add_breakpoint(address);
+                } else {
+                    self.debugger.remove_breakpoint(address);
+                }
+                return Some("OK".to_string());
+            }
+            "2" => &[Access::Write],
+            "3" => &[Access::Read],
+            "4" => &[Access::Read, Access::Write],
+            _ => return Some(String::new()),
+        };
+        for offset in 0..length as u16 {
+            for access in accesses {
+                if insert {
+                    self.debugger
+                        .add_watchpoint(*access, address.wrapping_add(offset));
+                } else {
+                    self.debugger
+                        .remove_watchpoint(*access, address.wrapping_add(offset));
+                }
+            }
+        }
+        Some("OK".to_string())
+    }
+
+    // Runs the program until it stops, the client interrupts it, or after a single
+    // instruction when stepping. Frames run at the speed of the machine, so the timers tick
+    // as often as when the ROM runs in a frontend.
+    fn resume(&mut self, stream: &mut TcpStream, step: bool) -> io::Result<String> {
+        if step {
+            self.debugger.step_into();
+        } else {
+            self.debugger.resume();
+        }
+        let mut instructions: u32 = 0;
+        loop {
+            let mut reply = None;
+            let debugger = &mut self.debugger;
+            self.chip8.run_frame_with(|chip8| {
+                instructions += 1;
+                if chip8.is_halted() {
+                    reply = Some("W00".to_string());
+                    return Ok(Step::Abort);
+                }
+                // Stopping ends the frame, so that stepping or breaking in a loop polling DT
+                // still sees the timers run.
+                match debugger.tick(chip8) {
+                    Some(reason) => {
+                        reply = Some(stop_reply(reason));
+                        Ok(Step::EndFrame)
+                    }
+                    None => Ok(Step::Continue),
+                }
+            });
+            if let Some(reply) = reply {
+                return Ok(reply);
+            }
+            // Frames count too, so the client can interrupt a machine running no instructions.
+            instructions += 1;
+            if instructions >= INSTRUCTIONS_PER_POLL {
+                if interrupted(stream)? {
+                    return Ok("S02".to_string());
+                }
+                instructions = 0;
+            }
+        }
+    }
+}
+
+fn stop_reply(reason: StopReason) -> String {
+    match reason {
+        StopReason::Breakpoint { .. } => "T05swbreak:;".to_string(),
+        StopReason::Watchpoint {
+            access: Access::Write,
+            address,
+            ..
+        } => format!("T05watch:{:X};", address),
+        StopReason::Watchpoint {
+            access: Access::Read,
+            address,
+            ..
+        } => format!("T05rwatch:{:X};", address),
+        // SIGILL for invalid instructions and SIGSEGV for everything else.
+        StopReason::Error {
+            error: Chip8Error::InvalidOpcode { .. },
+            ..
+        } => "S04".to_string(),
+        StopReason::Error { .. } => "S0B".to_string(),
+        _ => "S05".to_string(),
+    }
+}
+
+// Checks whether the client sent Ctrl-C (0x03) without waiting for it.
+fn interrupted(stream: &mut TcpStream) -> io::Result<bool> {
+    stream.set_nonblocking(true)?;
+    let mut byte = [0];
+    let result = stream.read(&mut byte);
+    stream.set_nonblocking(false)?;
+    match result {
+        Ok(1) => Ok(byte[0] == INTERRUPT),
+        // The client disconnected, so there's no one left to stop for.
+        Ok(_) => Ok(true),
+        Err(error) if error.kind() == io::ErrorKind::WouldBlock => Ok(false),
+        Err(error) => Err(error),
+    }
+}
+
+fn read_byte(stream: &mut TcpStream) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    match stream.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+// Reads the next `$data#checksum` packet, skipping acknowledgements. None once the client
+// disconnects.
+fn read_packet(stream: &mut TcpStream) -> io::Result<Option<String>> {
+    loop {
+        match read_byte(stream)? {
+            None => return Ok(None),
+            Some(b'$') => break,
+            Some(_) => {}
+        }
+    }
+    let mut data = Vec::new();
+    loop {
+        match read_byte(stream)? {
+            None => return Ok(None),
+            Some(b'#') => break,
+            Some(byte) => data.push(byte),
+        }
+    }
+    // The checksum is only useful on unreliable links, which TCP isn't.
+    for _ in 0..2 {
+        if read_byte(stream)?.is_none() {
+            return Ok(None);
+        }
+    }
+    Ok(Some(String::from_utf8_lossy(&data).into_owned()))
+}
+
+fn send_packet(stream: &mut TcpStream, data: &str) -> io::Result<()> {
+    let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+    write!(stream, "${}#{:02x}", data, checksum)?;
+    stream.flush()
+}
+
+fn parse_range(data: &str) -> Option<(usize, usize)> {
+    let (address, length) = data.split_once(',')?;
+    Some((
+        usize::from_str_radix(address, 16).ok()?,
+        usize::from_str_radix(length, 16).ok()?,
+    ))
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    let mut output = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        write!(output, "{:02x}", byte).unwrap();
+    }
+    output
+}
+
+fn decode_hex(data: &str) -> Option<Vec<u8>> {
+    if !data.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..data.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(data.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+/// The target description sent to the client, naming and sizing every register.
+pub fn target_xml() -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\"?>\n\
+         <!DOCTYPE target SYSTEM \"gdb-target.dtd\">\n\
+         <target version=\"1.0\">\n\
+         <feature name=\"org.rchip8.chip8\">\n",
+    );
+    for register in 0..16 {
+        writeln!(
+            xml,
+            "<reg name=\"v{:x}\" bitsize=\"8\" type=\"uint8\" regnum=\"{}\"/>",
+            register, register
+        )
+        .unwrap();
+    }
+    xml.push_str(
+        "<reg name=\"i\" bitsize=\"16\" type=\"data_ptr\" regnum=\"16\"/>\n\
+         <reg name=\"pc\" bitsize=\"16\" type=\"code_ptr\" regnum=\"17\"/>\n\
+         <reg name=\"sp\" bitsize=\"8\" type=\"uint8\" regnum=\"18\"/>\n\
+         <reg name=\"dt\" bitsize=\"8\" type=\"uint8\" regnum=\"19\"/>\n\
+         <reg name=\"st\" bitsize=\"8\" type=\"uint8\" regnum=\"20\"/>\n\
+         </feature>\n\
+         </target>\n",
+    );
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    struct Client {
+        stream: TcpStream,
+    }
+
+    impl Client {
+        // Sends a packet and returns the reply, checking the acknowledgement.
+        fn send(&mut self, data: &str) -> String {
+            send_packet(&mut self.stream, data).unwrap();
+            assert_eq!(read_byte(&mut self.stream).unwrap(), Some(b'+'));
+            let reply = read_packet(&mut self.stream).unwrap().unwrap();
+            self.stream.write_all(b"+").unwrap();
+            reply
+        }
+    }
+
+    #[test]
+    fn serves_a_scripted_session_over_loopback() {
+        // LD V0, 5; ADD V0, 1; JP 0x202
+        let mut chip8 = Chip8::new();
+        chip8
+            .load_file(&[0x60, 0x05, 0x70, 0x01, 0x12, 0x02])
+            .unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut stub = GdbStub::new(chip8);
+            stub.serve(stream).unwrap();
+            stub.into_inner()
+        });
+
+        let mut client = Client {
+            stream: TcpStream::connect(address).unwrap(),
+        };
+        assert!(client
+            .send("qSupported:swbreak+")
+            .contains("qXfer:features:read+"));
+        let xml = client.send("qXfer:features:read:target.xml:0,4000");
+        assert!(xml.starts_with('l') && xml.contains("name=\"pc\""));
+        assert_eq!(client.send("?"), "S05");
+        assert_eq!(client.send("p11"), "0002");
+
+        assert_eq!(client.send("s"), "S05");
+        assert_eq!(client.send("p0"), "05");
+        assert_eq!(client.send("Z0,204,2"), "OK");
+        assert_eq!(client.send("c"), "T05swbreak:;");
+        assert_eq!(client.send("p0"), "06");
+        assert_eq!(client.send("z0,204,2"), "OK");
+
+        assert_eq!(client.send("P0=2a"), "OK");
+        assert_eq!(client.send("P10=0003"), "OK");
+        assert_eq!(client.send("M300,2:abcd"), "OK");
+        assert_eq!(client.send("m300,2"), "abcd");
+        assert_eq!(client.send("m200,2"), "6005");
+        let registers = client.send("g");
+        assert_eq!(&registers[..2], "2a");
+        assert_eq!(&registers[32..40], "00030402");
+        assert_eq!(client.send("D"), "OK");
+
+        let chip8 = server.join().unwrap();
+        assert_eq!(chip8.get_registers()[0], 0x2A);
+        assert_eq!(chip8.get_i(), 0x300);
+        assert_eq!(chip8.get_memory()[0x300], 0xAB);
+    }
+
+    #[test]
+    fn timers_tick_when_the_debugger_stops() {
+        // LD V0, 10; LD DT, V0; loop: LD V0, DT; SE V0, 0; JP loop; end: JP end
+        let mut chip8 = Chip8::new();
+        chip8
+            .load_file(&[
+                0x60, 0x0A, 0xF0, 0x15, 0xF0, 0x07, 0x30, 0x00, 0x12, 0x04, 0x12, 0x0A,
+            ])
+            .unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut stub = GdbStub::new(chip8);
+            stub.serve(stream).unwrap();
+        });
+
+        let mut client = Client {
+            stream: TcpStream::connect(address).unwrap(),
+        };
+        // Each stop at SE ends a frame, so the loop sees DT count down to 0.
+        assert_eq!(client.send("Z0,206,2"), "OK");
+        for delay in (0..=10).rev() {
+            assert_eq!(client.send("c"), "T05swbreak:;");
+            assert_eq!(client.send("p0"), format!("{:02x}", delay));
+        }
+        assert_eq!(client.send("D"), "OK");
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,16 @@
+use crate::runner::Runner;
+
+/// Where a frontend reads the keypad from. `Runner` polls it at the start of every frame.
+pub trait InputSource {
+    /// Handles the input received since the last frame, e.g. by pressing keys with
+    /// `runner.chip8.key_down`. Frontend commands, like loading a save state, can change the
+    /// runner too. Returns false to stop running, e.g. when the window is closed.
+    fn poll(&mut self, runner: &mut Runner) -> bool;
+}
+
+/// No input, for frontends that press the keys on the machine directly.
+impl InputSource for () {
+    fn poll(&mut self, _runner: &mut Runner) -> bool {
+        true
+    }
+}
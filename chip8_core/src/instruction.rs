@@ -0,0 +1,369 @@
+use core::fmt;
+
+use crate::platform::Platform;
+
+/// A decoded CHIP-8, SUPER-CHIP or XO-CHIP instruction. `x` and `y` are register indexes,
+/// `nn` an 8 bit immediate, `n` a 4 bit immediate and `addr` a 12 bit address.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    /// 0NNN: calls a machine code routine. Only 0000 is executed, as a no-op.
+    Sys { addr: u16 },
+    /// 00CN: scrolls the screen N pixels down (SUPER-CHIP).
+    ScrollDown { n: u8 },
+    /// 00DN: scrolls the screen N pixels up (XO-CHIP).
+    ScrollUp { n: u8 },
+    /// 00E0: clears the screen.
+    Clear,
+    /// 00EE: returns from a subroutine.
+    Return,
+    /// 00FB: scrolls the screen 4 pixels right (SUPER-CHIP).
+    ScrollRight,
+    /// 00FC: scrolls the screen 4 pixels left (SUPER-CHIP).
+    ScrollLeft,
+    /// 00FD: exits the interpreter (SUPER-CHIP).
+    Exit,
+    /// 00FE: switches to the 64x32 low resolution mode (SUPER-CHIP).
+    Lores,
+    /// 00FF: switches to the 128x64 high resolution mode (SUPER-CHIP).
+    Hires,
+    /// 1NNN: jumps to NNN.
+    Jump { addr: u16 },
+    /// 2NNN: calls the subroutine at NNN.
+    Call { addr: u16 },
+    /// 3XNN: skips the next instruction if VX == NN.
+    SkipEqImm { x: u8, nn: u8 },
+    /// 4XNN: skips the next instruction if VX != NN.
+    SkipNeImm { x: u8, nn: u8 },
+    /// 5XY0: skips the next instruction if VX == VY.
+    SkipEqReg { x: u8, y: u8 },
+    /// 5XY2: saves VX..VY to memory starting at I (XO-CHIP).
+    SaveRange { x: u8, y: u8 },
+    /// 5XY3: loads VX..VY from memory starting at I (XO-CHIP).
+    LoadRange { x: u8, y: u8 },
+    /// 6XNN: VX = NN.
+    LoadImm { x: u8, nn: u8 },
+    /// 7XNN: VX += NN, without carry.
+    AddImm { x: u8, nn: u8 },
+    /// 8XY0: VX = VY.
+    Move { x: u8, y: u8 },
+    /// 8XY1: VX |= VY.
+    Or { x: u8, y: u8 },
+    /// 8XY2: VX &= VY.
+    And { x: u8, y: u8 },
+    /// 8XY3: VX ^= VY.
+    Xor { x: u8, y: u8 },
+    /// 8XY4: VX += VY, VF = carry.
+    Add { x: u8, y: u8 },
+    /// 8XY5: VX -= VY, VF = not borrow.
+    Sub { x: u8, y: u8 },
+    /// 8XY6: VX >>= 1, VF = shifted out bit.
+    ShiftRight { x: u8, y: u8 },
+    /// 8XY7: VX = VY - VX, VF = not borrow.
+    SubReverse { x: u8, y: u8 },
+    /// 8XYE: VX <<= 1, VF = shifted out bit.
+    ShiftLeft { x: u8, y: u8 },
+    /// 9XY0: skips the next instruction if VX != VY.
+    SkipNeReg { x: u8, y: u8 },
+    /// ANNN: I = NNN.
+    LoadI { addr: u16 },
+    /// BNNN: jumps to V0 + NNN (or VX + NNN with the jump quirk).
+    JumpOffset { addr: u16 },
+    /// CXNN: VX = random byte & NN.
+    Random { x: u8, nn: u8 },
+    /// DXYN: draws an N rows tall sprite at (VX, VY). DXY0 draws a 16x16 sprite on SUPER-CHIP.
+    Draw { x: u8, y: u8, n: u8 },
+    /// EX9E: skips the next instruction if the key VX is pressed.
+    SkipKeyPressed { x: u8 },
+    /// EXA1: skips the next instruction if the key VX isn't pressed.
+    SkipKeyNotPressed { x: u8 },
+    /// F000 NNNN: loads the 16 bit address stored after the instruction into I (XO-CHIP).
+    LoadLongI,
+    /// FN01: selects the bitplanes used for drawing (XO-CHIP).
+    Plane { n: u8 },
+    /// F002: loads 16 bytes starting at I into the audio pattern buffer (XO-CHIP).
+    Audio,
+    /// FX07: VX = delay timer.
+    LoadDelay { x: u8 },
+    /// FX0A: waits for a key press and stores it in VX.
+    WaitKey { x: u8 },
+    /// FX15: delay timer = VX.
+    SetDelay { x: u8 },
+    /// FX18: sound timer = VX.
+    SetSound { x: u8 },
+    /// FX1E: I += VX.
+    AddI { x: u8 },
+    /// FX29: points I at the small font sprite of the digit in VX.
+    LoadFont { x: u8 },
+    /// FX30: points I at the big font sprite of the digit in VX (SUPER-CHIP).
+    LoadBigFont { x: u8 },
+    /// FX33: stores the BCD representation of VX at I, I + 1 and I + 2.
+    Bcd { x: u8 },
+    /// FX3A: sets the pitch of the audio pattern to VX (XO-CHIP).
+    Pitch { x: u8 },
+    /// FX55: stores V0..VX in memory starting at I.
+    Store { x: u8 },
+    /// FX65: loads V0..VX from memory starting at I.
+    Load { x: u8 },
+    /// FX75: saves V0..VX to the RPL user flags (SUPER-CHIP).
+    SaveFlags { x: u8 },
+    /// FX85: loads V0..VX from the RPL user flags (SUPER-CHIP).
+    LoadFlags { x: u8 },
+}
+
+impl Instruction {
+    /// Decodes a single opcode. Returns None if it isn't part of any supported instruction set.
+    pub fn decode(opcode: u16) -> Option<Self> {
+        let addr = opcode & 0x0FFF;
+        let nn = (opcode & 0x00FF) as u8;
+        let n = (opcode & 0x000F) as u8;
+
+        let first_nibble = (opcode & 0xF000) >> 12;
+        // Getting the lower 4 bits of the high byte of the instruction. EX:
+        // instruction = 0x34ff -> in this example 4 represents the lower four bits of the high
+        // byte. If we shift 8 bits (f -> 4, f -> 4) we get 0x0034.
+        // if we perform bitwise and on 0x0034 and 0x00f we'll end with the lower 4 bytes:
+        // 0x0034 & 0x000f = 0x0004.
+        let x = ((opcode & 0x0F00) >> 8) as u8;
+        // Getting the higher 4 bits of the low byte instruction. We apply the same principle
+        // explained above.
+        let y = ((opcode & 0x00F0) >> 4) as u8;
+
+        let instruction = match (first_nibble, x, y, n) {
+            (0, 0, 0xC, _) => Instruction::ScrollDown { n },
+            (0, 0, 0xD, _) => Instruction::ScrollUp { n },
+            (0, 0, 0xE, 0) => Instruction::Clear,
+            (0, 0, 0xE, 0xE) => Instruction::Return,
+            (0, 0, 0xF, 0xB) => Instruction::ScrollRight,
+            (0, 0, 0xF, 0xC) => Instruction::ScrollLeft,
+            (0, 0, 0xF, 0xD) => Instruction::Exit,
+            (0, 0, 0xF, 0xE) => Instruction::Lores,
+            (0, 0, 0xF, 0xF) => Instruction::Hires,
+            (0, _, _, _) => Instruction::Sys { addr },
+            (1, _, _, _) => Instruction::Jump { addr },
+            (2, _, _, _) => Instruction::Call { addr },
+            (3, _, _, _) => Instruction::SkipEqImm { x, nn },
+            (4, _, _, _) => Instruction::SkipNeImm { x, nn },
+            (5, _, _, 0) => Instruction::SkipEqReg { x, y },
+            (5, _, _, 2) => Instruction::SaveRange { x, y },
+            (5, _, _, 3) => Instruction::LoadRange { x, y },
+            (6, _, _, _) => Instruction::LoadImm { x, nn },
+            (7, _, _, _) => Instruction::AddImm { x, nn },
+            (8, _, _, 0) => Instruction::Move { x, y },
+            (8, _, _, 1) => Instruction::Or { x, y },
+            (8, _, _, 2) => Instruction::And { x, y },
+            (8, _, _, 3) => Instruction::Xor { x, y },
+            (8, _, _, 4) => Instruction::Add { x, y },
+            (8, _, _, 5) => Instruction::Sub { x, y },
+            (8, _, _, 6) => Instruction::ShiftRight { x, y },
+            (8, _, _, 7) => Instruction::SubReverse { x, y },
+            (8, _, _, 0xE) => Instruction::ShiftLeft { x, y },
+            (9, _, _, 0) => Instruction::SkipNeReg { x, y },
+            (0xA, _, _, _) => Instruction::LoadI { addr },
+            (0xB, _, _, _) => Instruction::JumpOffset { addr },
+            (0xC, _, _, _) => Instruction::Random { x, nn },
+            (0xD, _, _, _) => Instruction::Draw { x, y, n },
+            (0xE, _, 9, 0xE) => Instruction::SkipKeyPressed { x },
+            (0xE, _, 0xA, 1) => Instruction::SkipKeyNotPressed { x },
+            (0xF, 0, 0, 0) => Instruction::LoadLongI,
+            (0xF, _, 0, 1) => Instruction::Plane { n: x },
+            (0xF, 0, 0, 2) => Instruction::Audio,
+            (0xF, _, 0, 7) => Instruction::LoadDelay { x },
+            (0xF, _, 0, 0xA) => Instruction::WaitKey { x },
+            (0xF, _, 1, 5) => Instruction::SetDelay { x },
+            (0xF, _, 1, 8) => Instruction::SetSound { x },
+            (0xF, _, 1, 0xE) => Instruction::AddI { x },
+            (0xF, _, 2, 9) => Instruction::LoadFont { x },
+            (0xF, _, 3, 0) => Instruction::LoadBigFont { x },
+            (0xF, _, 3, 3) => Instruction::Bcd { x },
+            (0xF, _, 3, 0xA) => Instruction::Pitch { x },
+            (0xF, _, 5, 5) => Instruction::Store { x },
+            (0xF, _, 6, 5) => Instruction::Load { x },
+            (0xF, _, 7, 5) => Instruction::SaveFlags { x },
+            (0xF, _, 8, 5) => Instruction::LoadFlags { x },
+            (_, _, _, _) => return None,
+        };
+        Some(instruction)
+    }
+
+    /// Encodes the instruction back into its opcode. `Instruction::decode(i.encode())` always
+    /// returns `i`.
+    pub fn encode(&self) -> u16 {
+        let xnn = |prefix: u16, x: u8, nn: u8| prefix << 12 | (x as u16 & 0xF) << 8 | nn as u16;
+        let xyn = |prefix: u16, x: u8, y: u8, n: u16| {
+            prefix << 12 | (x as u16 & 0xF) << 8 | (y as u16 & 0xF) << 4 | (n & 0xF)
+        };
+        let fx = |x: u8, low: u16| 0xF000 | (x as u16 & 0xF) << 8 | low;
+        match *self {
+            Instruction::Sys { addr } => addr & 0x0FFF,
+            Instruction::ScrollDown { n } => 0x00C0 | (n as u16 & 0xF),
+            Instruction::ScrollUp { n } => 0x00D0 | (n as u16 & 0xF),
+            Instruction::Clear => 0x00E0,
+            Instruction::Return => 0x00EE,
+            Instruction::ScrollRight => 0x00FB,
+            Instruction::ScrollLeft => 0x00FC,
+            Instruction::Exit => 0x00FD,
+            Instruction::Lores => 0x00FE,
+            Instruction::Hires => 0x00FF,
+            Instruction::Jump { addr } => 0x1000 | (addr & 0x0FFF),
+            Instruction::Call { addr } => 0x2000 | (addr & 0x0FFF),
+            Instruction::SkipEqImm { x, nn } => xnn(3, x, nn),
+            Instruction::SkipNeImm { x, nn } => xnn(4, x, nn),
+            Instruction::SkipEqReg { x, y } => xyn(5, x, y, 0),
+            Instruction::SaveRange { x, y } => xyn(5, x, y, 2),
+            Instruction::LoadRange { x, y } => xyn(5, x, y, 3),
+            Instruction::LoadImm { x, nn } => xnn(6, x, nn),
+            Instruction::AddImm { x, nn } => xnn(7, x, nn),
+            Instruction::Move { x, y } => xyn(8, x, y, 0),
+            Instruction::Or { x, y } => xyn(8, x, y, 1),
+            Instruction::And { x, y } => xyn(8, x, y, 2),
+            Instruction::Xor { x, y } => xyn(8, x, y, 3),
+            Instruction::Add { x, y } => xyn(8, x, y, 4),
+            Instruction::Sub { x, y } => xyn(8, x, y, 5),
+            Instruction::ShiftRight { x, y } => xyn(8, x, y, 6),
+            Instruction::SubReverse { x, y } => xyn(8, x, y, 7),
+            Instruction::ShiftLeft { x, y } => xyn(8, x, y, 0xE),
+            Instruction::SkipNeReg { x, y } => xyn(9, x, y, 0),
+            Instruction::LoadI { addr } => 0xA000 | (addr & 0x0FFF),
+            Instruction::JumpOffset { addr } => 0xB000 | (addr & 0x0FFF),
+            Instruction::Random { x, nn } => xnn(0xC, x, nn),
+            Instruction::Draw { x, y, n } => xyn(0xD, x, y, n as u16),
+            Instruction::SkipKeyPressed { x } => xnn(0xE, x, 0x9E),
+            Instruction::SkipKeyNotPressed { x } => xnn(0xE, x, 0xA1),
+            Instruction::LoadLongI => 0xF000,
+            Instruction::Plane { n } => fx(n, 0x01),
+            Instruction::Audio => 0xF002,
+            Instruction::LoadDelay { x } => fx(x, 0x07),
+            Instruction::WaitKey { x } => fx(x, 0x0A),
+            Instruction::SetDelay { x } => fx(x, 0x15),
+            Instruction::SetSound { x } => fx(x, 0x18),
+            Instruction::AddI { x } => fx(x, 0x1E),
+            Instruction::LoadFont { x } => fx(x, 0x29),
+            Instruction::LoadBigFont { x } => fx(x, 0x30),
+            Instruction::Bcd { x } => fx(x, 0x33),
+            Instruction::Pitch { x } => fx(x, 0x3A),
+            Instruction::Store { x } => fx(x, 0x55),
+            Instruction::Load { x } => fx(x, 0x65),
+            Instruction::SaveFlags { x } => fx(x, 0x75),
+            Instruction::LoadFlags { x } => fx(x, 0x85),
+        }
+    }
+
+    /// The first platform that supports the instruction.
+    pub fn platform(&self) -> Platform {
+        match self {
+            Instruction::ScrollDown { .. }
+            | Instruction::ScrollRight
+            | Instruction::ScrollLeft
+            | Instruction::Exit
+            | Instruction::Lores
+            | Instruction::Hires
+            | Instruction::LoadBigFont { .. }
+            | Instruction::SaveFlags { .. }
+            | Instruction::LoadFlags { .. } => Platform::SuperChip,
+            Instruction::ScrollUp { .. }
+            | Instruction::SaveRange { .. }
+            | Instruction::LoadRange { .. }
+            | Instruction::LoadLongI
+            | Instruction::Plane { .. }
+            | Instruction::Audio
+            | Instruction::Pitch { .. } => Platform::XoChip,
+            _ => Platform::Chip8,
+        }
+    }
+
+    /// Size of the instruction in bytes. F000 NNNN is the only one followed by an operand.
+    pub fn size(&self) -> u16 {
+        match self {
+            Instruction::LoadLongI => 4,
+            _ => 2,
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Instruction::Sys { addr } => write!(f, "SYS 0x{:03X}", addr),
+            Instruction::ScrollDown { n } => write!(f, "SCD {}", n),
+            Instruction::ScrollUp { n } => write!(f, "SCU {}", n),
+            Instruction::Clear => write!(f, "CLS"),
+            Instruction::Return => write!(f, "RET"),
+            Instruction::ScrollRight => write!(f, "SCR"),
+            Instruction::ScrollLeft => write!(f, "SCL"),
+            Instruction::Exit => write!(f, "EXIT"),
+            Instruction::Lores => write!(f, "LOW"),
+            Instruction::Hires => write!(f, "HIGH"),
+            Instruction::Jump { addr } => write!(f, "JP 0x{:03X}", addr),
+            Instruction::Call { addr } => write!(f, "CALL 0x{:03X}", addr),
+            Instruction::SkipEqImm { x, nn } => write!(f, "SE V{:X}, 0x{:02X}", x, nn),
+            Instruction::SkipNeImm { x, nn } => write!(f, "SNE V{:X}, 0x{:02X}", x, nn),
+            Instruction::SkipEqReg { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::SaveRange { x, y } => write!(f, "SAVE V{:X} - V{:X}", x, y),
+            Instruction::LoadRange { x, y } => write!(f, "LOAD V{:X} - V{:X}", x, y),
+            Instruction::LoadImm { x, nn } => write!(f, "LD V{:X}, 0x{:02X}", x, nn),
+            Instruction::AddImm { x, nn } => write!(f, "ADD V{:X}, 0x{:02X}", x, nn),
+            Instruction::Move { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            Instruction::Or { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            Instruction::And { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            Instruction::Xor { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instruction::Add { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instruction::Sub { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Instruction::ShiftRight { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Instruction::SubReverse { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Instruction::ShiftLeft { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Instruction::SkipNeReg { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instruction::LoadI { addr } => write!(f, "LD I, 0x{:03X}", addr),
+            Instruction::JumpOffset { addr } => write!(f, "JP V0, 0x{:03X}", addr),
+            Instruction::Random { x, nn } => write!(f, "RND V{:X}, 0x{:02X}", x, nn),
+            Instruction::Draw { x, y, n } => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::SkipKeyPressed { x } => write!(f, "SKP V{:X}", x),
+            Instruction::SkipKeyNotPressed { x } => write!(f, "SKNP V{:X}", x),
+            Instruction::LoadLongI => write!(f, "LD I, LONG"),
+            Instruction::Plane { n } => write!(f, "PLANE {}", n),
+            Instruction::Audio => write!(f, "AUDIO"),
+            Instruction::LoadDelay { x } => write!(f, "LD V{:X}, DT", x),
+            Instruction::WaitKey { x } => write!(f, "LD V{:X}, K", x),
+            Instruction::SetDelay { x } => write!(f, "LD DT, V{:X}", x),
+            Instruction::SetSound { x } => write!(f, "LD ST, V{:X}", x),
+            Instruction::AddI { x } => write!(f, "ADD I, V{:X}", x),
+            Instruction::LoadFont { x } => write!(f, "LD F, V{:X}", x),
+            Instruction::LoadBigFont { x } => write!(f, "LD HF, V{:X}", x),
+            Instruction::Bcd { x } => write!(f, "LD B, V{:X}", x),
+            Instruction::Pitch { x } => write!(f, "PITCH V{:X}", x),
+            Instruction::Store { x } => write!(f, "LD [I], V{:X}", x),
+            Instruction::Load { x } => write!(f, "LD V{:X}, [I]", x),
+            Instruction::SaveFlags { x } => write!(f, "LD R, V{:X}", x),
+            Instruction::LoadFlags { x } => write!(f, "LD V{:X}, R", x),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_and_encode_round_trip() {
+        for opcode in 0..=u16::MAX {
+            if let Some(instruction) = Instruction::decode(opcode) {
+                assert_eq!(instruction.encode(), opcode, "{}", instruction);
+            }
+        }
+    }
+
+    #[test]
+    fn displays_mnemonics() {
+        let mnemonics = [
+            (0x00E0, "CLS"),
+            (0x1234, "JP 0x234"),
+            (0x6A0F, "LD VA, 0x0F"),
+            (0x8126, "SHR V1, V2"),
+            (0xD125, "DRW V1, V2, 5"),
+            (0xF355, "LD [I], V3"),
+            (0x5132, "SAVE V1 - V3"),
+        ];
+        for (opcode, mnemonic) in mnemonics {
+            assert_eq!(Instruction::decode(opcode).unwrap().to_string(), mnemonic);
+        }
+    }
+}
@@ -1,6 +1,34 @@
+//! The CHIP-8, SUPER-CHIP and XO-CHIP interpreter shared by every frontend. It only needs
+//! `alloc`; everything else is behind default-on features:
+//!
+//! - `std`: trace files and the GDB stub,
+//! - `serde`: `Serialize` and `Deserialize` for save states,
+//! - `json`: saving and loading save states as JSON files,
+//! - `rand`: seeding the random source used by CXNN from the operating system. Without it, the
+//!   host seeds it.
+//!
+//! Frontends implement `VideoSink`, `AudioSink` and `InputSource` and let a `Runner` drive the
+//! machine frame by frame.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+pub mod audio;
 pub mod chip8;
+pub mod debugger;
 pub mod display;
+pub mod error;
+#[cfg(feature = "std")]
+pub mod gdb;
+pub mod input;
+pub mod instruction;
+pub mod platform;
+pub mod quirks;
+pub mod random;
+pub mod runner;
+pub mod trace;
 
+#[cfg(feature = "std")]
 pub fn hello_core() {
     println!("Hello from Chip8 core!");
 }
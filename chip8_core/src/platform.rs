@@ -0,0 +1,49 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::quirks::Quirks;
+
+/// The machine being emulated. Each platform is a superset of the previous one, so they can be
+/// compared to check whether an instruction is available.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Platform {
+    /// The original CHIP-8 with a 64x32 display.
+    #[default]
+    Chip8,
+    /// SUPER-CHIP 1.1: 128x64 high resolution mode, scrolling, 16x16 sprites, a big hex font
+    /// and RPL user flags.
+    SuperChip,
+    /// XO-CHIP: 64 KiB of memory, two bitplanes, an audio pattern buffer and a few extra
+    /// instructions on top of SUPER-CHIP.
+    XoChip,
+}
+
+impl Platform {
+    /// The quirks ROMs written for this platform usually expect.
+    pub fn default_quirks(&self) -> Quirks {
+        match self {
+            Platform::Chip8 => Quirks::default(),
+            Platform::SuperChip => Quirks::superchip(),
+            Platform::XoChip => Quirks::xochip(),
+        }
+    }
+
+    /// Amount of addressable memory in bytes.
+    pub fn memory_size(&self) -> usize {
+        match self {
+            Platform::Chip8 | Platform::SuperChip => 4096,
+            Platform::XoChip => 65536,
+        }
+    }
+
+    /// Looks up a platform by name. Accepts `chip8`, `schip` and `xochip`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "chip8" | "chip-8" => Some(Platform::Chip8),
+            "schip" | "superchip" | "super-chip" => Some(Platform::SuperChip),
+            "xochip" | "xo-chip" => Some(Platform::XoChip),
+            _ => None,
+        }
+    }
+}
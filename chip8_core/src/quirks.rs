@@ -0,0 +1,96 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Behaviours that differ between CHIP-8 interpreters. Each flag describes how a group of
+/// ambiguous opcodes is executed, so ROMs written for different machines can run side by side.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Quirks {
+    /// 8XY6/8XYE shift VX in place and ignore VY. When disabled, VY is shifted into VX
+    /// (COSMAC VIP behaviour).
+    pub shift: bool,
+    /// FX55/FX65 leave I untouched. When disabled, I is incremented by X + 1 after the
+    /// transfer (COSMAC VIP behaviour).
+    pub load_store: bool,
+    /// BNNN is executed as BXNN, jumping to VX + NNN instead of V0 + NNN.
+    pub jump: bool,
+    /// 8XY1/8XY2/8XY3 reset VF to 0 after the logical operation.
+    pub vf_reset: bool,
+    /// DXYN clips sprites at the edges of the screen instead of wrapping them around.
+    pub clipping: bool,
+    /// DXYN waits for the next timer tick (vertical blank) before the CPU continues.
+    pub display_wait: bool,
+}
+
+impl Quirks {
+    /// The original interpreter of the COSMAC VIP.
+    pub fn cosmac_vip() -> Self {
+        Self {
+            shift: false,
+            load_store: false,
+            jump: false,
+            vf_reset: true,
+            clipping: true,
+            display_wait: true,
+        }
+    }
+
+    /// The CHIP-48 interpreter for the HP-48 calculators.
+    pub fn chip48() -> Self {
+        Self {
+            shift: true,
+            load_store: true,
+            jump: true,
+            vf_reset: false,
+            clipping: true,
+            display_wait: false,
+        }
+    }
+
+    /// SUPER-CHIP 1.1, the successor of CHIP-48. It kept the behaviour of CHIP-48 for every
+    /// opcode covered by these flags, so both profiles are the same.
+    pub fn superchip() -> Self {
+        Self::chip48()
+    }
+
+    /// XO-CHIP, as implemented by Octo.
+    pub fn xochip() -> Self {
+        Self {
+            shift: false,
+            load_store: false,
+            jump: false,
+            vf_reset: false,
+            clipping: false,
+            display_wait: false,
+        }
+    }
+
+    /// Looks up a quirk profile by name. Accepts `default`, `vip`, `chip48`, `schip` and
+    /// `xochip`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "default" => Some(Self::default()),
+            "vip" | "cosmac-vip" | "cosmac_vip" => Some(Self::cosmac_vip()),
+            "chip48" | "chip-48" => Some(Self::chip48()),
+            "schip" | "superchip" | "super-chip" => Some(Self::superchip()),
+            "xochip" | "xo-chip" => Some(Self::xochip()),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Quirks {
+    /// The behaviour this emulator always had: shifts, loads/stores and logical operations
+    /// follow CHIP-48 (VF is left alone), jumps follow the COSMAC VIP, and sprites wrap around
+    /// the edges of the screen instead of being clipped.
+    fn default() -> Self {
+        Self {
+            shift: true,
+            load_store: true,
+            jump: false,
+            vf_reset: false,
+            clipping: false,
+            display_wait: false,
+        }
+    }
+}
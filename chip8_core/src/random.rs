@@ -0,0 +1,98 @@
+use alloc::boxed::Box;
+use core::fmt;
+
+/// Seed used by `Chip8` unless it's given another one, so runs are reproducible by default.
+pub const DEFAULT_SEED: u64 = 0;
+
+/// A source of random bytes for CXNN. Its whole state fits in a `u64`, so it can be stored in
+/// save states and restored later on.
+pub trait RandomSource: fmt::Debug + Send {
+    /// Returns a uniformly distributed byte, from 0 to 255.
+    fn next_byte(&mut self) -> u8;
+    fn state(&self) -> u64;
+    fn set_state(&mut self, state: u64);
+    fn clone_box(&self) -> Box<dyn RandomSource>;
+}
+
+impl Clone for Box<dyn RandomSource> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// The SplitMix64 generator: tiny, fast, and any state (including 0) is a valid one.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// A generator seeded from the operating system, for runs that shouldn't be reproducible.
+    /// Needs the `rand` feature; without it, hosts seed generators from their own source of
+    /// entropy with `new`.
+    #[cfg(feature = "rand")]
+    pub fn from_entropy() -> Self {
+        Self::new(rand::random())
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+impl Default for SplitMix64 {
+    fn default() -> Self {
+        Self::new(DEFAULT_SEED)
+    }
+}
+
+impl RandomSource for SplitMix64 {
+    fn next_byte(&mut self) -> u8 {
+        // The highest bits are the best mixed ones.
+        (self.next_u64() >> 56) as u8
+    }
+
+    fn state(&self) -> u64 {
+        self.state
+    }
+
+    fn set_state(&mut self, state: u64) {
+        self.state = state;
+    }
+
+    fn clone_box(&self) -> Box<dyn RandomSource> {
+        Box::new(*self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_gives_same_bytes() {
+        let mut first = SplitMix64::new(42);
+        let mut second = SplitMix64::new(42);
+        let first: Vec<u8> = (0..32).map(|_| first.next_byte()).collect();
+        let second: Vec<u8> = (0..32).map(|_| second.next_byte()).collect();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn produces_every_byte() {
+        let mut random = SplitMix64::default();
+        let mut seen = [false; 256];
+        for _ in 0..10_000 {
+            seen[random.next_byte() as usize] = true;
+        }
+        assert!(seen.iter().all(|seen| *seen));
+    }
+}
@@ -0,0 +1,289 @@
+use crate::audio::AudioSink;
+use crate::chip8::{Chip8, FrameReport, Step};
+use crate::debugger::{Debugger, StopReason};
+use crate::display::VideoSink;
+use crate::error::Chip8Error;
+use crate::input::InputSource;
+
+/// Why `Runner` stopped running frames.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Exit {
+    /// The input source asked to stop.
+    Quit,
+    /// The debugger stopped the program, e.g. at a breakpoint. The frame was finished, so
+    /// running resumes with the next one.
+    Debugger(StopReason),
+    /// The condition given to `run_frame_until` was met, before the frame was finished.
+    Stopped,
+}
+
+/// Drives a machine the same way for every frontend. Each frame, it polls the input, runs the
+/// frame with `Chip8::run_frame`, then hands the buzzer and the screen to the frontend.
+pub struct Runner {
+    pub chip8: Chip8,
+    /// Executes the instructions when set, so the program can be stopped at breakpoints.
+    pub debugger: Option<Debugger>,
+    // Once the ROM fails, nothing is executed and the machine keeps showing its last frame.
+    error: Option<Chip8Error>,
+    // Whether the video sink has yet to be told about the current error.
+    error_changed: bool,
+}
+
+impl Runner {
+    pub fn new(chip8: Chip8) -> Self {
+        Self {
+            chip8,
+            debugger: None,
+            error: None,
+            error_changed: false,
+        }
+    }
+
+    /// The error that stopped the ROM, if any.
+    pub fn error(&self) -> Option<&Chip8Error> {
+        self.error.as_ref()
+    }
+
+    /// Stops the ROM because of `error`, e.g. one raised while loading it.
+    pub fn stop(&mut self, error: Chip8Error) {
+        self.error = Some(error);
+        self.error_changed = true;
+    }
+
+    /// Runs `chip8` from now on, e.g. a loaded save state, even if the previous machine
+    /// stopped because of an error. Its whole screen is presented with the next frame.
+    pub fn load(&mut self, mut chip8: Chip8) {
+        chip8.invalidate_screen();
+        self.chip8 = chip8;
+        self.error_changed |= self.error.take().is_some();
+    }
+
+    /// Runs a frame. Returns why running should stop, if it should.
+    pub fn run_frame(
+        &mut self,
+        video: &mut impl VideoSink,
+        audio: &mut impl AudioSink,
+        input: &mut impl InputSource,
+    ) -> Option<Exit> {
+        self.run_frame_until(video, audio, input, |_| false)
+    }
+
+    /// Runs a frame like `run_frame`, but stops before the first instruction for which `stop`
+    /// returns true.
+    pub fn run_frame_until(
+        &mut self,
+        video: &mut impl VideoSink,
+        audio: &mut impl AudioSink,
+        input: &mut impl InputSource,
+        mut stop: impl FnMut(&Chip8) -> bool,
+    ) -> Option<Exit> {
+        if !input.poll(self) {
+            return Some(Exit::Quit);
+        }
+
+        let mut exit = None;
+        // A stopped machine is frozen, so its timers don't run either.
+        let mut report = FrameReport::default();
+        if self.error.is_none() {
+            let debugger = &mut self.debugger;
+            report = self.chip8.run_frame_with(|chip8| {
+                if stop(chip8) {
+                    exit = Some(Exit::Stopped);
+                    return Ok(Step::Abort);
+                }
+                let Some(debugger) = debugger else {
+                    return chip8.tick().map(|()| Step::Continue);
+                };
+                match debugger.tick(chip8) {
+                    None => Ok(Step::Continue),
+                    Some(StopReason::Error { error, .. }) => Err(error),
+                    Some(reason) => {
+                        exit = Some(Exit::Debugger(reason));
+                        Ok(Step::EndFrame)
+                    }
+                }
+            });
+        }
+        if exit == Some(Exit::Stopped) {
+            return exit;
+        }
+        if let Some(error) = report.error {
+            self.stop(error);
+        }
+
+        audio.set_buzzer(report.buzzer, self.chip8.get_audio_pattern());
+        if self.error_changed {
+            video.show_error(self.error.as_ref());
+            self.error_changed = false;
+        }
+        video.present(&self.chip8.get_framebuffer());
+        // The next frame only tells about what changes from now on.
+        self.chip8.clear_dirty();
+        exit
+    }
+
+    /// Runs frames at the frame rate of the machine until the input source or the debugger
+    /// stops it.
+    #[cfg(feature = "std")]
+    pub fn run(
+        &mut self,
+        video: &mut impl VideoSink,
+        audio: &mut impl AudioSink,
+        input: &mut impl InputSource,
+    ) -> Exit {
+        use std::time::{Duration, Instant};
+
+        let frame_interval = Duration::new(0, 1_000_000_000u32 / self.chip8.get_fps());
+        loop {
+            let frame_start = Instant::now();
+            if let Some(exit) = self.run_frame(video, audio, input) {
+                return exit;
+            }
+            // Modern hardware could run hundreds of frames per second, so the rest of the
+            // frame is slept away.
+            std::thread::sleep(frame_interval.saturating_sub(frame_start.elapsed()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::AudioPattern;
+    use crate::display::{DirtyRect, Framebuffer};
+    use alloc::vec::Vec;
+
+    // Records what the runner hands to the frontend.
+    #[derive(Default)]
+    struct Screen {
+        frames: Vec<(Vec<u8>, usize, usize)>,
+        dirty: Vec<Option<DirtyRect>>,
+        errors: Vec<Option<Chip8Error>>,
+    }
+
+    impl VideoSink for Screen {
+        fn present(&mut self, frame: &Framebuffer) {
+            self.frames
+                .push((frame.pixels.to_vec(), frame.width, frame.height));
+            self.dirty.push(frame.dirty);
+        }
+
+        fn show_error(&mut self, error: Option<&Chip8Error>) {
+            self.errors.push(error.copied());
+        }
+    }
+
+    #[derive(Default)]
+    struct Buzzer(Vec<bool>);
+
+    impl AudioSink for Buzzer {
+        fn set_buzzer(&mut self, on: bool, _pattern: Option<AudioPattern>) {
+            self.0.push(on);
+        }
+    }
+
+    // Presses keys once, and quits when asked to.
+    #[derive(Default)]
+    struct Keys {
+        pressed: Vec<u8>,
+        quit: bool,
+    }
+
+    impl InputSource for Keys {
+        fn poll(&mut self, runner: &mut Runner) -> bool {
+            for key in self.pressed.drain(..) {
+                runner.chip8.key_down(Some(key), true);
+            }
+            !self.quit
+        }
+    }
+
+    #[test]
+    fn runs_frames_and_freezes_on_errors() {
+        // LD V0, 3; LD ST, V0; LD V1, K; LD F, V1; DRW V2, V2, 5; an invalid opcode.
+        let rom = [
+            0x60, 0x03, 0xF0, 0x18, 0xF1, 0x0A, 0xF1, 0x29, 0xD2, 0x25, 0xFF, 0xFF,
+        ];
+        let mut chip8 = Chip8::new();
+        chip8.load_file(&rom).unwrap();
+        let mut runner = Runner::new(chip8);
+        let (mut screen, mut buzzer, mut keys) =
+            (Screen::default(), Buzzer::default(), Keys::default());
+        let mut run =
+            |runner: &mut Runner, keys: &mut Keys| runner.run_frame(&mut screen, &mut buzzer, keys);
+
+        assert_eq!(run(&mut runner, &mut keys), None);
+        keys.pressed.push(0x7);
+        assert_eq!(run(&mut runner, &mut keys), None);
+        let error = Chip8Error::InvalidOpcode {
+            pc: 0x20A,
+            opcode: 0xFFFF,
+        };
+        assert_eq!(runner.error(), Some(&error));
+
+        // The machine is frozen, timers included.
+        let sound_timer = runner.chip8.get_sound_timer();
+        assert_eq!(run(&mut runner, &mut keys), None);
+        assert_eq!(runner.chip8.get_sound_timer(), sound_timer);
+        keys.quit = true;
+        assert_eq!(run(&mut runner, &mut keys), Some(Exit::Quit));
+
+        let mut chip8 = Chip8::new();
+        chip8.load_file(&rom).unwrap();
+        runner.load(chip8);
+        keys.quit = false;
+        assert_eq!(run(&mut runner, &mut keys), None);
+
+        let Screen {
+            frames,
+            dirty,
+            errors,
+        } = screen;
+        assert_eq!(frames.len(), 4);
+        let (pixels, width, height) = &frames[0];
+        assert_eq!((pixels.len(), *width, *height), (64 * 32, 64, 32));
+        assert!(pixels.iter().all(|pixel| *pixel == 0));
+        assert!(frames[1].0.iter().any(|pixel| *pixel != 0));
+        // New machines are presented whole, then only the digit that was drawn, the 4x5 "7".
+        let whole = DirtyRect {
+            x: 0,
+            y: 0,
+            width: 64,
+            height: 32,
+        };
+        let digit = DirtyRect {
+            x: 0,
+            y: 0,
+            width: 4,
+            height: 5,
+        };
+        assert_eq!(dirty, [Some(whole), Some(digit), None, Some(whole)]);
+        assert_eq!(errors, [Some(error), None]);
+        assert_eq!(buzzer.0, [true, false, false, true]);
+    }
+
+    #[test]
+    fn stops_at_breakpoints_and_conditions() {
+        // LD V0, 1; LD V1, 2; JP 0x204.
+        let rom = [0x60, 0x01, 0x61, 0x02, 0x12, 0x04];
+        let mut chip8 = Chip8::new();
+        chip8.load_file(&rom).unwrap();
+        let mut runner = Runner::new(chip8);
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0x202);
+        runner.debugger = Some(debugger);
+
+        let exit = runner.run_frame(&mut (), &mut (), &mut ());
+        assert_eq!(
+            exit,
+            Some(Exit::Debugger(StopReason::Breakpoint { pc: 0x202 }))
+        );
+        assert_eq!(runner.chip8.get_registers()[..2], [1, 0]);
+
+        let exit = runner.run_frame_until(&mut (), &mut (), &mut (), |chip8| {
+            chip8.get_registers()[1] == 2
+        });
+        assert_eq!(exit, Some(Exit::Stopped));
+        assert_eq!(runner.chip8.get_pc(), 0x204);
+    }
+}
@@ -0,0 +1,414 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::num::ParseIntError;
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::fs::File;
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(feature = "std")]
+use std::io::{BufWriter, Write};
+#[cfg(feature = "std")]
+use std::path::Path;
+
+use crate::instruction::Instruction;
+
+const REGISTER_NAMES: [&str; 16] = [
+    "V0", "V1", "V2", "V3", "V4", "V5", "V6", "V7", "V8", "V9", "VA", "VB", "VC", "VD", "VE", "VF",
+];
+
+/// The registers of the CPU at a point in time.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Registers {
+    pub v: [u8; 16],
+    pub i: u16,
+    pub sp: u8,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
+
+/// A register whose value changed while executing an instruction.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RegisterDelta {
+    pub name: &'static str,
+    pub old: u16,
+    pub new: u16,
+}
+
+/// An executed instruction, with the registers before and after executing it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TraceEvent {
+    pub pc: u16,
+    pub opcode: u16,
+    pub instruction: Instruction,
+    pub before: Registers,
+    pub after: Registers,
+}
+
+impl TraceEvent {
+    /// The registers changed by the instruction.
+    pub fn deltas(&self) -> Vec<RegisterDelta> {
+        let (before, after) = (&self.before, &self.after);
+        (0..16)
+            .map(|x| (REGISTER_NAMES[x], before.v[x] as u16, after.v[x] as u16))
+            .chain([
+                ("I", before.i, after.i),
+                ("SP", before.sp as u16, after.sp as u16),
+                ("DT", before.delay_timer as u16, after.delay_timer as u16),
+                ("ST", before.sound_timer as u16, after.sound_timer as u16),
+            ])
+            .filter(|(_, old, new)| old != new)
+            .map(|(name, old, new)| RegisterDelta { name, old, new })
+            .collect()
+    }
+}
+
+impl fmt::Display for TraceEvent {
+    /// `0x0200: 6005 LD V0, 0x05 | V0: 0x00 -> 0x05`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "0x{:04X}: {:04X} {}",
+            self.pc, self.opcode, self.instruction
+        )?;
+        for (index, delta) in self.deltas().iter().enumerate() {
+            let separator = if index == 0 { " | " } else { ", " };
+            write!(
+                f,
+                "{}{}: 0x{:02X} -> 0x{:02X}",
+                separator, delta.name, delta.old, delta.new
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Observes every instruction executed by a `Chip8`. Tracing is disabled unless a tracer is
+/// attached with `Chip8::set_tracer`, in which case the registers aren't even captured.
+pub trait Tracer: Send {
+    fn trace(&mut self, event: &TraceEvent);
+}
+
+// Holds the tracer of a `Chip8`. Tracers write to files and loggers that can't be duplicated,
+// so clones of a `Chip8` start without one.
+#[derive(Default)]
+pub(crate) struct TracerSlot(pub(crate) Option<Box<dyn Tracer>>);
+
+impl Clone for TracerSlot {
+    fn clone(&self) -> Self {
+        Self(None)
+    }
+}
+
+impl fmt::Debug for TracerSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = if self.0.is_some() {
+            "attached"
+        } else {
+            "detached"
+        };
+        f.write_str(state)
+    }
+}
+
+/// Writes one line per instruction to any writer, such as a log file. Needs the `std` feature.
+#[cfg(feature = "std")]
+pub struct WriterTracer<W: Write + Send> {
+    writer: W,
+}
+
+#[cfg(feature = "std")]
+impl<W: Write + Send> WriterTracer<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(feature = "std")]
+impl WriterTracer<BufWriter<File>> {
+    /// Creates (or truncates) the log file at `path`.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: Write + Send> Tracer for WriterTracer<W> {
+    fn trace(&mut self, event: &TraceEvent) {
+        // A trace is a debugging aid, so failing to write it shouldn't stop the emulator.
+        let _ = writeln!(self.writer, "{}", event);
+    }
+}
+
+/// One line of an execution trace: the number of instructions executed before it, and the
+/// state of the CPU right before executing the instruction at `pc`. It's written as
+///
+/// `00000000 PC:0200 OP:6005 V0:00 V1:00 ... VF:00 I:0000 SP:00 DT:00 ST:00`
+///
+/// with every value in hexadecimal except the cycle, so the traces of other emulators can be
+/// produced in the same format and compared with `chip8-tracediff`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TraceRecord {
+    pub cycle: u64,
+    pub pc: u16,
+    pub opcode: u16,
+    pub registers: Registers,
+}
+
+impl TraceRecord {
+    /// The names of the fields whose values differ between both records. Cycles aren't
+    /// compared, as emulators count them from different starting points.
+    pub fn differences(&self, other: &TraceRecord) -> Vec<&'static str> {
+        let (ours, theirs) = (&self.registers, &other.registers);
+        let mut fields = Vec::new();
+        if self.pc != other.pc {
+            fields.push("PC");
+        }
+        if self.opcode != other.opcode {
+            fields.push("OP");
+        }
+        fields.extend(
+            (0..16)
+                .filter(|x| ours.v[*x] != theirs.v[*x])
+                .map(|x| REGISTER_NAMES[x]),
+        );
+        for (name, differs) in [
+            ("I", ours.i != theirs.i),
+            ("SP", ours.sp != theirs.sp),
+            ("DT", ours.delay_timer != theirs.delay_timer),
+            ("ST", ours.sound_timer != theirs.sound_timer),
+        ] {
+            if differs {
+                fields.push(name);
+            }
+        }
+        fields
+    }
+}
+
+impl fmt::Display for TraceRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let registers = &self.registers;
+        write!(
+            f,
+            "{:08} PC:{:04X} OP:{:04X}",
+            self.cycle, self.pc, self.opcode
+        )?;
+        for (name, value) in REGISTER_NAMES.iter().zip(registers.v) {
+            write!(f, " {}:{:02X}", name, value)?;
+        }
+        write!(
+            f,
+            " I:{:04X} SP:{:02X} DT:{:02X} ST:{:02X}",
+            registers.i, registers.sp, registers.delay_timer, registers.sound_timer
+        )
+    }
+}
+
+impl FromStr for TraceRecord {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut tokens = line.split_whitespace();
+        let cycle = tokens
+            .next()
+            .and_then(|cycle| cycle.parse().ok())
+            .ok_or_else(|| String::from("expected the cycle"))?;
+        // Values too large for their field are rejected rather than truncated.
+        let pc = parse_field(tokens.next(), "PC", u16::from_str_radix)?;
+        let opcode = parse_field(tokens.next(), "OP", u16::from_str_radix)?;
+        let mut v = [0; 16];
+        for (x, name) in REGISTER_NAMES.iter().enumerate() {
+            v[x] = parse_field(tokens.next(), name, u8::from_str_radix)?;
+        }
+        let registers = Registers {
+            v,
+            i: parse_field(tokens.next(), "I", u16::from_str_radix)?,
+            sp: parse_field(tokens.next(), "SP", u8::from_str_radix)?,
+            delay_timer: parse_field(tokens.next(), "DT", u8::from_str_radix)?,
+            sound_timer: parse_field(tokens.next(), "ST", u8::from_str_radix)?,
+        };
+        if let Some(token) = tokens.next() {
+            return Err(format!("unexpected {} after ST", token));
+        }
+        Ok(Self {
+            cycle,
+            pc,
+            opcode,
+            registers,
+        })
+    }
+}
+
+// Parses a `NAME:value` token of a trace line, with the value in hex.
+fn parse_field<T>(
+    token: Option<&str>,
+    name: &str,
+    from_str_radix: fn(&str, u32) -> Result<T, ParseIntError>,
+) -> Result<T, String> {
+    token
+        .and_then(|token| token.strip_prefix(name)?.strip_prefix(':'))
+        .and_then(|value| from_str_radix(value, 16).ok())
+        .ok_or_else(|| format!("expected {}:<hex value>", name))
+}
+
+/// Writes a `TraceRecord` line per executed instruction, counting cycles from 0. Needs the `std`
+/// feature.
+#[cfg(feature = "std")]
+pub struct TraceRecorder<W: Write + Send> {
+    writer: W,
+    cycle: u64,
+}
+
+#[cfg(feature = "std")]
+impl<W: Write + Send> TraceRecorder<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer, cycle: 0 }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(feature = "std")]
+impl TraceRecorder<BufWriter<File>> {
+    /// Creates (or truncates) the trace file at `path`.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: Write + Send> Tracer for TraceRecorder<W> {
+    fn trace(&mut self, event: &TraceEvent) {
+        let record = TraceRecord {
+            cycle: self.cycle,
+            pc: event.pc,
+            opcode: event.opcode,
+            registers: event.before,
+        };
+        let _ = writeln!(self.writer, "{}", record);
+        self.cycle += 1;
+    }
+}
+
+/// Sends every instruction to the `log` crate, at the trace level and with the `chip8` target.
+#[cfg(feature = "log")]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct LogTracer;
+
+#[cfg(feature = "log")]
+impl Tracer for LogTracer {
+    fn trace(&mut self, event: &TraceEvent) {
+        log::trace!(target: "chip8", "{}", event);
+    }
+}
+
+/// Emits a `tracing` event per instruction, at the trace level, with the PC, opcode and
+/// instruction as fields.
+#[cfg(feature = "tracing")]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TracingTracer;
+
+#[cfg(feature = "tracing")]
+impl Tracer for TracingTracer {
+    fn trace(&mut self, event: &TraceEvent) {
+        let deltas: Vec<String> = event
+            .deltas()
+            .iter()
+            .map(|delta| format!("{}: 0x{:02X} -> 0x{:02X}", delta.name, delta.old, delta.new))
+            .collect();
+        tracing::trace!(
+            target: "chip8",
+            pc = event.pc,
+            opcode = event.opcode,
+            instruction = %event.instruction,
+            deltas = %deltas.join(", "),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::Chip8;
+    use std::sync::{Arc, Mutex};
+
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl Tracer for Recorder {
+        fn trace(&mut self, event: &TraceEvent) {
+            self.0.lock().unwrap().push(event.to_string());
+        }
+    }
+
+    #[test]
+    fn traces_instructions_and_register_changes() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let mut chip8 = Chip8::new();
+        chip8
+            .load_file(&[0x60, 0x05, 0xA3, 0x00, 0x12, 0x04])
+            .unwrap();
+        chip8.set_tracer(Some(Box::new(Recorder(lines.clone()))));
+        for _ in 0..3 {
+            chip8.tick().unwrap();
+        }
+        assert_eq!(
+            *lines.lock().unwrap(),
+            [
+                "0x0200: 6005 LD V0, 0x05 | V0: 0x00 -> 0x05",
+                "0x0202: A300 LD I, 0x300 | I: 0x00 -> 0x300",
+                "0x0204: 1204 JP 0x204",
+            ]
+        );
+
+        // Clones don't share the tracer.
+        chip8.clone().tick().unwrap();
+        assert_eq!(lines.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn trace_records_round_trip() {
+        let mut registers = Registers::default();
+        registers.v[0xA] = 0x3C;
+        registers.i = 0x2F0;
+        registers.sp = 1;
+        let record = TraceRecord {
+            cycle: 42,
+            pc: 0x204,
+            opcode: 0xD125,
+            registers,
+        };
+        let line = record.to_string();
+        assert_eq!(
+            line,
+            "00000042 PC:0204 OP:D125 V0:00 V1:00 V2:00 V3:00 V4:00 V5:00 V6:00 V7:00 V8:00 \
+V9:00 VA:3C VB:00 VC:00 VD:00 VE:00 VF:00 I:02F0 SP:01 DT:00 ST:00"
+        );
+        assert_eq!(line.parse(), Ok(record));
+        assert!("00000042 PC:0204".parse::<TraceRecord>().is_err());
+
+        // Foreign traces with values too large for their register, or extra fields, are
+        // rejected instead of being truncated.
+        let too_large = line.replace("V3:00", "V3:1FF");
+        assert_eq!(
+            too_large.parse::<TraceRecord>(),
+            Err(String::from("expected V3:<hex value>"))
+        );
+        let too_large = line.replace("SP:01", "SP:100");
+        assert!(too_large.parse::<TraceRecord>().is_err());
+        let extra = format!("{} KEYS:0000", line);
+        assert_eq!(
+            extra.parse::<TraceRecord>(),
+            Err(String::from("unexpected KEYS:0000 after ST"))
+        );
+    }
+}
@@ -0,0 +1,272 @@
+//! Executes one instruction on random machine states with both `Chip8` and the reference
+//! interpreter in `reference`, under every quirk profile, and checks they end up in the same
+//! state. When an instruction fails, only the errors are compared, since the state of a
+//! crashed program is unspecified.
+
+mod reference;
+
+use chip8_core::chip8::Chip8;
+use chip8_core::platform::Platform;
+use chip8_core::quirks::Quirks;
+use chip8_core::random::{RandomSource, SplitMix64};
+use proptest::prelude::*;
+use reference::{Machine, SCREEN_SIZE};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+// Every instruction family, as a base opcode and the mask of its operands. Generating opcodes
+// from these makes sure the rarer families are tested as often as the common ones.
+const FAMILIES: &[(u16, u16)] = &[
+    (0x0000, 0x0000),
+    (0x0000, 0x0FFF),
+    (0x00C0, 0x000F),
+    (0x00D0, 0x000F),
+    (0x00E0, 0x0000),
+    (0x00EE, 0x0000),
+    (0x00FB, 0x0000),
+    (0x00FC, 0x0000),
+    (0x00FD, 0x0000),
+    (0x00FE, 0x0000),
+    (0x00FF, 0x0000),
+    (0x1000, 0x0FFF),
+    (0x2000, 0x0FFF),
+    (0x3000, 0x0FFF),
+    (0x4000, 0x0FFF),
+    (0x5000, 0x0FFF),
+    (0x5002, 0x0FF0),
+    (0x5003, 0x0FF0),
+    (0x6000, 0x0FFF),
+    (0x7000, 0x0FFF),
+    (0x8000, 0x0FFF),
+    (0x8004, 0x0FF3),
+    (0x800E, 0x0FF0),
+    (0x9000, 0x0FFF),
+    (0xA000, 0x0FFF),
+    (0xB000, 0x0FFF),
+    (0xC000, 0x0FFF),
+    (0xD000, 0x0FFF),
+    (0xE09E, 0x0F00),
+    (0xE0A1, 0x0F00),
+    (0xE000, 0x0FFF),
+    (0xF000, 0x0000),
+    (0xF001, 0x0F00),
+    (0xF002, 0x0F00),
+    (0xF007, 0x0F00),
+    (0xF00A, 0x0F00),
+    (0xF015, 0x0F00),
+    (0xF018, 0x0F00),
+    (0xF01E, 0x0F00),
+    (0xF029, 0x0F00),
+    (0xF030, 0x0F00),
+    (0xF033, 0x0F00),
+    (0xF03A, 0x0F00),
+    (0xF055, 0x0F00),
+    (0xF065, 0x0F00),
+    (0xF075, 0x0F00),
+    (0xF085, 0x0F00),
+    (0xF000, 0x0FFF),
+];
+
+fn opcode() -> impl Strategy<Value = u16> {
+    prop_oneof![
+        any::<u16>(),
+        (0..FAMILIES.len(), any::<u16>()).prop_map(|(family, operands)| {
+            let (base, mask) = FAMILIES[family];
+            base | (operands & mask)
+        }),
+    ]
+}
+
+fn bytes(seed: u64, length: usize) -> Vec<u8> {
+    let mut random = SplitMix64::new(seed);
+    (0..length).map(|_| random.next_byte()).collect()
+}
+
+prop_compose! {
+    // Mostly in range, so that most instructions execute, but sometimes anywhere.
+    fn address()(address in prop_oneof![4 => 0x200u16..0x1000, 1 => any::<u16>()]) -> u16 {
+        address
+    }
+}
+
+prop_compose! {
+    fn machine(platform: Platform, quirks: Quirks)(
+        memory_seed in any::<u64>(),
+        screen_seed in any::<u64>(),
+        opcode in opcode(),
+        pc in address(),
+        i in address(),
+        v in any::<[u8; 16]>(),
+        sp in 0u8..=16,
+        stack in any::<[u16; 16]>(),
+        delay_timer in any::<u8>(),
+        sound_timer in any::<u8>(),
+        keys in any::<[bool; 16]>(),
+        hires in any::<bool>(),
+        planes in 0u8..=3,
+        flags in any::<[u8; 16]>(),
+        halted in prop::bool::weighted(0.05),
+        audio_pattern in any::<Option<[u8; 16]>>(),
+        pitch in any::<u8>(),
+        rng_state in any::<u64>(),
+    ) -> Machine {
+        let mut memory = bytes(memory_seed, platform.memory_size());
+        if let Some(word) = memory.get_mut(pc as usize..pc as usize + 2) {
+            word.copy_from_slice(&opcode.to_be_bytes());
+        }
+        // Only XO-CHIP has a second plane to draw pixels on.
+        let pixel_mask = if platform == Platform::XoChip { 0b11 } else { 0b01 };
+        let screen = bytes(screen_seed, SCREEN_SIZE).iter().map(|pixel| pixel & pixel_mask).collect();
+        Machine {
+            platform,
+            quirks,
+            memory,
+            v,
+            i,
+            pc,
+            sp,
+            stack,
+            delay_timer,
+            sound_timer,
+            keys,
+            screen,
+            hires: hires && platform >= Platform::SuperChip,
+            planes: if platform == Platform::XoChip { planes } else { 1 },
+            flags,
+            halted,
+            waiting_for_vblank: false,
+            audio_pattern,
+            pitch,
+            rng: SplitMix64::new(rng_state),
+        }
+    }
+}
+
+// Save states hold the whole machine, so they are the way to put a `Chip8` in any state.
+fn to_chip8(machine: &Machine) -> Chip8 {
+    let state = json!({
+        "memory": machine.memory,
+        "stack": machine.stack,
+        "v": machine.v,
+        "keys": machine.keys,
+        "screen": machine.screen,
+        "i": machine.i,
+        "pc": machine.pc,
+        "sp": machine.sp,
+        "fps": 60,
+        "sound_timer": machine.sound_timer,
+        "delay_timer": machine.delay_timer,
+        "quirks": machine.quirks,
+        "platform": machine.platform,
+        "hires": machine.hires,
+        "halted": machine.halted,
+        "flags": machine.flags,
+        "planes": machine.planes,
+        "audio_pattern": machine.audio_pattern,
+        "pitch": machine.pitch,
+        "rng_state": machine.rng.state(),
+    });
+    serde_json::from_value(state).expect("Couldn't load the machine as a save state.")
+}
+
+fn from_chip8(chip8: &Chip8) -> Machine {
+    let state = serde_json::to_value(chip8).unwrap();
+    fn field<T: DeserializeOwned>(state: &Value, name: &str) -> T {
+        serde_json::from_value(state[name].clone()).unwrap()
+    }
+    Machine {
+        platform: field(&state, "platform"),
+        quirks: field(&state, "quirks"),
+        memory: field(&state, "memory"),
+        v: field(&state, "v"),
+        i: field(&state, "i"),
+        pc: field(&state, "pc"),
+        sp: field(&state, "sp"),
+        stack: field(&state, "stack"),
+        delay_timer: field(&state, "delay_timer"),
+        sound_timer: field(&state, "sound_timer"),
+        keys: field(&state, "keys"),
+        screen: field(&state, "screen"),
+        hires: field(&state, "hires"),
+        planes: field(&state, "planes"),
+        flags: field(&state, "flags"),
+        halted: field(&state, "halted"),
+        waiting_for_vblank: chip8.is_waiting_for_vblank(),
+        audio_pattern: field(&state, "audio_pattern"),
+        pitch: field(&state, "pitch"),
+        rng: SplitMix64::new(field(&state, "rng_state")),
+    }
+}
+
+fn first_difference(left: &[u8], right: &[u8]) -> Option<usize> {
+    left.iter()
+        .zip(right)
+        .position(|(left, right)| left != right)
+}
+
+fn check(mut expected: Machine) -> Result<(), TestCaseError> {
+    let mut chip8 = to_chip8(&expected);
+    prop_assert_eq!(
+        from_chip8(&chip8),
+        expected.clone(),
+        "the state didn't load as is"
+    );
+
+    let result = chip8.tick();
+    let expected_result = expected.step();
+    prop_assert_eq!(result, expected_result);
+    if result.is_err() {
+        return Ok(());
+    }
+
+    let actual = from_chip8(&chip8);
+    if let Some(address) = first_difference(&actual.memory, &expected.memory) {
+        prop_assert!(
+            false,
+            "memory differs at {:#06X}: {:#04X} instead of {:#04X}",
+            address,
+            actual.memory[address],
+            expected.memory[address]
+        );
+    }
+    if let Some(pixel) = first_difference(&actual.screen, &expected.screen) {
+        prop_assert!(
+            false,
+            "pixel {} differs: {} instead of {}",
+            pixel,
+            actual.screen[pixel],
+            expected.screen[pixel]
+        );
+    }
+    prop_assert_eq!(actual, expected);
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(512))]
+
+    #[test]
+    fn matches_reference_with_default_quirks(state in machine(Platform::Chip8, Quirks::default())) {
+        check(state)?;
+    }
+
+    #[test]
+    fn matches_reference_with_cosmac_vip_quirks(state in machine(Platform::Chip8, Quirks::cosmac_vip())) {
+        check(state)?;
+    }
+
+    #[test]
+    fn matches_reference_with_chip48_quirks(state in machine(Platform::Chip8, Quirks::chip48())) {
+        check(state)?;
+    }
+
+    #[test]
+    fn matches_reference_with_superchip_quirks(state in machine(Platform::SuperChip, Quirks::superchip())) {
+        check(state)?;
+    }
+
+    #[test]
+    fn matches_reference_with_xochip_quirks(state in machine(Platform::XoChip, Quirks::xochip())) {
+        check(state)?;
+    }
+}
@@ -0,0 +1,386 @@
+//! A reference interpreter, written straight from the CHIP-8, SUPER-CHIP and XO-CHIP specs
+//! with no regard for speed. It decodes opcodes by hand and keeps the whole machine in plain
+//! public fields, so differential tests can set up any state and compare it with `Chip8`.
+
+use chip8_core::error::Chip8Error;
+use chip8_core::platform::Platform;
+use chip8_core::quirks::Quirks;
+use chip8_core::random::{RandomSource, SplitMix64};
+use std::fmt;
+
+pub const SCREEN_SIZE: usize = 128 * 64;
+const BIG_FONT_ADDRESS: u16 = 80;
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct Machine {
+    pub platform: Platform,
+    pub quirks: Quirks,
+    pub memory: Vec<u8>,
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    pub sp: u8,
+    pub stack: [u16; 16],
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub keys: [bool; 16],
+    /// Always 128x64 pixels. In low resolution only the first 64x32 are used, 64 per row.
+    pub screen: Vec<u8>,
+    pub hires: bool,
+    pub planes: u8,
+    pub flags: [u8; 16],
+    pub halted: bool,
+    pub waiting_for_vblank: bool,
+    pub audio_pattern: Option<[u8; 16]>,
+    pub pitch: u8,
+    pub rng: SplitMix64,
+}
+
+// Memory and the screen are far too large to be useful in a failure message.
+impl fmt::Debug for Machine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let opcode = self.word(self.pc).ok();
+        f.debug_struct("Machine")
+            .field("platform", &self.platform)
+            .field("quirks", &self.quirks)
+            .field("opcode", &opcode.map(|opcode| format!("{:04X}", opcode)))
+            .field("v", &self.v)
+            .field("i", &self.i)
+            .field("pc", &self.pc)
+            .field("sp", &self.sp)
+            .field("stack", &self.stack)
+            .field("timers", &(self.delay_timer, self.sound_timer))
+            .field("keys", &self.keys)
+            .field("hires", &self.hires)
+            .field("planes", &self.planes)
+            .field("flags", &self.flags)
+            .field("halted", &self.halted)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Machine {
+    fn width(&self) -> usize {
+        if self.hires {
+            128
+        } else {
+            64
+        }
+    }
+
+    fn height(&self) -> usize {
+        if self.hires {
+            64
+        } else {
+            32
+        }
+    }
+
+    fn read(&self, address: usize) -> Result<u8, Chip8Error> {
+        match self.memory.get(address) {
+            Some(byte) => Ok(*byte),
+            None => Err(Chip8Error::MemoryOutOfBounds { address }),
+        }
+    }
+
+    fn write(&mut self, address: usize, value: u8) -> Result<(), Chip8Error> {
+        match self.memory.get_mut(address) {
+            Some(byte) => {
+                *byte = value;
+                Ok(())
+            }
+            None => Err(Chip8Error::MemoryOutOfBounds { address }),
+        }
+    }
+
+    fn word(&self, address: u16) -> Result<u16, Chip8Error> {
+        let high = self.read(address as usize)?;
+        let low = self.read(address as usize + 1)?;
+        Ok(u16::from_be_bytes([high, low]))
+    }
+
+    fn skip(&mut self) {
+        // XO-CHIP skips both words of F000 NNNN.
+        if self.platform == Platform::XoChip && self.word(self.pc) == Ok(0xF000) {
+            self.pc = self.pc.wrapping_add(4);
+        } else {
+            self.pc = self.pc.wrapping_add(2);
+        }
+    }
+
+    /// Executes one instruction. After an error the state is unspecified.
+    pub fn step(&mut self) -> Result<(), Chip8Error> {
+        if self.halted || self.waiting_for_vblank {
+            return Ok(());
+        }
+
+        let pc = self.pc;
+        let opcode = self.word(pc)?;
+        self.pc = pc.wrapping_add(2);
+        let invalid = Err(Chip8Error::InvalidOpcode { pc, opcode });
+
+        let x = ((opcode >> 8) & 0xF) as usize;
+        let y = ((opcode >> 4) & 0xF) as usize;
+        let n = (opcode & 0xF) as u8;
+        let nn = (opcode & 0xFF) as u8;
+        let nnn = opcode & 0xFFF;
+        let schip = self.platform >= Platform::SuperChip;
+        let xochip = self.platform == Platform::XoChip;
+
+        match opcode >> 12 {
+            0x0 => match opcode {
+                0x0000 => {}
+                0x00E0 => {
+                    for pixel in self.screen.iter_mut() {
+                        *pixel &= !self.planes;
+                    }
+                }
+                0x00EE => {
+                    if self.sp == 0 {
+                        return Err(Chip8Error::StackUnderflow);
+                    }
+                    self.sp -= 1;
+                    self.pc = self.stack[self.sp as usize];
+                }
+                0x00C0..=0x00CF if schip => self.scroll(0, n as isize),
+                0x00D0..=0x00DF if xochip => self.scroll(0, -(n as isize)),
+                0x00FB if schip => self.scroll(4, 0),
+                0x00FC if schip => self.scroll(-4, 0),
+                0x00FD if schip => self.halted = true,
+                0x00FE if schip => {
+                    self.hires = false;
+                    self.screen = vec![0; SCREEN_SIZE];
+                }
+                0x00FF if schip => {
+                    self.hires = true;
+                    self.screen = vec![0; SCREEN_SIZE];
+                }
+                _ => return invalid,
+            },
+            0x1 => self.pc = nnn,
+            0x2 => {
+                if self.sp as usize == self.stack.len() {
+                    return Err(Chip8Error::StackOverflow);
+                }
+                self.stack[self.sp as usize] = self.pc;
+                self.sp += 1;
+                self.pc = nnn;
+            }
+            0x3 => {
+                if self.v[x] == nn {
+                    self.skip();
+                }
+            }
+            0x4 => {
+                if self.v[x] != nn {
+                    self.skip();
+                }
+            }
+            0x5 => match n {
+                0 => {
+                    if self.v[x] == self.v[y] {
+                        self.skip();
+                    }
+                }
+                2 if xochip => {
+                    for (offset, register) in range(x, y).into_iter().enumerate() {
+                        self.write(self.i as usize + offset, self.v[register])?;
+                    }
+                }
+                3 if xochip => {
+                    for (offset, register) in range(x, y).into_iter().enumerate() {
+                        self.v[register] = self.read(self.i as usize + offset)?;
+                    }
+                }
+                _ => return invalid,
+            },
+            0x6 => self.v[x] = nn,
+            0x7 => self.v[x] = self.v[x].wrapping_add(nn),
+            0x8 => {
+                let (vx, vy) = (self.v[x], self.v[y]);
+                // The flag is written last, so it wins when X is F.
+                let (result, flag) = match n {
+                    0x0 => (vy, None),
+                    0x1 => (vx | vy, self.quirks.vf_reset.then_some(0)),
+                    0x2 => (vx & vy, self.quirks.vf_reset.then_some(0)),
+                    0x3 => (vx ^ vy, self.quirks.vf_reset.then_some(0)),
+                    0x4 => {
+                        let sum = vx as u16 + vy as u16;
+                        (sum as u8, Some((sum > 0xFF) as u8))
+                    }
+                    0x5 => (vx.wrapping_sub(vy), Some((vx >= vy) as u8)),
+                    0x7 => (vy.wrapping_sub(vx), Some((vy >= vx) as u8)),
+                    0x6 => {
+                        let source = if self.quirks.shift { vx } else { vy };
+                        (source >> 1, Some(source & 1))
+                    }
+                    0xE => {
+                        let source = if self.quirks.shift { vx } else { vy };
+                        (source << 1, Some(source >> 7))
+                    }
+                    _ => return invalid,
+                };
+                self.v[x] = result;
+                if let Some(flag) = flag {
+                    self.v[0xF] = flag;
+                }
+            }
+            0x9 => {
+                if n != 0 {
+                    return invalid;
+                }
+                if self.v[x] != self.v[y] {
+                    self.skip();
+                }
+            }
+            0xA => self.i = nnn,
+            0xB => {
+                let register = if self.quirks.jump { x } else { 0 };
+                self.pc = self.v[register] as u16 + nnn;
+            }
+            0xC => self.v[x] = self.rng.next_byte() & nn,
+            0xD => self.draw(x, y, n)?,
+            0xE => {
+                let pressed = self.keys[(self.v[x] & 0xF) as usize];
+                match nn {
+                    0x9E if pressed => self.skip(),
+                    0xA1 if !pressed => self.skip(),
+                    0x9E | 0xA1 => {}
+                    _ => return invalid,
+                }
+            }
+            _ => match nn {
+                0x00 if xochip && x == 0 => {
+                    self.i = self.word(self.pc)?;
+                    self.pc = self.pc.wrapping_add(2);
+                }
+                0x01 if xochip => self.planes = x as u8 & 0b11,
+                0x02 if xochip && x == 0 => {
+                    let mut pattern = [0; 16];
+                    for (offset, sample) in pattern.iter_mut().enumerate() {
+                        *sample = self.read(self.i as usize + offset)?;
+                    }
+                    self.audio_pattern = Some(pattern);
+                }
+                0x07 => self.v[x] = self.delay_timer,
+                0x0A => match self.keys.iter().position(|pressed| *pressed) {
+                    Some(key) => self.v[x] = key as u8,
+                    None => self.pc = pc,
+                },
+                0x15 => self.delay_timer = self.v[x],
+                0x18 => self.sound_timer = self.v[x],
+                0x1E => {
+                    let sum = self.i as u32 + self.v[x] as u32;
+                    self.i = sum as u16;
+                    if sum > 0xFFFF {
+                        self.v[0xF] = 1;
+                    }
+                }
+                0x29 => self.i = (self.v[x] & 0xF) as u16 * 5,
+                0x30 if schip => self.i = BIG_FONT_ADDRESS + (self.v[x] & 0xF) as u16 * 10,
+                0x33 => {
+                    let value = self.v[x];
+                    self.write(self.i as usize, value / 100)?;
+                    self.write(self.i as usize + 1, value / 10 % 10)?;
+                    self.write(self.i as usize + 2, value % 10)?;
+                }
+                0x3A if xochip => self.pitch = self.v[x],
+                0x55 => {
+                    for register in 0..=x {
+                        self.write(self.i as usize + register, self.v[register])?;
+                    }
+                    if !self.quirks.load_store {
+                        self.i = self.i.wrapping_add(x as u16 + 1);
+                    }
+                }
+                0x65 => {
+                    for register in 0..=x {
+                        self.v[register] = self.read(self.i as usize + register)?;
+                    }
+                    if !self.quirks.load_store {
+                        self.i = self.i.wrapping_add(x as u16 + 1);
+                    }
+                }
+                0x75 if schip => self.flags[..=x].copy_from_slice(&self.v[..=x]),
+                0x85 if schip => self.v[..=x].copy_from_slice(&self.flags[..=x]),
+                _ => return invalid,
+            },
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, x: usize, y: usize, n: u8) -> Result<(), Chip8Error> {
+        // SUPER-CHIP draws 16x16 sprites for DXY0, with two bytes per row.
+        let (columns, rows) = if n == 0 && self.platform >= Platform::SuperChip {
+            (16, 16)
+        } else {
+            (8, n as usize)
+        };
+        let bytes_per_plane = rows * columns / 8;
+        let (width, height) = (self.width(), self.height());
+        let left = self.v[x] as usize % width;
+        let top = self.v[y] as usize % height;
+
+        let mut address = self.i as usize;
+        let mut collision = false;
+        for plane in [1, 2] {
+            if self.planes & plane == 0 {
+                continue;
+            }
+            let mut sprite = Vec::new();
+            for offset in 0..bytes_per_plane {
+                sprite.push(self.read(address + offset)?);
+            }
+            address += bytes_per_plane;
+
+            for row in 0..rows {
+                for column in 0..columns {
+                    let byte = sprite[row * columns / 8 + column / 8];
+                    if byte & (0x80 >> (column % 8)) == 0 {
+                        continue;
+                    }
+                    let (px, py) = (left + column, top + row);
+                    if self.quirks.clipping && (px >= width || py >= height) {
+                        continue;
+                    }
+                    let index = (py % height) * width + px % width;
+                    collision |= self.screen[index] & plane != 0;
+                    self.screen[index] ^= plane;
+                }
+            }
+        }
+        self.v[0xF] = collision as u8;
+        if self.quirks.display_wait {
+            self.waiting_for_vblank = true;
+        }
+        Ok(())
+    }
+
+    // Moves the selected planes by (dx, dy) pixels of the current resolution, filling the
+    // uncovered area with blank pixels.
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = (self.width() as isize, self.height() as isize);
+        let before = self.screen.clone();
+        for y in 0..height {
+            for x in 0..width {
+                let (from_x, from_y) = (x - dx, y - dy);
+                let moved = if (0..width).contains(&from_x) && (0..height).contains(&from_y) {
+                    before[(from_y * width + from_x) as usize] & self.planes
+                } else {
+                    0
+                };
+                let index = (y * width + x) as usize;
+                self.screen[index] = (self.screen[index] & !self.planes) | moved;
+            }
+        }
+    }
+}
+
+// The registers of 5XY2 and 5XY3 in the order they are stored, which is reversed when X > Y.
+fn range(x: usize, y: usize) -> Vec<usize> {
+    if x <= y {
+        (x..=y).collect()
+    } else {
+        (y..=x).rev().collect()
+    }
+}
@@ -1,3 +1,4 @@
+use chip8_core::audio::{AudioPattern, AudioSink};
 use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
 use sdl2::AudioSubsystem;
 
@@ -6,12 +7,26 @@ struct SquareWave {
     phase_inc: f32,
     phase: f32,
     volume: f32,
+    // XO-CHIP audio pattern. When present, it's played instead of the square wave.
+    pattern: Option<AudioPattern>,
+    // Position inside the pattern, measured in samples of the pattern (bits).
+    pattern_position: f32,
+    freq: i32,
 }
 
 impl AudioCallback for SquareWave {
     type Channel = f32;
 
     fn callback(&mut self, out: &mut [Self::Channel]) {
+        if let Some(pattern) = self.pattern {
+            let pattern_inc = pattern.playback_rate() / self.freq as f32;
+            for x in out.iter_mut() {
+                *x = if pattern.sample(self.pattern_position as usize) { 1.0 } else { -1.0 };
+                self.pattern_position = (self.pattern_position + pattern_inc) % 128.0;
+            }
+            return;
+        }
+
         for x in out.iter_mut() {
             *x = if self.phase <= 0.5 { 1.0 } else { -1.0 };
             self.phase = (self.phase + self.phase_inc) % 1.0;
@@ -36,6 +51,9 @@ impl AudioDeviceWrapper {
                 phase_inc: 240.0 / spec.freq as f32,
                 phase: 0.0,
                 volume: 0.25,
+                pattern: None,
+                pattern_position: 0.0,
+                freq: spec.freq,
             })
             .expect("Couldn't open the sound playback!");
 
@@ -49,4 +67,16 @@ impl AudioDeviceWrapper {
             self.device.pause();
         }
     }
+
+    /// Updates the XO-CHIP audio pattern played by the buzzer. None plays the default tone.
+    pub fn set_pattern(&mut self, pattern: Option<AudioPattern>) {
+        self.device.lock().pattern = pattern;
+    }
+}
+
+impl AudioSink for AudioDeviceWrapper {
+    fn set_buzzer(&mut self, on: bool, pattern: Option<AudioPattern>) {
+        self.set_pattern(pattern);
+        self.beep(on);
+    }
 }
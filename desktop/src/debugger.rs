@@ -0,0 +1,125 @@
+use chip8_core::chip8::Chip8;
+use chip8_core::debugger::{dump_state, Access, Debugger, Register, StopReason};
+use std::io::{BufRead, Write};
+
+const HELP: &str = "\
+c                 continue
+s                 step into
+n                 step over calls
+o                 step out of the current subroutine
+u ADDR            run until PC reaches ADDR
+b ADDR / d ADDR   add / delete a breakpoint
+rw ADDR / ww ADDR watch reads / writes of ADDR (repeat to remove)
+w REG             watch changes of V0-VF or I (repeat to remove)
+r                 show the registers, stack and timers
+x ADDR [LEN]      dump LEN bytes of memory starting at ADDR
+q                 quit the emulator";
+
+/// Reads debugger commands from the console until the program is resumed. Returns false if
+/// the user asked to quit.
+pub fn repl(debugger: &mut Debugger, chip8: &Chip8, reason: StopReason) -> bool {
+    println!("{}", reason);
+    print!("{}", dump_state(chip8));
+    let stdin = std::io::stdin();
+    loop {
+        print!("(chip8) ");
+        std::io::stdout().flush().expect("Couldn't write to the console.");
+        let mut line = String::new();
+        // The end of the input resumes the program, as there are no more commands to read.
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            debugger.resume();
+            return true;
+        }
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] => continue,
+            ["c"] => debugger.resume(),
+            ["s"] => debugger.step_into(),
+            ["n"] => debugger.step_over(chip8),
+            ["o"] => debugger.step_out(chip8),
+            ["q"] => return false,
+            ["r"] => {
+                print!("{}", dump_state(chip8));
+                continue;
+            }
+            ["u", address] => match parse_address(address) {
+                Some(address) => debugger.run_to(address),
+                None => {
+                    println!("Invalid address: {}", address);
+                    continue;
+                }
+            },
+            [command @ ("b" | "d" | "rw" | "ww"), address] => {
+                match parse_address(address) {
+                    Some(address) => toggle(debugger, command, address),
+                    None => println!("Invalid address: {}", address),
+                }
+                continue;
+            }
+            ["w", name] => {
+                match Register::from_name(name) {
+                    Some(register) => {
+                        if !debugger.unwatch_register(register) {
+                            debugger.watch_register(register);
+                        }
+                    }
+                    None => println!("Invalid register: {}", name),
+                }
+                continue;
+            }
+            ["x", address, rest @ ..] => {
+                let length = rest.first().and_then(|length| length.parse().ok()).unwrap_or(16);
+                match parse_address(address) {
+                    Some(address) => dump_memory(chip8, address as usize, length),
+                    None => println!("Invalid address: {}", address),
+                }
+                continue;
+            }
+            _ => {
+                println!("{}", HELP);
+                continue;
+            }
+        }
+        return true;
+    }
+}
+
+fn toggle(debugger: &mut Debugger, command: &str, address: u16) {
+    match command {
+        "b" => debugger.add_breakpoint(address),
+        "d" => {
+            if !debugger.remove_breakpoint(address) {
+                println!("There is no breakpoint at 0x{:03X}", address);
+            }
+        }
+        _ => {
+            let access = if command == "rw" {
+                Access::Read
+            } else {
+                Access::Write
+            };
+            if !debugger.remove_watchpoint(access, address) {
+                debugger.add_watchpoint(access, address);
+            }
+        }
+    }
+}
+
+fn dump_memory(chip8: &Chip8, address: usize, length: usize) {
+    let memory = chip8.get_memory();
+    let end = (address + length).min(memory.len());
+    for start in (address.min(end)..end).step_by(8) {
+        let bytes: Vec<String> = memory[start..(start + 8).min(end)]
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect();
+        println!("0x{:04X}: {}", start, bytes.join(" "));
+    }
+}
+
+// Addresses are hexadecimal, with or without the 0x prefix.
+fn parse_address(text: &str) -> Option<u16> {
+    let digits = text.strip_prefix("0x").unwrap_or(text);
+    u16::from_str_radix(digits, 16).ok()
+}
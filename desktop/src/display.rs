@@ -1,31 +1,88 @@
-use chip8_core::chip8::{Chip8, SCREEN_WIDTH};
+use chip8_core::display::{Framebuffer, Palette};
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
-use sdl2::render::WindowCanvas;
+use sdl2::render::{Texture, WindowCanvas};
 
 pub struct Scale {
     pub width: i32,
     pub height: i32,
 }
 
-pub fn draw_to_screen(canvas: &mut WindowCanvas, emu: &Chip8, scale: &Scale) {
-    canvas.set_draw_color(Color::RGB(0, 0, 0));
-    canvas.clear();
+impl Scale {
+    /// Computes the size of a CHIP-8 pixel so the active resolution fills the whole window.
+    pub fn fit(window_size: (u32, u32), resolution: (usize, usize)) -> Self {
+        let (window_width, window_height) = window_size;
+        let (width, height) = resolution;
+        Self {
+            width: (window_width as i32 / width as i32).max(1),
+            height: (window_height as i32 / height as i32).max(1),
+        }
+    }
+}
 
-    let screen_buffer = emu.get_display();
-    canvas.set_draw_color(Color::RGB(255, 255, 255));
-    for (i, pixel) in screen_buffer.iter().enumerate() {
-        if *pixel {
-            let x = (i % SCREEN_WIDTH) as u32;
-            let y = (i / SCREEN_WIDTH) as u32;
-            let rect = Rect::new(
-                (x * (scale.width as u32)) as i32,
-                (y * (scale.height as u32)) as i32,
-                scale.width as u32,
-                scale.height as u32,
-            );
-            canvas.fill_rect(rect).unwrap();
+/// Copies the dirty rows of `frame` into `texture`, as RGB888 pixels. The texture has the size
+/// of the high resolution screen, so every resolution fits in its top left corner.
+pub fn upload(texture: &mut Texture, frame: &Framebuffer, palette: &Palette) {
+    let Some(dirty) = frame.dirty else {
+        return;
+    };
+    // Locked texture memory doesn't hold the previous pixels, so only whole runs of dirty rows
+    // are locked and written.
+    let end = dirty.y + dirty.height;
+    let mut y = dirty.y;
+    while y < end {
+        if !frame.is_row_dirty(y) {
+            y += 1;
+            continue;
+        }
+        let top = y;
+        while y < end && frame.is_row_dirty(y) {
+            y += 1;
         }
+        let rect = Rect::new(
+            dirty.x as i32,
+            top as i32,
+            dirty.width as u32,
+            (y - top) as u32,
+        );
+        texture
+            .with_lock(rect, |buffer, pitch| {
+                for (row, line) in (top..y).zip(buffer.chunks_mut(pitch)) {
+                    let pixels = &frame.row(row)[dirty.x..dirty.x + dirty.width];
+                    for (pixel, texel) in pixels.iter().zip(line.chunks_exact_mut(4)) {
+                        // Each pixel holds one bit per bitplane, which is also its index in
+                        // the palette.
+                        let (r, g, b) = palette.colors[*pixel as usize];
+                        texel.copy_from_slice(&u32::from_be_bytes([0, r, g, b]).to_ne_bytes());
+                    }
+                }
+            })
+            .expect("Couldn't update the screen texture.");
     }
+}
+
+/// Draws the `width` x `height` top left corner of `texture`, scaled by `scale`.
+pub fn draw_to_screen(
+    canvas: &mut WindowCanvas,
+    texture: &Texture,
+    (width, height): (usize, usize),
+    scale: &Scale,
+    palette: &Palette,
+) {
+    // The part of the window the scaled screen doesn't cover shows the background colour.
+    let (r, g, b) = palette.colors[0];
+    canvas.set_draw_color(Color::RGB(r, g, b));
+    canvas.clear();
+
+    let source = Rect::new(0, 0, width as u32, height as u32);
+    let target = Rect::new(
+        0,
+        0,
+        width as u32 * scale.width as u32,
+        height as u32 * scale.height as u32,
+    );
+    canvas
+        .copy(texture, source, target)
+        .expect("Couldn't draw the screen texture.");
     canvas.present();
 }
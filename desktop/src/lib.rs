@@ -1,22 +1,104 @@
 pub mod audio;
+pub mod debugger;
 pub mod display;
 
 use crate::audio::AudioDeviceWrapper;
-use chip8_core::chip8::Chip8;
-use sdl2::event::{Event, WindowEvent};
+use assembler::{octo, AssemblyError};
+use chip8_core::chip8::{Chip8, HIRES_SCREEN_HEIGHT, HIRES_SCREEN_WIDTH};
+use chip8_core::debugger::Debugger;
+use chip8_core::display::{Framebuffer, Palette, VideoSink};
+use chip8_core::error::Chip8Error;
+use chip8_core::gdb::GdbStub;
+use chip8_core::input::InputSource;
+use chip8_core::platform::Platform;
+use chip8_core::quirks::Quirks;
+use chip8_core::random::SplitMix64;
+use chip8_core::runner::{Exit, Runner};
+use chip8_core::trace::TraceRecorder;
+use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
-use std::io::Read;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::{Texture, WindowCanvas};
+use sdl2::EventPump;
+use std::fmt;
 use std::time::UNIX_EPOCH;
-use std::time::{Duration, Instant};
-
-pub fn run(path_to_rom: &str) {
-    const NUMBER_OF_CYCLES: u8 = 8;
-    let mut chip8 = Chip8::new();
-    let mut scale = display::Scale {
-        width: 15,
-        height: 18,
+
+const WINDOW_TITLE: &str = "rust-sdl2 demo";
+
+/// Why a ROM couldn't be loaded.
+#[derive(Debug)]
+pub enum LoadError {
+    /// The ROM file couldn't be read.
+    Io(std::io::Error),
+    /// The Octo source didn't compile.
+    Assembly(AssemblyError),
+    /// The machine rejected the ROM, e.g. because it doesn't fit in memory.
+    Chip8(Chip8Error),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(error) => write!(f, "couldn't read the file: {}", error),
+            LoadError::Assembly(error) => write!(f, "{}", error),
+            LoadError::Chip8(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<std::io::Error> for LoadError {
+    fn from(error: std::io::Error) -> Self {
+        LoadError::Io(error)
+    }
+}
+
+impl From<AssemblyError> for LoadError {
+    fn from(error: AssemblyError) -> Self {
+        LoadError::Assembly(error)
+    }
+}
+
+impl From<Chip8Error> for LoadError {
+    fn from(error: Chip8Error) -> Self {
+        LoadError::Chip8(error)
+    }
+}
+
+pub fn run(
+    path_to_rom: &str,
+    platform: Platform,
+    quirks: Quirks,
+    palette: Palette,
+    instructions_per_second: u32,
+    seed: Option<u64>,
+    trace: Option<&str>,
+) -> Result<(), LoadError> {
+    let mut chip8 = Chip8::with_platform(platform, quirks);
+    chip8.set_instructions_per_second(instructions_per_second);
+    // Games are only reproducible when asked to, otherwise every run is different.
+    let rng = seed.map_or_else(SplitMix64::from_entropy, SplitMix64::new);
+    chip8.set_random_source(Box::new(rng));
+    if let Some(trace) = trace {
+        match TraceRecorder::create(trace) {
+            Ok(tracer) => chip8.set_tracer(Some(Box::new(tracer))),
+            Err(error) => println!("Couldn't create the trace file: {}", error),
+        }
+    }
+    // Once the ROM fails, the emulator stops executing it and reports the error in the window.
+    // Files that can't be read or don't compile have no program to show, so they are reported
+    // right away.
+    let error = match load_file(path_to_rom, &mut chip8) {
+        Ok(()) => None,
+        Err(LoadError::Chip8(error)) => Some(error),
+        Err(error) => return Err(error),
     };
-    load_file(path_to_rom, &mut chip8);
+    let mut runner = Runner::new(chip8);
+    runner.debugger = Some(Debugger::new());
+    if let Some(error) = error {
+        runner.stop(error);
+    }
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context
         .video()
@@ -24,92 +106,170 @@ pub fn run(path_to_rom: &str) {
     let audio_subsystem = sdl_context
         .audio()
         .expect("Couldn't initialize the audio component.");
-    let audio_device = AudioDeviceWrapper::new(&audio_subsystem);
+    let mut audio_device = AudioDeviceWrapper::new(&audio_subsystem);
 
     let window = video_subsystem
-        .window("rust-sdl2 demo", 950, 600)
+        .window(WINDOW_TITLE, 950, 600)
         .position_centered()
         .resizable()
         .opengl()
         .build()
         .unwrap();
 
-    let mut canvas = window.into_canvas().build().unwrap();
-    let mut event_pump = sdl_context.event_pump().unwrap();
-    let mut frame_time = Instant::now();
-    let frame_interval = Duration::new(0, 1_000_000_000u32 / chip8.get_fps());
-    'running: loop {
-        for event in event_pump.poll_iter() {
+    let canvas = window.into_canvas().build().unwrap();
+    let texture_creator = canvas.texture_creator();
+    let texture = texture_creator
+        .create_texture_streaming(
+            PixelFormatEnum::RGB888,
+            HIRES_SCREEN_WIDTH as u32,
+            HIRES_SCREEN_HEIGHT as u32,
+        )
+        .expect("Couldn't create the screen texture.");
+    let mut screen = Screen {
+        canvas,
+        texture,
+        palette,
+        window_size: None,
+    };
+    let mut input = Input {
+        event_pump: sdl_context.event_pump().unwrap(),
+        rom_name: get_file_name(path_to_rom),
+    };
+    // The debugger console takes over whenever the program stops, until it's resumed.
+    while let Exit::Debugger(reason) = runner.run(&mut screen, &mut audio_device, &mut input) {
+        let debugger = runner
+            .debugger
+            .as_mut()
+            .expect("The debugger is always set.");
+        if !debugger::repl(debugger, &runner.chip8, reason) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+// The window. The scale follows both its size and the active resolution, since SUPER-CHIP
+// games may switch between low and high resolution at any time. The texture keeps the last
+// frame, so only the rows that changed are uploaded to it.
+struct Screen<'a> {
+    canvas: WindowCanvas,
+    texture: Texture<'a>,
+    palette: Palette,
+    // The size the window had when it was last drawn.
+    window_size: Option<(u32, u32)>,
+}
+
+impl VideoSink for Screen<'_> {
+    fn present(&mut self, frame: &Framebuffer) {
+        let window_size = self
+            .canvas
+            .output_size()
+            .expect("Couldn't get the size of the window.");
+        // Unchanged frames aren't drawn again, unless the window was resized.
+        if !frame.is_dirty() && self.window_size == Some(window_size) {
+            return;
+        }
+        self.window_size = Some(window_size);
+        display::upload(&mut self.texture, frame, &self.palette);
+        let resolution = (frame.width, frame.height);
+        let scale = display::Scale::fit(window_size, resolution);
+        display::draw_to_screen(
+            &mut self.canvas,
+            &self.texture,
+            resolution,
+            &scale,
+            &self.palette,
+        );
+    }
+
+    fn show_error(&mut self, error: Option<&Chip8Error>) {
+        let title = match error {
+            Some(error) => format!("{} - error: {}", WINDOW_TITLE, error),
+            None => String::from(WINDOW_TITLE),
+        };
+        self.canvas
+            .window_mut()
+            .set_title(&title)
+            .expect("Couldn't update the window title.");
+    }
+}
+
+// The keyboard: the keypad, plus F1 to save a state, F2 to load it and F3 to break into the
+// debugger console.
+struct Input {
+    event_pump: EventPump,
+    rom_name: String,
+}
+
+impl InputSource for Input {
+    fn poll(&mut self, runner: &mut Runner) -> bool {
+        for event in self.event_pump.poll_iter() {
             match event {
                 Event::Quit { .. }
                 | Event::KeyDown {
                     keycode: Some(Keycode::Escape),
                     ..
-                } => break 'running,
+                } => return false,
                 Event::KeyDown {
                     keycode: Some(keycode),
                     ..
-                } => {
-                    match keycode {
-                        Keycode::F1 => {
-                            let rom_name = get_file_name(&path_to_rom);
-                            chip8.save_state(&rom_name);
-                        }
-                        Keycode::F2 => {
-                            let rom_name = get_file_name(&path_to_rom);
-                            let cpu = chip8.load_state(&rom_name);
-                            if let Some(cpu) = cpu {
-                                chip8 = cpu;
-                            }
+                } => match keycode {
+                    Keycode::F1 => runner.chip8.save_state(&self.rom_name),
+                    // Breaks into the debugger console before the next instruction.
+                    Keycode::F3 => {
+                        if let Some(debugger) = &mut runner.debugger {
+                            debugger.pause();
                         }
-                        _ => {
-                            let key = map_key(keycode);
-                            chip8.key_down(key, true);
+                    }
+                    Keycode::F2 => {
+                        if let Some(mut cpu) = runner.chip8.load_state(&self.rom_name) {
+                            // Keep tracing to the same file.
+                            cpu.set_tracer(runner.chip8.take_tracer());
+                            runner.load(cpu);
                         }
                     }
-                }
+                    _ => runner.chip8.key_down(map_key(keycode), true),
+                },
                 Event::KeyUp {
                     keycode: Some(keycode),
                     ..
-                } => {
-                    let key = map_key(keycode);
-                    chip8.key_down(key, false);
-                }
-                Event::Window { win_event, .. } => {
-                    if let WindowEvent::Resized(w, h) = win_event {
-                        scale.width = w / 64;
-                        scale.height = h / 32;
-                    }
-                }
+                } => runner.chip8.key_down(map_key(keycode), false),
                 _ => {}
             }
         }
+        true
+    }
+}
 
-        // The rest of the game loop goes here...
-        for _ in 0..NUMBER_OF_CYCLES {
-            chip8.tick();
-        }
-
-        // This sleep call ensures that the system will run at 60fps. Modern hardware is so advanced that the emulator
-        // may run at more than 400fps. So, this step is required in order to achieve a decent execution speed.
-        std::thread::sleep(frame_interval.saturating_sub(frame_time.elapsed()));
-        display::draw_to_screen(&mut canvas, &mut chip8, &scale);
-        canvas.present();
-        let should_beep = chip8.tick_timers();
-        println!("Should beep: {}", should_beep);
-        audio_device.beep(should_beep);
-
-        frame_time = Instant::now();
+/// Runs the ROM without a window, under the control of a GDB client connecting to `port`.
+pub fn serve_gdb(
+    path_to_rom: &str,
+    platform: Platform,
+    quirks: Quirks,
+    instructions_per_second: u32,
+    port: u16,
+) {
+    let mut chip8 = Chip8::with_platform(platform, quirks);
+    chip8.set_instructions_per_second(instructions_per_second);
+    if let Err(error) = load_file(path_to_rom, &mut chip8) {
+        println!("Couldn't load the ROM: {}", error);
+        return;
+    }
+    println!("Waiting for GDB on 127.0.0.1:{}", port);
+    let mut stub = GdbStub::new(chip8);
+    if let Err(error) = stub.listen(("127.0.0.1", port)) {
+        println!("The GDB session failed: {}", error);
     }
 }
 
-pub fn load_file(path: &str, emu: &mut Chip8) {
-    let mut file = std::fs::File::open(path).expect("Couldn't find the specified file.");
-    let mut file_buffer = Vec::new();
-    println!("file_name: {}", get_file_name(&path));
-    file.read_to_end(&mut file_buffer)
-        .expect("Couldn't read file to memory!");
-    emu.load_file(&file_buffer);
+pub fn load_file(path: &str, emu: &mut Chip8) -> Result<(), LoadError> {
+    // Octo sources are compiled on the fly.
+    if path.ends_with(".8o") {
+        let program = octo::compile_file(std::path::Path::new(path))?;
+        return Ok(emu.load_file(&program.rom)?);
+    }
+    let rom = std::fs::read(path)?;
+    Ok(emu.load_file(&rom)?)
 }
 
 pub fn map_key(key: Keycode) -> Option<u8> {
@@ -123,7 +283,7 @@ pub fn map_key(key: Keycode) -> Option<u8> {
         Keycode::E => Some(0x6),
         Keycode::R => Some(0xD),
         Keycode::A => Some(0x7),
-        Keycode::B => Some(0x8),
+        Keycode::S => Some(0x8),
         Keycode::D => Some(0x9),
         Keycode::F => Some(0xE),
         Keycode::Z => Some(0xA),
@@ -136,11 +296,10 @@ pub fn map_key(key: Keycode) -> Option<u8> {
 
 pub fn get_current_time_in_microseconds() -> u128 {
     let current_system_time = std::time::SystemTime::now();
-    let current_time_in_micros = current_system_time
+    current_system_time
         .duration_since(UNIX_EPOCH)
         .expect("Couldn't get the duration since UNIX EPOCH from current system time.")
-        .as_micros();
-    current_time_in_micros
+        .as_micros()
 }
 
 fn get_file_name(file_path: &str) -> String {
@@ -150,7 +309,7 @@ fn get_file_name(file_path: &str) -> String {
         let file_name_with_extension = String::from(*file_name_with_extension);
         let file_name_parts: Vec<&str> = file_name_with_extension.split(".").collect();
         let file_name = file_name_parts.first();
-        let file_name = *file_name.unwrap_or_else(|| &"save-state");
+        let file_name = *file_name.unwrap_or(&"save-state");
         return String::from(file_name);
     }
     String::from("save-state")
@@ -1,11 +1,99 @@
 extern crate sdl2;
-use desktop::run;
+use chip8_core::chip8::DEFAULT_INSTRUCTIONS_PER_SECOND;
+use chip8_core::display::Palette;
+use chip8_core::platform::Platform;
+use chip8_core::quirks::Quirks;
+use desktop::{run, serve_gdb};
+
+const USAGE: &str = "Usage: program [path_to_rom] [--platform chip8|schip|xochip] \
+[--quirks default|vip|chip48|schip|xochip] [--palette RRGGBB,RRGGBB,RRGGBB,RRGGBB] [--ips number] [--gdb port] [--seed number] [--trace path_to_log]";
 
 fn main() {
     let args: Vec<String> = std::env::args().collect();
     if args.len() < 2 {
-        println!("Usage: program [path_to_rom]");
+        println!("{}", USAGE);
         return;
     }
-    run(&args[1]);
+
+    let mut platform = Platform::Chip8;
+    let mut quirks = None;
+    let mut palette = Palette::default();
+    let mut instructions_per_second = DEFAULT_INSTRUCTIONS_PER_SECOND;
+    let mut gdb_port = None;
+    let mut seed = None;
+    let mut trace = None;
+    let mut options = args[2..].iter();
+    while let Some(option) = options.next() {
+        let value = options.next();
+        match (option.as_str(), value) {
+            ("--platform", Some(name)) => match Platform::from_name(name) {
+                Some(selected) => platform = selected,
+                None => {
+                    println!("Unknown platform: {}", name);
+                    return;
+                }
+            },
+            ("--quirks", Some(name)) => match Quirks::from_name(name) {
+                Some(selected) => quirks = Some(selected),
+                None => {
+                    println!("Unknown quirk profile: {}", name);
+                    return;
+                }
+            },
+            ("--palette", Some(list)) => match Palette::from_hex_list(list) {
+                Some(selected) => palette = selected,
+                None => {
+                    println!("Invalid palette: {}", list);
+                    return;
+                }
+            },
+            ("--ips", Some(number)) => match number.parse::<u32>() {
+                Ok(selected) => instructions_per_second = selected,
+                Err(_) => {
+                    println!("Invalid number of instructions per second: {}", number);
+                    return;
+                }
+            },
+            ("--gdb", Some(port)) => match port.parse::<u16>() {
+                Ok(selected) => gdb_port = Some(selected),
+                Err(_) => {
+                    println!("Invalid port: {}", port);
+                    return;
+                }
+            },
+            ("--seed", Some(number)) => match number.parse::<u64>() {
+                Ok(selected) => seed = Some(selected),
+                Err(_) => {
+                    println!("Invalid seed: {}", number);
+                    return;
+                }
+            },
+            ("--trace", Some(path)) => trace = Some(path.as_str()),
+            _ => {
+                println!("{}", USAGE);
+                return;
+            }
+        }
+    }
+
+    // Unless told otherwise, run the ROM with the quirks of its platform.
+    let quirks = quirks.unwrap_or_else(|| platform.default_quirks());
+    match gdb_port {
+        Some(port) => serve_gdb(&args[1], platform, quirks, instructions_per_second, port),
+        None => {
+            let result = run(
+                &args[1],
+                platform,
+                quirks,
+                palette,
+                instructions_per_second,
+                seed,
+                trace,
+            );
+            if let Err(error) = result {
+                eprintln!("{}", error);
+                std::process::exit(1);
+            }
+        }
+    }
 }
@@ -0,0 +1,304 @@
+use chip8_core::chip8::MEMORY_START_ADDRESS;
+use chip8_core::instruction::Instruction;
+use chip8_core::platform::Platform;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fmt::Write;
+
+// Number of bytes per `db` line.
+const BYTES_PER_LINE: usize = 8;
+// Column where the address and raw bytes comment starts.
+const COMMENT_COLUMN: usize = 32;
+/// The largest ROM that fits in the 64 KiB address space after 0x200.
+pub const MAX_ROM_SIZE: usize = 0x10000 - MEMORY_START_ADDRESS as usize;
+
+/// A ROM too large to be addressed, with its size in bytes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RomTooLarge(pub usize);
+
+impl fmt::Display for RomTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the ROM is {} bytes long, but at most {} bytes fit in memory",
+            self.0, MAX_ROM_SIZE
+        )
+    }
+}
+
+impl std::error::Error for RomTooLarge {}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum LabelKind {
+    // Ordered by priority: an address that is both called and read through I is a subroutine.
+    Data,
+    Label,
+    Subroutine,
+}
+
+/// The result of following the control flow of a ROM.
+pub struct Disassembly<'a> {
+    rom: &'a [u8],
+    platform: Platform,
+    // Addresses where a reachable instruction starts.
+    code: BTreeSet<u16>,
+    labels: BTreeMap<u16, LabelKind>,
+}
+
+impl<'a> Disassembly<'a> {
+    /// Follows every path of execution starting at 0x200 to tell code apart from data.
+    pub fn new(rom: &'a [u8], platform: Platform) -> Result<Self, RomTooLarge> {
+        // Addresses past the end of memory can't be named.
+        if rom.len() > MAX_ROM_SIZE {
+            return Err(RomTooLarge(rom.len()));
+        }
+        let mut disassembly = Self {
+            rom,
+            platform,
+            code: BTreeSet::new(),
+            labels: BTreeMap::new(),
+        };
+        disassembly.trace();
+        Ok(disassembly)
+    }
+
+    /// Returns whether a reachable instruction starts at `address`.
+    pub fn is_code(&self, address: u16) -> bool {
+        self.code.contains(&address)
+    }
+
+    /// The name of the label at `address`, if any jump, call or I load targets it.
+    pub fn label(&self, address: u16) -> Option<String> {
+        let prefix = match self.labels.get(&address)? {
+            LabelKind::Subroutine => "sub",
+            LabelKind::Label => "label",
+            LabelKind::Data => "data",
+        };
+        Some(format!("{}_{:03X}", prefix, address))
+    }
+
+    fn end(&self) -> u32 {
+        MEMORY_START_ADDRESS as u32 + self.rom.len() as u32
+    }
+
+    fn contains(&self, address: u16) -> bool {
+        address >= MEMORY_START_ADDRESS && (address as u32) < self.end()
+    }
+
+    fn byte(&self, address: u16) -> Option<u8> {
+        if !self.contains(address) {
+            return None;
+        }
+        Some(self.rom[(address - MEMORY_START_ADDRESS) as usize])
+    }
+
+    fn word(&self, address: u16) -> Option<u16> {
+        let higher_byte = self.byte(address)? as u16;
+        let lower_byte = self.byte(address.checked_add(1)?)? as u16;
+        Some((higher_byte << 8) | lower_byte)
+    }
+
+    // Decodes the instruction at `address`, as long as it's fully inside the ROM and supported
+    // by the platform.
+    fn instruction(&self, address: u16) -> Option<Instruction> {
+        let instruction = Instruction::decode(self.word(address)?)?;
+        if instruction.platform() > self.platform {
+            return None;
+        }
+        if instruction.size() == 4 {
+            self.word(address.checked_add(2)?)?;
+        }
+        Some(instruction)
+    }
+
+    fn add_label(&mut self, address: u16, kind: LabelKind) {
+        if !self.contains(address) {
+            return;
+        }
+        let label = self.labels.entry(address).or_insert(kind);
+        *label = (*label).max(kind);
+    }
+
+    fn trace(&mut self) {
+        let mut pending = vec![MEMORY_START_ADDRESS];
+        while let Some(address) = pending.pop() {
+            if self.code.contains(&address) {
+                continue;
+            }
+            let instruction = match self.instruction(address) {
+                // Machine code routines are never executed, so they are most likely data.
+                Some(Instruction::Sys { .. }) | None => continue,
+                Some(instruction) => instruction,
+            };
+            self.code.insert(address);
+
+            let next = address.wrapping_add(instruction.size());
+            match instruction {
+                Instruction::Jump { addr } => {
+                    self.add_label(addr, LabelKind::Label);
+                    pending.push(addr);
+                }
+                Instruction::Call { addr } => {
+                    self.add_label(addr, LabelKind::Subroutine);
+                    pending.push(addr);
+                    pending.push(next);
+                }
+                Instruction::Return | Instruction::Exit => {}
+                // The destination depends on a register, so the path can't be followed.
+                Instruction::JumpOffset { addr } => self.add_label(addr, LabelKind::Data),
+                Instruction::SkipEqImm { .. }
+                | Instruction::SkipNeImm { .. }
+                | Instruction::SkipEqReg { .. }
+                | Instruction::SkipNeReg { .. }
+                | Instruction::SkipKeyPressed { .. }
+                | Instruction::SkipKeyNotPressed { .. } => {
+                    // On XO-CHIP, skipping over F000 NNNN skips four bytes.
+                    let skipped = match self.instruction(next) {
+                        Some(Instruction::LoadLongI) => 4,
+                        _ => 2,
+                    };
+                    pending.push(next);
+                    pending.push(next.wrapping_add(skipped));
+                }
+                Instruction::LoadI { addr } => {
+                    self.add_label(addr, LabelKind::Data);
+                    pending.push(next);
+                }
+                Instruction::LoadLongI => {
+                    if let Some(addr) = self.word(address + 2) {
+                        self.add_label(addr, LabelKind::Data);
+                    }
+                    pending.push(next);
+                }
+                _ => pending.push(next),
+            }
+        }
+    }
+
+    // Name used to reference `address` in an operand: its label, or the raw address.
+    fn operand(&self, address: u16, digits: usize) -> String {
+        self.label(address)
+            .unwrap_or_else(|| format!("0x{:0digits$X}", address, digits = digits))
+    }
+
+    fn mnemonic(&self, address: u16, instruction: Instruction) -> String {
+        match instruction {
+            Instruction::Jump { addr } => format!("JP {}", self.operand(addr, 3)),
+            Instruction::Call { addr } => format!("CALL {}", self.operand(addr, 3)),
+            Instruction::LoadI { addr } => format!("LD I, {}", self.operand(addr, 3)),
+            Instruction::JumpOffset { addr } => format!("JP V0, {}", self.operand(addr, 3)),
+            Instruction::LoadLongI => {
+                let addr = self.word(address + 2).unwrap_or_default();
+                format!("LD I, LONG {}", self.operand(addr, 4))
+            }
+            _ => instruction.to_string(),
+        }
+    }
+
+    // Whether the instruction at `address` can be printed as such. It can't when a label or
+    // another instruction starts in the middle of it, since they would be swallowed.
+    fn fits(&self, address: u16, instruction: Instruction) -> bool {
+        (1..instruction.size()).all(|offset| {
+            let inner = address + offset;
+            !self.code.contains(&inner) && !self.labels.contains_key(&inner)
+        })
+    }
+
+    fn write_line(output: &mut String, text: &str, address: u16, bytes: &[u8]) {
+        let raw_bytes: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        let line = format!("    {}", text);
+        writeln!(
+            output,
+            "{:width$}; {:04X}: {}",
+            line,
+            address,
+            raw_bytes.join(" "),
+            width = COMMENT_COLUMN
+        )
+        .unwrap();
+    }
+
+    /// Prints the ROM as source code that can be assembled back into the same bytes.
+    pub fn to_source(&self) -> String {
+        let mut output = String::new();
+        let end = self.end();
+        let mut address = MEMORY_START_ADDRESS as u32;
+        while address < end {
+            let current = address as u16;
+            if let Some(label) = self.label(current) {
+                writeln!(output, "{}:", label).unwrap();
+            }
+
+            if let Some(instruction) = self.instruction(current) {
+                if self.is_code(current) && self.fits(current, instruction) {
+                    let size = instruction.size() as usize;
+                    let start = (current - MEMORY_START_ADDRESS) as usize;
+                    let text = self.mnemonic(current, instruction);
+                    Self::write_line(&mut output, &text, current, &self.rom[start..start + size]);
+                    address += size as u32;
+                    continue;
+                }
+            }
+
+            // Data runs until the next label or instruction.
+            let start = (current - MEMORY_START_ADDRESS) as usize;
+            let mut length = 1;
+            while length < BYTES_PER_LINE && address + (length as u32) < end {
+                let next = current + length as u16;
+                if self.code.contains(&next) || self.labels.contains_key(&next) {
+                    break;
+                }
+                length += 1;
+            }
+            let bytes = &self.rom[start..start + length];
+            let values: Vec<String> = bytes.iter().map(|byte| format!("0x{:02X}", byte)).collect();
+            let text = format!("db {}", values.join(", "));
+            Self::write_line(&mut output, &text, current, bytes);
+            address += length as u32;
+        }
+        output
+    }
+}
+
+/// Disassembles a ROM into source code that can be assembled back into the same bytes.
+pub fn disassemble(rom: &[u8], platform: Platform) -> Result<String, RomTooLarge> {
+    Ok(Disassembly::new(rom, platform)?.to_source())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tells_code_apart_from_data() {
+        // LD I, 0x206; DRW V0, V0, 1; JP 0x204 (loop); then a sprite.
+        let rom = [0xA2, 0x06, 0xD0, 0x01, 0x12, 0x04, 0xFF];
+        let source = disassemble(&rom, Platform::Chip8).unwrap();
+        let expected = "    LD I, data_206              ; 0200: A2 06\n\
+                        \x20   DRW V0, V0, 1               ; 0202: D0 01\n\
+                        label_204:\n\
+                        \x20   JP label_204                ; 0204: 12 04\n\
+                        data_206:\n\
+                        \x20   db 0xFF                     ; 0206: FF\n";
+        assert_eq!(source, expected);
+    }
+
+    #[test]
+    fn follows_both_paths_of_skips() {
+        // SE V0, 0; RET; CALL 0x208; JP 0x206 (loop); RET
+        let rom = [0x30, 0x00, 0x00, 0xEE, 0x22, 0x08, 0x12, 0x06, 0x00, 0xEE];
+        let disassembly = Disassembly::new(&rom, Platform::Chip8).unwrap();
+        for address in [0x200, 0x202, 0x204, 0x206, 0x208] {
+            assert!(disassembly.is_code(address), "{:03X}", address);
+        }
+        assert_eq!(disassembly.label(0x208).as_deref(), Some("sub_208"));
+    }
+
+    #[test]
+    fn rejects_roms_past_the_end_of_memory() {
+        let rom = vec![0; MAX_ROM_SIZE];
+        assert!(disassemble(&rom, Platform::XoChip).is_ok());
+        let rom = vec![0; 65025];
+        assert_eq!(disassemble(&rom, Platform::XoChip), Err(RomTooLarge(65025)));
+    }
+}
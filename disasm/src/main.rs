@@ -0,0 +1,39 @@
+use chip8_core::platform::Platform;
+use disasm::disassemble;
+
+const USAGE: &str = "Usage: chip8-disasm [path_to_rom] [--platform chip8|schip|xochip]";
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 {
+        println!("{}", USAGE);
+        return;
+    }
+
+    let platform = match (args.get(2).map(String::as_str), args.get(3)) {
+        (None, _) => Platform::Chip8,
+        (Some("--platform"), Some(name)) => match Platform::from_name(name) {
+            Some(platform) => platform,
+            None => {
+                eprintln!("Unknown platform: {}", name);
+                std::process::exit(1);
+            }
+        },
+        _ => {
+            println!("{}", USAGE);
+            return;
+        }
+    };
+
+    let rom = std::fs::read(&args[1]).unwrap_or_else(|error| {
+        eprintln!("Couldn't read {}: {}", args[1], error);
+        std::process::exit(1);
+    });
+    match disassemble(&rom, platform) {
+        Ok(source) => print!("{}", source),
+        Err(error) => {
+            eprintln!("Couldn't disassemble {}: {}", args[1], error);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! Runs arbitrary bytes as a ROM, with arbitrary key presses. Inputs are laid out as
+//!
+//! - 1 byte: the platform (CHIP-8, SUPER-CHIP or XO-CHIP),
+//! - 1 byte: the quirks, one bit each,
+//! - 1 byte: the number of key events, followed by 2 bytes per event: the frame it happens at
+//!   and the key in the low nibble, pressed if bit 4 is set,
+//! - the rest: the ROM.
+//!
+//! so the ROMs of the seed corpus only need a small header. Errors are expected, panics aren't.
+#![no_main]
+
+use chip8_core::chip8::Chip8;
+use chip8_core::platform::Platform;
+use chip8_core::quirks::Quirks;
+use libfuzzer_sys::fuzz_target;
+
+const FRAMES: usize = 500;
+
+struct KeyEvent {
+    frame: usize,
+    key: u8,
+    is_down: bool,
+}
+
+fn split_input(data: &[u8]) -> Option<(Platform, Quirks, Vec<KeyEvent>, &[u8])> {
+    let (&[platform, quirks, events], rest) = data.split_first_chunk::<3>()?;
+    let platform = match platform % 3 {
+        0 => Platform::Chip8,
+        1 => Platform::SuperChip,
+        _ => Platform::XoChip,
+    };
+    let quirks = Quirks {
+        shift: quirks & 1 != 0,
+        load_store: quirks & 2 != 0,
+        jump: quirks & 4 != 0,
+        vf_reset: quirks & 8 != 0,
+        clipping: quirks & 16 != 0,
+        display_wait: quirks & 32 != 0,
+    };
+    let events_len = (events as usize * 2).min(rest.len());
+    let (events, rom) = rest.split_at(events_len);
+    let events = events
+        .chunks_exact(2)
+        .map(|event| KeyEvent {
+            frame: event[0] as usize,
+            key: event[1] & 0xF,
+            is_down: event[1] & 0x10 != 0,
+        })
+        .collect();
+    Some((platform, quirks, events, rom))
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Some((platform, quirks, events, rom)) = split_input(data) else {
+        return;
+    };
+    let mut chip8 = Chip8::with_platform(platform, quirks);
+    if chip8.load_file(rom).is_err() {
+        return;
+    }
+
+    for frame in 0..FRAMES {
+        for event in events.iter().filter(|event| event.frame == frame) {
+            chip8.key_down(Some(event.key), event.is_down);
+        }
+        if chip8.run_frame().error.is_some() {
+            break;
+        }
+    }
+
+    // Whatever state the ROM leaves behind must be a valid save state.
+    let state = serde_json::to_vec(&chip8).expect("Couldn't serialize the CPU.");
+    serde_json::from_slice::<Chip8>(&state).expect("Couldn't load a save state of the CPU.");
+});
@@ -0,0 +1,25 @@
+//! Loads arbitrary bytes as a JSON save state and, if they are accepted, keeps running the
+//! program. Rejecting a state is fine, but an accepted one must never make the CPU panic.
+#![no_main]
+
+use chip8_core::chip8::Chip8;
+use libfuzzer_sys::fuzz_target;
+
+const TICKS: usize = 1000;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(mut chip8) = serde_json::from_slice::<Chip8>(data) else {
+        return;
+    };
+    for tick in 0..TICKS {
+        if chip8.tick().is_err() {
+            break;
+        }
+        if tick % 8 == 7 {
+            chip8.tick_timers();
+        }
+    }
+    let _ = chip8.get_display();
+    let _ = chip8.get_stack();
+    let _ = chip8.get_audio_pattern();
+});
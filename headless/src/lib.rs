@@ -0,0 +1,286 @@
+pub mod screen;
+
+use chip8_core::chip8::{Chip8, DEFAULT_INSTRUCTIONS_PER_SECOND};
+use chip8_core::error::Chip8Error;
+use chip8_core::input::InputSource;
+use chip8_core::instruction::Instruction;
+use chip8_core::runner::Runner;
+
+/// Frames a scripted key is held down for unless the script says otherwise.
+pub const DEFAULT_PRESS_LENGTH: u32 = 5;
+
+/// A key held down from `frame` for `length` frames, at least one. Presses of the same key may
+/// overlap, in which case it's held until the last one ends.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct KeyPress {
+    pub frame: u32,
+    pub key: u8,
+    pub length: u32,
+}
+
+/// Parses a key script: `FRAME:KEY[:LENGTH]` entries separated by commas, spaces or new lines,
+/// with the key in hexadecimal. Everything after a `#` on a line is a comment.
+pub fn parse_key_script(script: &str) -> Result<Vec<KeyPress>, String> {
+    script
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .flat_map(|line| line.split(|c: char| c == ',' || c.is_whitespace()))
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| parse_key_press(entry).ok_or_else(|| format!("Invalid key press: {}", entry)))
+        .collect()
+}
+
+fn parse_key_press(entry: &str) -> Option<KeyPress> {
+    let mut parts = entry.split(':');
+    let frame = parts.next()?.parse().ok()?;
+    let key = u8::from_str_radix(parts.next()?, 16)
+        .ok()
+        .filter(|key| *key <= 0xF)?;
+    let length = match parts.next() {
+        Some(length) => length.parse().ok().filter(|length| *length > 0)?,
+        None => DEFAULT_PRESS_LENGTH,
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(KeyPress { frame, key, length })
+}
+
+/// Stops a run before the last frame.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StopCondition {
+    /// The program exited with 00FD.
+    Halt,
+    /// The program jumps to the instruction itself, as test ROMs do once they are done.
+    Loop,
+    /// The program counter reached the address.
+    Pc(u16),
+}
+
+impl StopCondition {
+    /// Parses `halt`, `loop` or `pc:ADDR`, with the address in hexadecimal.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "halt" => Some(StopCondition::Halt),
+            "loop" => Some(StopCondition::Loop),
+            _ => {
+                let address = name.strip_prefix("pc:")?;
+                let address = address.strip_prefix("0x").unwrap_or(address);
+                u16::from_str_radix(address, 16).ok().map(StopCondition::Pc)
+            }
+        }
+    }
+
+    fn is_met(&self, chip8: &Chip8) -> bool {
+        match *self {
+            StopCondition::Halt => chip8.is_halted(),
+            StopCondition::Loop => {
+                chip8.get_current_instruction()
+                    == Some(Instruction::Jump {
+                        addr: chip8.get_pc(),
+                    })
+            }
+            StopCondition::Pc(address) => chip8.get_pc() == address,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RunOptions {
+    pub frames: u32,
+    pub instructions_per_second: u32,
+    pub keys: Vec<KeyPress>,
+    pub stop: Vec<StopCondition>,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        Self {
+            frames: 600,
+            instructions_per_second: DEFAULT_INSTRUCTIONS_PER_SECOND,
+            keys: Vec::new(),
+            stop: Vec::new(),
+        }
+    }
+}
+
+/// How a run ended.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// Every frame was run.
+    Finished,
+    /// A stop condition was met.
+    Stopped(StopCondition),
+    /// The program crashed.
+    Crashed(Chip8Error),
+}
+
+/// The result of `run`, with the number of frames that were fully or partially run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    pub frames: u32,
+    pub outcome: Outcome,
+}
+
+// Presses and releases the scripted keys as their frames come.
+struct ScriptedKeys<'a> {
+    keys: &'a [KeyPress],
+    frame: u32,
+    // The presses holding each key down.
+    held: [u32; 16],
+}
+
+impl InputSource for ScriptedKeys<'_> {
+    fn poll(&mut self, runner: &mut Runner) -> bool {
+        let before = self.held;
+        for press in self.keys {
+            let held = &mut self.held[press.key as usize];
+            if self.frame == press.frame {
+                *held += 1;
+            }
+            if self.frame == press.frame.saturating_add(press.length) {
+                *held -= 1;
+            }
+        }
+        // Only keys whose state changed, so one press ending as another starts isn't a release.
+        for (key, (before, after)) in before.iter().zip(self.held).enumerate() {
+            if (*before > 0) != (after > 0) {
+                runner.chip8.key_down(Some(key as u8), after > 0);
+            }
+        }
+        self.frame += 1;
+        true
+    }
+}
+
+/// Runs the loaded program without any frontend. Every frame, scripted keys are pressed or
+/// released, then the instructions due at `instructions_per_second` are executed and the timers
+/// tick once.
+pub fn run(chip8: &mut Chip8, options: &RunOptions) -> Report {
+    // The runner borrows the machine for the run.
+    let mut machine = std::mem::replace(chip8, Chip8::new());
+    machine.set_instructions_per_second(options.instructions_per_second);
+    let mut runner = Runner::new(machine);
+    let mut keys = ScriptedKeys {
+        keys: &options.keys,
+        frame: 0,
+        held: [0; 16],
+    };
+    let mut met = None;
+    let mut report = Report {
+        frames: options.frames,
+        outcome: Outcome::Finished,
+    };
+    for frame in 0..options.frames {
+        let exit = runner.run_frame_until(&mut (), &mut (), &mut keys, |chip8| {
+            met = options
+                .stop
+                .iter()
+                .find(|condition| condition.is_met(chip8))
+                .copied();
+            met.is_some()
+        });
+        let outcome = match (exit, runner.error()) {
+            (Some(_), _) => Outcome::Stopped(met.expect("The runner only stops on conditions.")),
+            (None, Some(error)) => Outcome::Crashed(*error),
+            (None, None) => continue,
+        };
+        report = Report {
+            frames: frame + 1,
+            outcome,
+        };
+        break;
+    }
+    *chip8 = runner.chip8;
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_key_scripts() {
+        let script = "10:5, 20:a:2 # jump\n30:F:1";
+        assert_eq!(
+            parse_key_script(script),
+            Ok(vec![
+                KeyPress {
+                    frame: 10,
+                    key: 5,
+                    length: DEFAULT_PRESS_LENGTH
+                },
+                KeyPress {
+                    frame: 20,
+                    key: 0xA,
+                    length: 2
+                },
+                KeyPress {
+                    frame: 30,
+                    key: 0xF,
+                    length: 1
+                },
+            ])
+        );
+        assert!(parse_key_script("10:10").is_err());
+        assert!(parse_key_script("10").is_err());
+        assert!(parse_key_script("10:5:0").is_err());
+    }
+
+    #[test]
+    fn holds_keys_until_the_last_overlapping_press_ends() {
+        // loop: SKP V0; JP self; JP loop: runs into the JP to itself once key 0 is released.
+        let rom = [0xE0, 0x9E, 0x12, 0x02, 0x12, 0x00];
+        let mut chip8 = Chip8::new();
+        chip8.load_file(&rom).unwrap();
+        let options = RunOptions {
+            keys: vec![
+                KeyPress {
+                    frame: 0,
+                    key: 0,
+                    length: 10,
+                },
+                KeyPress {
+                    frame: 5,
+                    key: 0,
+                    length: 10,
+                },
+            ],
+            stop: vec![StopCondition::Loop],
+            ..RunOptions::default()
+        };
+        let report = run(&mut chip8, &options);
+        assert_eq!(report.outcome, Outcome::Stopped(StopCondition::Loop));
+        assert_eq!(report.frames, 16);
+    }
+
+    #[test]
+    fn stops_on_conditions_and_crashes() {
+        // Waits for key 7, stores it in V1 and loops forever.
+        let rom = [0xF1, 0x0A, 0x12, 0x02];
+        let mut chip8 = Chip8::new();
+        chip8.load_file(&rom).unwrap();
+        let options = RunOptions {
+            frames: 100,
+            keys: vec![KeyPress {
+                frame: 3,
+                key: 7,
+                length: 1,
+            }],
+            stop: vec![StopCondition::Loop],
+            ..RunOptions::default()
+        };
+        let report = run(&mut chip8, &options);
+        assert_eq!(report.outcome, Outcome::Stopped(StopCondition::Loop));
+        assert_eq!(chip8.get_registers()[1], 7);
+
+        let mut chip8 = Chip8::new();
+        chip8.load_file(&[0xFF, 0xFF]).unwrap();
+        let report = run(&mut chip8, &RunOptions::default());
+        assert_eq!(report.frames, 1);
+        assert!(matches!(
+            report.outcome,
+            Outcome::Crashed(Chip8Error::InvalidOpcode { .. })
+        ));
+    }
+}
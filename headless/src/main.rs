@@ -0,0 +1,138 @@
+use chip8_core::chip8::Chip8;
+use chip8_core::debugger::dump_state;
+use chip8_core::display::Palette;
+use chip8_core::platform::Platform;
+use chip8_core::quirks::Quirks;
+use headless::{parse_key_script, run, screen, Outcome, RunOptions, StopCondition};
+use std::process::exit;
+
+const USAGE: &str = "Usage: chip8-headless [path_to_rom] [--platform chip8|schip|xochip] \
+[--quirks default|vip|chip48|schip|xochip] [--frames number] [--ips number] \
+[--until halt|loop|pc:ADDR] [--keys FRAME:KEY[:LENGTH],...] [--key-script path] [--seed number] \
+[--screen path.txt|path.pbm|path.png] [--palette RRGGBB,RRGGBB,RRGGBB,RRGGBB]";
+
+// Exit codes, so scripts can tell what happened.
+const EXIT_CRASHED: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_NOT_STOPPED: i32 = 3;
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 {
+        println!("{}", USAGE);
+        exit(EXIT_USAGE);
+    }
+
+    let mut platform = Platform::Chip8;
+    let mut quirks = None;
+    let mut palette = Palette::default();
+    let mut seed = None;
+    let mut screen_path = None;
+    let mut options = RunOptions::default();
+    let mut arguments = args[2..].iter();
+    while let Some(option) = arguments.next() {
+        let value = arguments.next();
+        match (option.as_str(), value) {
+            ("--platform", Some(name)) => {
+                platform = parse(Platform::from_name(name), "platform", name)
+            }
+            ("--quirks", Some(name)) => {
+                quirks = Some(parse(Quirks::from_name(name), "quirk profile", name))
+            }
+            ("--palette", Some(list)) => {
+                palette = parse(Palette::from_hex_list(list), "palette", list)
+            }
+            ("--frames", Some(number)) => {
+                options.frames = parse(number.parse().ok(), "number of frames", number)
+            }
+            ("--ips", Some(number)) => {
+                options.instructions_per_second =
+                    parse(number.parse().ok(), "instructions per second", number)
+            }
+            ("--seed", Some(number)) => seed = Some(parse(number.parse().ok(), "seed", number)),
+            ("--until", Some(name)) => {
+                options
+                    .stop
+                    .push(parse(StopCondition::from_name(name), "condition", name))
+            }
+            ("--keys", Some(script)) => options.keys.extend(parse_keys(script)),
+            ("--key-script", Some(path)) => {
+                let script = std::fs::read_to_string(path).unwrap_or_else(|error| {
+                    eprintln!("Couldn't read {}: {}", path, error);
+                    exit(EXIT_USAGE);
+                });
+                options.keys.extend(parse_keys(&script));
+            }
+            ("--screen", Some(path)) => screen_path = Some(path.as_str()),
+            _ => {
+                println!("{}", USAGE);
+                exit(EXIT_USAGE);
+            }
+        }
+    }
+
+    let rom = std::fs::read(&args[1]).unwrap_or_else(|error| {
+        eprintln!("Couldn't read {}: {}", args[1], error);
+        exit(EXIT_USAGE);
+    });
+    let quirks = quirks.unwrap_or_else(|| platform.default_quirks());
+    let mut chip8 = Chip8::with_platform(platform, quirks);
+    if let Some(seed) = seed {
+        chip8.seed_random(seed);
+    }
+    if let Err(error) = chip8.load_file(&rom) {
+        eprintln!("Couldn't load the ROM: {}", error);
+        exit(EXIT_CRASHED);
+    }
+
+    let report = run(&mut chip8, &options);
+    match screen_path {
+        Some(path) => write_screen(&chip8, path, &palette),
+        None => print!("{}", screen::to_ascii(&chip8)),
+    }
+    println!("Frames: {}", report.frames);
+    println!("Screen hash: {:016X}", screen::hash(&chip8));
+    print!("{}", dump_state(&chip8));
+
+    match report.outcome {
+        Outcome::Crashed(error) => {
+            eprintln!("Crashed: {}", error);
+            exit(EXIT_CRASHED);
+        }
+        // Asking for a stop condition means the ROM was expected to reach it.
+        Outcome::Finished if !options.stop.is_empty() => {
+            eprintln!("No stop condition was met after {} frames", report.frames);
+            exit(EXIT_NOT_STOPPED);
+        }
+        Outcome::Finished | Outcome::Stopped(_) => {}
+    }
+}
+
+fn parse<T>(value: Option<T>, what: &str, text: &str) -> T {
+    value.unwrap_or_else(|| {
+        eprintln!("Invalid {}: {}", what, text);
+        exit(EXIT_USAGE);
+    })
+}
+
+fn parse_keys(script: &str) -> Vec<headless::KeyPress> {
+    parse_key_script(script).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        exit(EXIT_USAGE);
+    })
+}
+
+// The format follows the extension of the file: PBM, PNG or otherwise ASCII.
+fn write_screen(chip8: &Chip8, path: &str, palette: &Palette) {
+    let data = if path.ends_with(".png") {
+        screen::to_png(chip8, palette)
+    } else if path.ends_with(".pbm") {
+        screen::to_pbm(chip8).into_bytes()
+    } else {
+        screen::to_ascii(chip8).into_bytes()
+    };
+    if let Err(error) = std::fs::write(path, data) {
+        eprintln!("Couldn't write {}: {}", path, error);
+        exit(EXIT_USAGE);
+    }
+}
@@ -0,0 +1,104 @@
+use chip8_core::chip8::Chip8;
+use chip8_core::display::Palette;
+use std::fmt::Write;
+
+// Characters for the palette indices 0 to 3, i.e. no plane, the first, the second and both.
+const ASCII_PIXELS: [char; 4] = ['.', '#', '+', '@'];
+
+/// The pixels of the active resolution, as palette indices, along with its width and height.
+pub fn pixels(chip8: &Chip8) -> (usize, usize, Vec<u8>) {
+    let (width, height) = chip8.get_resolution();
+    let display = chip8.get_display();
+    (width, height, display[..width * height].to_vec())
+}
+
+/// One line of text per row, with `.` for unlit pixels.
+pub fn to_ascii(chip8: &Chip8) -> String {
+    let (width, _, pixels) = pixels(chip8);
+    let mut text = String::new();
+    for row in pixels.chunks(width) {
+        text.extend(row.iter().map(|pixel| ASCII_PIXELS[(*pixel & 3) as usize]));
+        text.push('\n');
+    }
+    text
+}
+
+/// A plain PBM (P1) image, where every lit pixel is black, whatever plane it's drawn on.
+pub fn to_pbm(chip8: &Chip8) -> String {
+    let (width, height, pixels) = pixels(chip8);
+    let mut text = String::new();
+    writeln!(text, "P1\n{} {}", width, height).unwrap();
+    for row in pixels.chunks(width) {
+        let row: Vec<&str> = row
+            .iter()
+            .map(|pixel| if *pixel != 0 { "1" } else { "0" })
+            .collect();
+        writeln!(text, "{}", row.join(" ")).unwrap();
+    }
+    text
+}
+
+/// An RGB PNG image with one image pixel per CHIP-8 pixel, drawn with the given palette.
+pub fn to_png(chip8: &Chip8, palette: &Palette) -> Vec<u8> {
+    let (width, height, pixels) = pixels(chip8);
+    let data: Vec<u8> = pixels
+        .iter()
+        .flat_map(|pixel| {
+            let (r, g, b) = palette.colors[(*pixel & 3) as usize];
+            [r, g, b]
+        })
+        .collect();
+
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder
+        .write_header()
+        .expect("Couldn't write the PNG header to memory.");
+    writer
+        .write_image_data(&data)
+        .expect("Couldn't write the PNG image to memory.");
+    writer.finish().expect("Couldn't finish the PNG image.");
+    png
+}
+
+/// A 64-bit FNV-1a hash of the resolution and pixels of the screen, to compare screens at a
+/// glance.
+pub fn hash(chip8: &Chip8) -> u64 {
+    const OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01B3;
+    let (width, height, pixels) = pixels(chip8);
+    [width as u8, height as u8]
+        .iter()
+        .chain(&pixels)
+        .fold(OFFSET_BASIS, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(PRIME)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_the_screen() {
+        // Draws the top row of the font's 0 at (0, 0), i.e. 11110000.
+        let mut chip8 = Chip8::new();
+        chip8.load_file(&[0xA0, 0x00, 0xD0, 0x01]).unwrap();
+        let blank = hash(&chip8);
+        chip8.tick().unwrap();
+        chip8.tick().unwrap();
+        assert_ne!(hash(&chip8), blank);
+
+        let ascii = to_ascii(&chip8);
+        assert_eq!(ascii.lines().count(), 32);
+        assert!(ascii.starts_with(&format!("####{}\n", ".".repeat(60))));
+
+        let pbm = to_pbm(&chip8);
+        assert!(pbm.starts_with("P1\n64 32\n1 1 1 1 0 "));
+
+        let png = to_png(&chip8, &Palette::default());
+        assert_eq!(&png[1..4], b"PNG");
+    }
+}
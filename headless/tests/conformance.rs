@@ -0,0 +1,123 @@
+//! Runs the test ROMs in `tests/roms` headlessly and compares their final screen and registers
+//! with the snapshots in `tests/golden`. After an intentional change in behaviour, regenerate
+//! the snapshots with `UPDATE_GOLDEN=1 cargo test -p headless --test conformance` and review
+//! the diff.
+//!
+//! Each ROM is assembled with `chip8-asm` and may start with these comments:
+//! `; platform: schip`, `; quirks: default, vip` (one snapshot per profile), `; keys: 5:A:3`
+//! and `; frames: 60`. The program must end in a jump to itself.
+
+use chip8_core::chip8::Chip8;
+use chip8_core::debugger::dump_state;
+use chip8_core::platform::Platform;
+use chip8_core::quirks::Quirks;
+use headless::{parse_key_script, run, screen, Outcome, RunOptions, StopCondition};
+use std::path::{Path, PathBuf};
+
+const DEFAULT_FRAMES: u32 = 120;
+
+struct TestRom {
+    name: String,
+    rom: Vec<u8>,
+    platform: Platform,
+    quirks: Vec<String>,
+    options: RunOptions,
+}
+
+fn load_test_rom(path: &Path) -> TestRom {
+    let source = std::fs::read_to_string(path).unwrap();
+    let mut platform = Platform::Chip8;
+    let mut quirks = vec![String::from("default")];
+    let mut options = RunOptions {
+        frames: DEFAULT_FRAMES,
+        stop: vec![StopCondition::Loop],
+        ..RunOptions::default()
+    };
+    let directives = source
+        .lines()
+        .map_while(|line| line.strip_prefix(';'))
+        .filter_map(|line| line.split_once(':'));
+    for (name, value) in directives {
+        let value = value.trim();
+        match name.trim() {
+            "platform" => platform = Platform::from_name(value).unwrap(),
+            "quirks" => {
+                quirks = value
+                    .split(',')
+                    .map(|name| name.trim().to_string())
+                    .collect()
+            }
+            "keys" => options.keys = parse_key_script(value).unwrap(),
+            "frames" => options.frames = value.parse().unwrap(),
+            _ => {}
+        }
+    }
+
+    let rom = assembler::assemble_file(path).unwrap_or_else(|error| panic!("{}", error));
+    TestRom {
+        name: path.file_stem().unwrap().to_string_lossy().into_owned(),
+        rom,
+        platform,
+        quirks,
+        options,
+    }
+}
+
+// The final screen and registers, as stored in the golden snapshots.
+fn snapshot(test: &TestRom, quirks: &str) -> String {
+    let mut chip8 = Chip8::with_platform(test.platform, Quirks::from_name(quirks).unwrap());
+    chip8.load_file(&test.rom).unwrap();
+    let report = run(&mut chip8, &test.options);
+    assert_eq!(
+        report.outcome,
+        Outcome::Stopped(StopCondition::Loop),
+        "{} ({}) didn't reach its final loop",
+        test.name,
+        quirks
+    );
+    format!("{}{}", screen::to_ascii(&chip8), dump_state(&chip8))
+}
+
+fn test_roms() -> Vec<PathBuf> {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/roms");
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "asm"))
+        .collect();
+    paths.sort();
+    paths
+}
+
+#[test]
+fn test_roms_match_golden_snapshots() {
+    let golden = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let mut mismatches = Vec::new();
+    for path in test_roms() {
+        let test = load_test_rom(&path);
+        for quirks in &test.quirks {
+            let actual = snapshot(&test, quirks);
+            let golden_path = golden.join(format!("{}.{}.txt", test.name, quirks));
+            if update {
+                std::fs::write(&golden_path, &actual).unwrap();
+                continue;
+            }
+            let expected = std::fs::read_to_string(&golden_path).unwrap_or_default();
+            if actual != expected {
+                mismatches.push(format!(
+                    "{}:\n--- expected\n{}--- actual\n{}",
+                    golden_path.display(),
+                    expected,
+                    actual
+                ));
+            }
+        }
+    }
+    assert!(
+        mismatches.is_empty(),
+        "{} snapshot(s) differ (run with UPDATE_GOLDEN=1 to regenerate them):\n{}",
+        mismatches.len(),
+        mismatches.join("\n")
+    );
+}
@@ -0,0 +1,35 @@
+; FX33, read back with FX65, and the digits of 137 drawn with FX29.
+        LD I, scratch
+        LD V0, 0
+        LD B, V0
+        LD V2, [I]
+        LD V9, V0
+        LD VA, V1
+        LD VB, V2
+        LD I, scratch
+        LD V0, 255
+        LD B, V0
+        LD V2, [I]
+        LD VC, V0
+        LD VD, V1
+        LD VE, V2
+        LD I, scratch
+        LD V0, 137
+        LD B, V0
+        LD V2, [I]
+        LD V3, V0
+        LD V4, V1
+        LD V5, V2
+        LD V6, 0
+        LD V7, 0
+        LD F, V3
+        DRW V6, V7, 5
+        ADD V6, 5
+        LD F, V4
+        DRW V6, V7, 5
+        ADD V6, 5
+        LD F, V5
+        DRW V6, V7, 5
+done:   JP done
+scratch:
+        db 0, 0, 0
@@ -0,0 +1,11 @@
+; 2NNN and 00EE. Stops inside a nested subroutine, so the stack holds two return addresses.
+        LD V0, 0
+        CALL first
+        ADD V0, 0x10
+        CALL second
+done:   JP done
+first:  ADD V0, 1
+        RET
+second: ADD V0, 2
+        CALL third
+third:  JP third
@@ -0,0 +1,26 @@
+; 8XY4, 8XY5, 8XY7 and 7XNN. Results are left in V0-VA and the flags in VB-VE.
+        LD V0, 0x10
+        LD V1, 0x20
+        ADD V0, V1              ; 0x30, no carry
+        LD VC, VF
+        LD V2, 0xFF
+        LD V3, 0x02
+        ADD V2, V3              ; 0x01, carry
+        LD VD, VF
+        LD V4, 0x05
+        LD V5, 0x07
+        SUB V4, V5              ; 0xFE, borrow
+        LD VE, VF
+        LD V6, 0x07
+        LD V7, 0x05
+        SUB V6, V7              ; 0x02, no borrow
+        LD VB, VF
+        LD V8, 0x05
+        LD V9, 0x07
+        SUBN V8, V9             ; 0x02, no borrow
+        LD VA, 0xFF
+        ADD VA, 0x02            ; 0x01, VF untouched
+        LD V1, 0x01
+        LD VF, 0xFF
+        ADD VF, V1              ; the flag overwrites the sum
+done:   JP done
@@ -0,0 +1,21 @@
+; FX29, drawing the 16 digits of the font in two rows, and 00E0 clearing a first drawing.
+        LD V0, 0
+        LD V1, 0
+        LD F, V0
+        DRW V0, V1, 5
+        CLS
+        LD V2, 0                ; digit
+row:    LD V0, 0
+column: LD F, V2
+        DRW V0, V1, 5
+        ADD V0, 5
+        ADD V2, 1
+        LD V3, V2
+        LD V4, 7
+        AND V3, V4
+        SE V3, 0
+        JP column
+        ADD V1, 6
+        SE V2, 16
+        JP row
+done:   JP done
@@ -0,0 +1,11 @@
+; BNNN. With the jump quirk it behaves as BXNN, adding VX instead of V0.
+; quirks: default, schip
+        LD V0, 4
+        LD V2, 8
+        JP V0, table
+table:  LD V5, 0x11
+        LD V5, 0x22
+        LD V5, 0xA0             ; table + V0
+        JP done
+        LD V5, 0xB0             ; table + V2
+done:   JP done
@@ -0,0 +1,15 @@
+; FX0A, EX9E and EXA1 with scripted keys.
+; keys: 5:A:3, 20:3:10
+; frames: 60
+        LD V0, K                ; gets A at frame 5
+        LD V1, 3
+wait:   SKP V1
+        JP wait                 ; until 3 is pressed at frame 20
+        LD V2, 0x01
+        LD V3, 0x0A
+        SKNP V3                 ; A was released at frame 8
+        LD V4, 0x01
+        SKP V1                  ; 3 is still down
+        LD V5, 0x01
+        LD V6, DT
+done:   JP done
@@ -0,0 +1,19 @@
+; FX55 and FX65. Without the load/store quirk, I is left past the last register.
+; quirks: default, vip
+        LD V0, 0x11
+        LD V1, 0x22
+        LD V2, 0x33
+        LD I, scratch
+        LD [I], V2
+        LD V3, 0
+        ADD V3, 1
+        LD I, scratch
+        LD V2, [I]
+        LD V4, V0
+        LD V5, V1
+        LD V6, V2
+        LD I, scratch
+        LD V0, [I]
+done:   JP done
+scratch:
+        db 0, 0, 0, 0
@@ -0,0 +1,16 @@
+; 8XY1, 8XY2 and 8XY3. The VF reset quirk clears VF after each of them.
+; quirks: default, vip
+        LD V0, 0x0F
+        LD V1, 0xF0
+        LD VF, 0x55
+        OR V0, V1               ; 0xFF
+        LD V4, VF
+        LD V2, 0x3C
+        LD VF, 0x55
+        AND V2, V1              ; 0x30
+        LD V5, VF
+        LD V3, 0x3C
+        LD VF, 0x55
+        XOR V3, V1              ; 0xCC
+        LD V6, VF
+done:   JP done
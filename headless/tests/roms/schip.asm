@@ -0,0 +1,23 @@
+; SUPER-CHIP high resolution, big font, 16x16 sprites, scrolling and RPL flags.
+; platform: schip
+; quirks: schip
+        HIGH
+        LD V0, 0
+        LD V1, 0
+        LD V2, 0x0B
+        LD HF, V2
+        DRW V0, V1, 10
+        LD V0, 16
+        LD I, square
+        DRW V0, V1, 0
+        SCR
+        SCD 4
+        LD V2, 0x42
+        LD V3, 0x43
+        LD R, V3
+        LD V2, 0
+        LD V3, 0
+        LD V3, R
+done:   JP done
+square: dw 0xFFFF, 0x8001, 0x8001, 0x8001, 0x8001, 0x8001, 0x8001, 0x8001
+        dw 0x8001, 0x8001, 0x8001, 0x8001, 0x8001, 0x8001, 0x8001, 0xFFFF
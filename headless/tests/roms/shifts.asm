@@ -0,0 +1,17 @@
+; 8XY6 and 8XYE. Without the shift quirk, VY is shifted into VX.
+; quirks: default, vip
+        LD V0, 0x01
+        LD V1, 0x80
+        SHR V0, V1
+        LD V2, VF
+        LD V3, 0x40
+        LD V4, 0x81
+        SHL V3, V4
+        LD V5, VF
+        LD V6, 0x81
+        LD V7, 0x02
+        SHL V6, V7
+        LD V8, VF
+        LD VF, 0x03
+        SHR VF, VF              ; the flag overwrites the result
+done:   JP done
@@ -0,0 +1,16 @@
+; DXYN at the edges of the screen, with and without the clipping quirk, and collisions.
+; quirks: default, vip
+        LD I, block
+        LD V0, 60
+        LD V1, 30
+        DRW V0, V1, 4           ; wraps around or gets clipped
+        LD V2, VF
+        DRW V0, V1, 4           ; erases it
+        LD V3, VF
+        DRW V0, V1, 4
+        LD V0, 70
+        LD V1, 40
+        DRW V0, V1, 4           ; the starting position always wraps
+        LD V4, VF
+done:   JP done
+block:  db 0xFF, 0x81, 0x81, 0xFF
@@ -0,0 +1,12 @@
+; FX15, FX18 and FX07, with the timers ticking once per frame.
+; frames: 30
+        LD V0, 10
+        LD V1, 20
+        LD DT, V0
+        LD ST, V1
+        LD V3, 0
+wait:   ADD V3, 1
+        LD V2, DT
+        SE V2, 0
+        JP wait
+done:   JP done
@@ -0,0 +1,24 @@
+; XO-CHIP bitplanes, F000 NNNN and the register range instructions.
+; platform: xochip
+; quirks: xochip
+        LD V0, 2
+        LD V1, 2
+        PLANE 1
+        LD I, LONG sprite
+        DRW V0, V1, 4
+        LD V0, 4
+        PLANE 2
+        DRW V0, V1, 4
+        PLANE 3
+        LD V0, 10
+        DRW V0, V1, 4           ; both planes, 4 rows each
+        LD V2, 0x12
+        LD V3, 0x34
+        LD I, scratch
+        SAVE V2 - V3
+        LOAD V3 - V2            ; reversed range
+done:   JP done
+sprite: db 0xF0, 0x90, 0x90, 0xF0
+        db 0x0F, 0x09, 0x09, 0x0F
+scratch:
+        db 0, 0
@@ -0,0 +1,367 @@
+use crate::ffi::*;
+use chip8_core::audio::{AudioPattern, AudioSink};
+use chip8_core::chip8::{
+    Chip8, DEFAULT_INSTRUCTIONS_PER_SECOND, HIRES_SCREEN_HEIGHT, HIRES_SCREEN_WIDTH,
+};
+use chip8_core::display::{Framebuffer, Palette, VideoSink};
+use chip8_core::error::Chip8Error;
+use chip8_core::input::InputSource;
+use chip8_core::platform::Platform;
+use chip8_core::quirks::Quirks;
+use chip8_core::runner::Runner;
+use std::ffi::{c_uint, CStr};
+
+pub const SAMPLE_RATE: u32 = 44100;
+// The timers of every platform tick at 60 Hz, and a frame runs between two ticks.
+pub const FRAMES_PER_SECOND: u32 = 60;
+// The tone of the desktop frontend.
+const BUZZER_FREQUENCY: f32 = 240.0;
+const VOLUME: i16 = i16::MAX / 4;
+// Upper bound of the JSON save state of anything but the memory and the screen.
+const STATE_OVERHEAD: usize = 4096;
+
+/// The joypad buttons, with the key each one presses. The d-pad presses 2, 4, 6 and 8 and A
+/// presses 5, as most games use those keys to move and act.
+pub const JOYPAD_KEYS: [(c_uint, u8, &CStr); 16] = [
+    (RETRO_DEVICE_ID_JOYPAD_UP, 0x2, c"2 (Up)"),
+    (RETRO_DEVICE_ID_JOYPAD_DOWN, 0x8, c"8 (Down)"),
+    (RETRO_DEVICE_ID_JOYPAD_LEFT, 0x4, c"4 (Left)"),
+    (RETRO_DEVICE_ID_JOYPAD_RIGHT, 0x6, c"6 (Right)"),
+    (RETRO_DEVICE_ID_JOYPAD_A, 0x5, c"5"),
+    (RETRO_DEVICE_ID_JOYPAD_B, 0x0, c"0"),
+    (RETRO_DEVICE_ID_JOYPAD_Y, 0x1, c"1"),
+    (RETRO_DEVICE_ID_JOYPAD_X, 0x3, c"3"),
+    (RETRO_DEVICE_ID_JOYPAD_L, 0x7, c"7"),
+    (RETRO_DEVICE_ID_JOYPAD_R, 0x9, c"9"),
+    (RETRO_DEVICE_ID_JOYPAD_L2, 0xA, c"A"),
+    (RETRO_DEVICE_ID_JOYPAD_R2, 0xB, c"B"),
+    (RETRO_DEVICE_ID_JOYPAD_L3, 0xC, c"C"),
+    (RETRO_DEVICE_ID_JOYPAD_R3, 0xD, c"D"),
+    (RETRO_DEVICE_ID_JOYPAD_SELECT, 0xE, c"E"),
+    (RETRO_DEVICE_ID_JOYPAD_START, 0xF, c"F"),
+];
+
+/// The core options, as `(key, "Description; first value (default)|other values")`.
+pub const OPTIONS: [(&CStr, &CStr); 3] = [
+    (
+        c"chip8_platform",
+        c"Platform (restarts the game); chip8|schip|xochip",
+    ),
+    (
+        c"chip8_quirks",
+        c"Quirks (restarts the game); platform|default|vip|chip48|schip|xochip",
+    ),
+    (
+        c"chip8_speed",
+        c"Instructions per second; 480|600|900|1200|1800|3000|6000|12000|30000|60000|120|240|360",
+    ),
+];
+
+/// The settings of the core options.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Options {
+    pub platform: Platform,
+    /// The quirks to run with, or None for the quirks of the platform.
+    pub quirks: Option<Quirks>,
+    pub instructions_per_second: u32,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            platform: Platform::Chip8,
+            quirks: None,
+            instructions_per_second: DEFAULT_INSTRUCTIONS_PER_SECOND,
+        }
+    }
+}
+
+impl Options {
+    /// Applies the value of an option. Returns false if the key or the value is unknown.
+    pub fn set(&mut self, key: &str, value: &str) -> bool {
+        match key {
+            "chip8_platform" => Platform::from_name(value).map(|platform| self.platform = platform),
+            "chip8_quirks" if value == "platform" => {
+                self.quirks = None;
+                Some(())
+            }
+            "chip8_quirks" => Quirks::from_name(value).map(|quirks| self.quirks = Some(quirks)),
+            "chip8_speed" => value
+                .parse()
+                .ok()
+                .filter(|rate| *rate > 0)
+                .map(|rate| self.instructions_per_second = rate),
+            _ => None,
+        }
+        .is_some()
+    }
+
+    fn quirks(&self) -> Quirks {
+        self.quirks
+            .unwrap_or_else(|| self.platform.default_quirks())
+    }
+
+    // A machine for the platform, quirks and speed of the options.
+    fn machine(&self) -> Chip8 {
+        let mut chip8 = Chip8::with_platform(self.platform, self.quirks());
+        chip8.set_instructions_per_second(self.instructions_per_second);
+        chip8
+    }
+}
+
+/// A loaded game: the machine running it and the last frame of video and audio.
+pub struct Core {
+    runner: Runner,
+    rom: Vec<u8>,
+    options: Options,
+    video: Video,
+    audio: Audio,
+}
+
+impl Core {
+    /// Starts running `rom`. The random source isn't seeded from the clock, so that runs can be
+    /// replayed, e.g. by netplay.
+    pub fn load(rom: &[u8], options: Options) -> Result<Self, Chip8Error> {
+        let mut chip8 = options.machine();
+        chip8.load_file(rom)?;
+        // Starts in a buffer big enough for every platform, so that it's never reallocated.
+        chip8.adopt_memory_buffer(&mut Chip8::with_platform(
+            Platform::XoChip,
+            Quirks::default(),
+        ));
+        let mut core = Self {
+            runner: Runner::new(chip8),
+            rom: rom.to_vec(),
+            options,
+            video: Video {
+                palette: Palette::default(),
+                pixels: vec![0; HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT],
+            },
+            audio: Audio {
+                samples: Vec::new(),
+                phase: 0.0,
+            },
+        };
+        core.render();
+        Ok(core)
+    }
+
+    /// Restarts the game with the current options.
+    pub fn reset(&mut self) {
+        let mut chip8 = self.options.machine();
+        let error = chip8.load_file(&self.rom).err();
+        self.load_machine(chip8);
+        if let Some(error) = error {
+            self.runner.stop(error);
+        }
+        self.audio.phase = 0.0;
+        self.render();
+    }
+
+    /// Changes the options. The speed changes right away, while a new platform or new quirks
+    /// restart the game.
+    pub fn set_options(&mut self, options: Options) {
+        let restart =
+            options.platform != self.options.platform || options.quirks != self.options.quirks;
+        self.options = options;
+        self.runner
+            .chip8
+            .set_instructions_per_second(options.instructions_per_second);
+        if restart {
+            self.reset();
+        }
+    }
+
+    /// Runs a frame with the given keys pressed, producing its video and audio.
+    pub fn run_frame(&mut self, keys: [bool; 16]) {
+        self.runner
+            .run_frame(&mut self.video, &mut self.audio, &mut Keys(keys));
+    }
+
+    /// The frame as XRGB8888 pixels, along with its width and height.
+    pub fn video(&self) -> (&[u32], usize, usize) {
+        let (width, height) = self.runner.chip8.get_resolution();
+        (&self.video.pixels[..width * height], width, height)
+    }
+
+    /// The audio of the frame, as interleaved stereo samples.
+    pub fn audio(&self) -> &[i16] {
+        &self.audio.samples
+    }
+
+    /// The memory of the machine. It stays at the same address for the whole game, even across
+    /// resets and save states.
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        self.runner.chip8.get_memory_mut()
+    }
+
+    /// Size of the buffer save states are written to. Frontends expect it not to change while
+    /// a game runs, even when the platform option changes, so it's an upper bound of the JSON
+    /// save state of the platform with the most memory.
+    pub fn state_size(&self) -> usize {
+        // Up to "255," per byte of memory and "3," per pixel.
+        let memory = Platform::XoChip.memory_size() * 4;
+        let screen = HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT * 2;
+        memory + screen + STATE_OVERHEAD
+    }
+
+    /// Writes a save state into `buffer`, padded with spaces. False if it doesn't fit.
+    pub fn save_state(&self, buffer: &mut [u8]) -> bool {
+        let state = serde_json::to_vec(&self.runner.chip8)
+            .expect("Couldn't serialize the current state of the CPU.");
+        let Some((data, padding)) = buffer.split_at_mut_checked(state.len()) else {
+            return false;
+        };
+        data.copy_from_slice(&state);
+        padding.fill(b' ');
+        true
+    }
+
+    /// Restores a save state written by `save_state`, at the speed of the options. False if it
+    /// isn't a valid one.
+    pub fn load_state(&mut self, state: &[u8]) -> bool {
+        match serde_json::from_slice::<Chip8>(state) {
+            Ok(mut chip8) => {
+                chip8.set_instructions_per_second(self.options.instructions_per_second);
+                self.load_machine(chip8);
+                self.render();
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    // Swaps the machine for `chip8`, which keeps running in the memory buffer of the previous one.
+    fn load_machine(&mut self, mut chip8: Chip8) {
+        chip8.adopt_memory_buffer(&mut self.runner.chip8);
+        self.runner.load(chip8);
+    }
+
+    // Draws the machine as it is, e.g. right after loading a ROM or a save state.
+    fn render(&mut self) {
+        self.video.present(&self.runner.chip8.get_framebuffer());
+    }
+}
+
+// The joypad, already read from the frontend.
+struct Keys([bool; 16]);
+
+impl InputSource for Keys {
+    fn poll(&mut self, runner: &mut Runner) -> bool {
+        for (key, pressed) in self.0.iter().enumerate() {
+            runner.chip8.key_down(Some(key as u8), *pressed);
+        }
+        true
+    }
+}
+
+// The frame, as XRGB8888 pixels of the active resolution.
+struct Video {
+    palette: Palette,
+    pixels: Vec<u32>,
+}
+
+impl VideoSink for Video {
+    fn present(&mut self, frame: &Framebuffer) {
+        frame.paint_dirty_rows(&self.palette, &mut self.pixels, |r, g, b| {
+            [u32::from_be_bytes([0, r, g, b])]
+        });
+    }
+}
+
+// The samples of the frame, synthesized from the buzzer.
+struct Audio {
+    samples: Vec<i16>,
+    // Position of the buzzer in its wave, or in the XO-CHIP pattern, from 0 to 1.
+    phase: f32,
+}
+
+impl AudioSink for Audio {
+    fn set_buzzer(&mut self, on: bool, pattern: Option<AudioPattern>) {
+        let frames = (SAMPLE_RATE / FRAMES_PER_SECOND) as usize;
+        // XO-CHIP games may play a pattern instead of the square wave.
+        let step = match pattern {
+            Some(pattern) => pattern.playback_rate() / 128.0 / SAMPLE_RATE as f32,
+            None => BUZZER_FREQUENCY / SAMPLE_RATE as f32,
+        };
+        self.samples.clear();
+        for _ in 0..frames {
+            let high = match pattern {
+                Some(pattern) => pattern.sample((self.phase * 128.0) as usize),
+                None => self.phase < 0.5,
+            };
+            let sample = match (on, high) {
+                (false, _) => 0,
+                (true, true) => VOLUME,
+                (true, false) => -VOLUME,
+            };
+            self.samples.extend([sample, sample]);
+            self.phase = (self.phase + step) % 1.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // LD V0, 0x0A; LD ST, V0; LD V0, K; LD F, V0; DRW V1, V1, 5; JP 0x20A.
+    const ROM: [u8; 12] = [
+        0x60, 0x0A, 0xF0, 0x18, 0xF0, 0x0A, 0xF0, 0x29, 0xD1, 0x15, 0x12, 0x0A,
+    ];
+
+    #[test]
+    fn joypad_covers_the_whole_keypad() {
+        let mut keys: Vec<u8> = JOYPAD_KEYS.iter().map(|(_, key, _)| *key).collect();
+        keys.sort();
+        assert_eq!(keys, (0..16).collect::<Vec<u8>>());
+        let mut buttons: Vec<c_uint> = JOYPAD_KEYS.iter().map(|(id, _, _)| *id).collect();
+        buttons.sort();
+        assert_eq!(buttons, (0..16).collect::<Vec<c_uint>>());
+    }
+
+    #[test]
+    fn options_parse_their_values() {
+        let mut options = Options::default();
+        assert!(options.set("chip8_platform", "schip"));
+        assert!(options.set("chip8_quirks", "vip"));
+        assert!(options.set("chip8_speed", "1200"));
+        assert_eq!(options.platform, Platform::SuperChip);
+        assert_eq!(options.quirks(), Quirks::cosmac_vip());
+        assert_eq!(options.instructions_per_second, 1200);
+
+        assert!(options.set("chip8_quirks", "platform"));
+        assert_eq!(options.quirks(), Quirks::superchip());
+        assert!(!options.set("chip8_speed", "0"));
+        assert!(!options.set("chip8_volume", "1"));
+        assert_eq!(options.instructions_per_second, 1200);
+    }
+
+    #[test]
+    fn runs_frames_and_restores_save_states() {
+        let mut core = Core::load(&ROM, Options::default()).unwrap();
+        core.run_frame([false; 16]);
+        assert_eq!(core.audio().len(), 735 * 2);
+        assert!(core.audio().iter().any(|sample| *sample != 0));
+        let (pixels, width, height) = core.video();
+        assert_eq!((pixels.len(), width, height), (64 * 32, 64, 32));
+        assert!(pixels.iter().all(|pixel| *pixel == 0));
+
+        let mut state = vec![0; core.state_size()];
+        let mut options = Options::default();
+        options.set("chip8_platform", "xochip");
+        let xochip = Core::load(&ROM, options).unwrap();
+        assert_eq!(xochip.state_size(), state.len());
+        assert!(core.save_state(&mut state));
+
+        let mut keys = [false; 16];
+        keys[0x7] = true;
+        core.run_frame(keys);
+        let lit = |core: &Core| core.video().0.iter().filter(|pixel| **pixel != 0).count();
+        assert!(lit(&core) > 0);
+
+        assert!(core.load_state(&state));
+        assert_eq!(lit(&core), 0);
+        assert!(!core.load_state(b"{}"));
+    }
+}
@@ -0,0 +1,341 @@
+//! A libretro core, so RetroArch and other libretro frontends can run CHIP-8, SUPER-CHIP and
+//! XO-CHIP ROMs. The frontend calls the `retro_*` functions below; `Core` does the emulation.
+
+pub mod core;
+pub mod ffi;
+
+use crate::core::{Core, Options, FRAMES_PER_SECOND, JOYPAD_KEYS, OPTIONS, SAMPLE_RATE};
+use crate::ffi::*;
+use chip8_core::chip8::{HIRES_SCREEN_HEIGHT, HIRES_SCREEN_WIDTH, SCREEN_HEIGHT, SCREEN_WIDTH};
+use std::ffi::{c_char, c_uint, c_void, CStr};
+use std::ptr;
+use std::sync::{Mutex, MutexGuard};
+
+// The callbacks given by the frontend.
+#[derive(Copy, Clone)]
+struct Frontend {
+    environment: Option<RetroEnvironment>,
+    video_refresh: Option<RetroVideoRefresh>,
+    audio_sample_batch: Option<RetroAudioSampleBatch>,
+    input_poll: Option<RetroInputPoll>,
+    input_state: Option<RetroInputState>,
+}
+
+static FRONTEND: Mutex<Frontend> = Mutex::new(Frontend {
+    environment: None,
+    video_refresh: None,
+    audio_sample_batch: None,
+    input_poll: None,
+    input_state: None,
+});
+static CORE: Mutex<Option<Core>> = Mutex::new(None);
+
+fn frontend() -> Frontend {
+    *FRONTEND.lock().unwrap_or_else(|error| error.into_inner())
+}
+
+fn set_frontend(update: impl FnOnce(&mut Frontend)) {
+    update(&mut FRONTEND.lock().unwrap_or_else(|error| error.into_inner()));
+}
+
+fn core() -> MutexGuard<'static, Option<Core>> {
+    CORE.lock().unwrap_or_else(|error| error.into_inner())
+}
+
+fn environment(cmd: c_uint, data: *mut c_void) -> bool {
+    frontend()
+        .environment
+        .is_some_and(|environment| environment(cmd, data))
+}
+
+// Reads the core options set in the frontend. Options it doesn't know keep their defaults.
+fn read_options() -> Options {
+    let mut options = Options::default();
+    for (key, _) in OPTIONS {
+        let mut variable = RetroVariable {
+            key: key.as_ptr(),
+            value: ptr::null(),
+        };
+        if !environment(
+            RETRO_ENVIRONMENT_GET_VARIABLE,
+            &mut variable as *mut _ as *mut c_void,
+        ) || variable.value.is_null()
+        {
+            continue;
+        }
+        // SAFETY: the frontend returns a NUL-terminated string that lives until the next call.
+        let value = unsafe { CStr::from_ptr(variable.value) };
+        if let (Ok(key), Ok(value)) = (key.to_str(), value.to_str()) {
+            options.set(key, value);
+        }
+    }
+    options
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_environment(callback: RetroEnvironment) {
+    set_frontend(|frontend| frontend.environment = Some(callback));
+    let mut variables: Vec<RetroVariable> = OPTIONS
+        .iter()
+        .map(|(key, value)| RetroVariable {
+            key: key.as_ptr(),
+            value: value.as_ptr(),
+        })
+        .collect();
+    variables.push(RetroVariable {
+        key: ptr::null(),
+        value: ptr::null(),
+    });
+    callback(
+        RETRO_ENVIRONMENT_SET_VARIABLES,
+        variables.as_mut_ptr() as *mut c_void,
+    );
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(callback: RetroVideoRefresh) {
+    set_frontend(|frontend| frontend.video_refresh = Some(callback));
+}
+
+/// The core only outputs audio in batches.
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_callback: RetroAudioSample) {}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(callback: RetroAudioSampleBatch) {
+    set_frontend(|frontend| frontend.audio_sample_batch = Some(callback));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(callback: RetroInputPoll) {
+    set_frontend(|frontend| frontend.input_poll = Some(callback));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(callback: RetroInputState) {
+    set_frontend(|frontend| frontend.input_state = Some(callback));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {}
+
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    *core() = None;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> c_uint {
+    RETRO_API_VERSION
+}
+
+/// # Safety
+///
+/// `info` must point to a writable `retro_system_info`.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut RetroSystemInfo) {
+    info.write(RetroSystemInfo {
+        library_name: c"RCHIP-8".as_ptr(),
+        library_version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast(),
+        valid_extensions: c"ch8|c8|sc8|xo8".as_ptr(),
+        need_fullpath: false,
+        block_extract: false,
+    });
+}
+
+/// # Safety
+///
+/// `info` must point to a writable `retro_system_av_info`.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut RetroSystemAvInfo) {
+    info.write(RetroSystemAvInfo {
+        geometry: RetroGameGeometry {
+            base_width: SCREEN_WIDTH as c_uint,
+            base_height: SCREEN_HEIGHT as c_uint,
+            max_width: HIRES_SCREEN_WIDTH as c_uint,
+            max_height: HIRES_SCREEN_HEIGHT as c_uint,
+            aspect_ratio: 2.0,
+        },
+        timing: RetroSystemTiming {
+            fps: FRAMES_PER_SECOND as f64,
+            sample_rate: SAMPLE_RATE as f64,
+        },
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(_port: c_uint, _device: c_uint) {}
+
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    if let Some(core) = core().as_mut() {
+        core.reset();
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_run() {
+    let frontend = frontend();
+    let mut core = core();
+    let Some(core) = core.as_mut() else {
+        return;
+    };
+
+    let mut updated = false;
+    if environment(
+        RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE,
+        &mut updated as *mut bool as *mut c_void,
+    ) && updated
+    {
+        core.set_options(read_options());
+    }
+
+    if let Some(input_poll) = frontend.input_poll {
+        input_poll();
+    }
+    let mut keys = [false; 16];
+    if let Some(input_state) = frontend.input_state {
+        for (id, key, _) in JOYPAD_KEYS {
+            keys[key as usize] = input_state(0, RETRO_DEVICE_JOYPAD, 0, id) != 0;
+        }
+    }
+    core.run_frame(keys);
+
+    if let Some(video_refresh) = frontend.video_refresh {
+        let (pixels, width, height) = core.video();
+        video_refresh(
+            pixels.as_ptr() as *const c_void,
+            width as c_uint,
+            height as c_uint,
+            width * 4,
+        );
+    }
+    if let Some(audio_sample_batch) = frontend.audio_sample_batch {
+        let audio = core.audio();
+        audio_sample_batch(audio.as_ptr(), audio.len() / 2);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    core().as_ref().map_or(0, Core::state_size)
+}
+
+/// # Safety
+///
+/// `data` must point to `size` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    if data.is_null() {
+        return false;
+    }
+    let buffer = std::slice::from_raw_parts_mut(data as *mut u8, size);
+    core().as_ref().is_some_and(|core| core.save_state(buffer))
+}
+
+/// # Safety
+///
+/// `data` must point to `size` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    if data.is_null() {
+        return false;
+    }
+    let state = std::slice::from_raw_parts(data as *const u8, size);
+    core().as_mut().is_some_and(|core| core.load_state(state))
+}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_set(_index: c_uint, _enabled: bool, _code: *const c_char) {}
+
+/// # Safety
+///
+/// `game` must be null or point to a valid `retro_game_info` whose `data` points to `size`
+/// readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const RetroGameInfo) -> bool {
+    let Some(game) = game.as_ref() else {
+        return false;
+    };
+    if game.data.is_null() {
+        return false;
+    }
+    let mut pixel_format = RETRO_PIXEL_FORMAT_XRGB8888;
+    if !environment(
+        RETRO_ENVIRONMENT_SET_PIXEL_FORMAT,
+        &mut pixel_format as *mut c_uint as *mut c_void,
+    ) {
+        return false;
+    }
+    let mut descriptors: Vec<RetroInputDescriptor> = JOYPAD_KEYS
+        .iter()
+        .map(|(id, _, description)| RetroInputDescriptor {
+            port: 0,
+            device: RETRO_DEVICE_JOYPAD,
+            index: 0,
+            id: *id,
+            description: description.as_ptr(),
+        })
+        .collect();
+    descriptors.push(RetroInputDescriptor {
+        port: 0,
+        device: 0,
+        index: 0,
+        id: 0,
+        description: ptr::null(),
+    });
+    environment(
+        RETRO_ENVIRONMENT_SET_INPUT_DESCRIPTORS,
+        descriptors.as_mut_ptr() as *mut c_void,
+    );
+
+    let rom = std::slice::from_raw_parts(game.data as *const u8, game.size);
+    match Core::load(rom, read_options()) {
+        Ok(loaded) => {
+            *core() = Some(loaded);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(
+    _game_type: c_uint,
+    _info: *const RetroGameInfo,
+    _num_info: usize,
+) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    *core() = None;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> c_uint {
+    RETRO_REGION_NTSC
+}
+
+/// The memory of the machine, for cheats and achievements.
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(id: c_uint) -> *mut c_void {
+    match core().as_mut() {
+        Some(core) if id == RETRO_MEMORY_SYSTEM_RAM => {
+            core.memory_mut().as_mut_ptr() as *mut c_void
+        }
+        _ => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(id: c_uint) -> usize {
+    match core().as_mut() {
+        Some(core) if id == RETRO_MEMORY_SYSTEM_RAM => core.memory_mut().len(),
+        _ => 0,
+    }
+}
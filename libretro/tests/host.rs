@@ -0,0 +1,332 @@
+//! A tiny libretro frontend: loads the core as a shared library, like RetroArch would, and
+//! checks what it does through the libretro API only.
+
+use libloading::{Library, Symbol};
+use std::cell::RefCell;
+use std::ffi::{c_char, c_uint, c_void, CStr};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+const RETRO_DEVICE_JOYPAD: c_uint = 1;
+const RETRO_DEVICE_ID_JOYPAD_A: c_uint = 8;
+const RETRO_ENVIRONMENT_SET_PIXEL_FORMAT: c_uint = 10;
+const RETRO_ENVIRONMENT_GET_VARIABLE: c_uint = 15;
+const RETRO_ENVIRONMENT_SET_VARIABLES: c_uint = 16;
+const RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE: c_uint = 17;
+const RETRO_PIXEL_FORMAT_XRGB8888: c_uint = 1;
+const RETRO_MEMORY_SYSTEM_RAM: c_uint = 2;
+
+// LD V0, 0x0A; LD ST, V0; LD V0, K; LD F, V0; DRW V1, V1, 5; JP 0x20A: beeps, then waits for a
+// key and draws its digit at 0,0.
+const ROM: [u8; 12] = [
+    0x60, 0x0A, 0xF0, 0x18, 0xF0, 0x0A, 0xF0, 0x29, 0xD1, 0x15, 0x12, 0x0A,
+];
+
+#[repr(C)]
+struct RetroGameInfo {
+    path: *const c_char,
+    data: *const c_void,
+    size: usize,
+    meta: *const c_char,
+}
+
+#[repr(C)]
+struct RetroVariable {
+    key: *const c_char,
+    value: *const c_char,
+}
+
+// What the core sent to the frontend, and the state of the frontend's options and joypad.
+#[derive(Default)]
+struct Host {
+    pixel_format: Option<c_uint>,
+    options: Vec<(String, String)>,
+    speed: Option<&'static CStr>,
+    options_updated: bool,
+    pressed: Vec<c_uint>,
+    frame: Option<(Vec<u32>, usize, usize)>,
+    audio_frames: usize,
+    audio_peak: i16,
+}
+
+thread_local! {
+    static HOST: RefCell<Host> = RefCell::new(Host::default());
+}
+
+// The core is a single global instance, so the tests take turns.
+static CORE_LOCK: Mutex<()> = Mutex::new(());
+
+extern "C" fn environment(cmd: c_uint, data: *mut c_void) -> bool {
+    HOST.with_borrow_mut(|host| unsafe {
+        match cmd {
+            RETRO_ENVIRONMENT_SET_PIXEL_FORMAT => {
+                host.pixel_format = Some(*(data as *const c_uint));
+                true
+            }
+            RETRO_ENVIRONMENT_SET_VARIABLES => {
+                let mut variable = data as *const RetroVariable;
+                while !(*variable).key.is_null() {
+                    let key = CStr::from_ptr((*variable).key).to_string_lossy();
+                    let value = CStr::from_ptr((*variable).value).to_string_lossy();
+                    host.options.push((key.into_owned(), value.into_owned()));
+                    variable = variable.add(1);
+                }
+                true
+            }
+            RETRO_ENVIRONMENT_GET_VARIABLE => {
+                let variable = &mut *(data as *mut RetroVariable);
+                match (CStr::from_ptr(variable.key).to_bytes(), host.speed) {
+                    (b"chip8_speed", Some(speed)) => {
+                        variable.value = speed.as_ptr();
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE => {
+                *(data as *mut bool) = std::mem::take(&mut host.options_updated);
+                true
+            }
+            _ => false,
+        }
+    })
+}
+
+extern "C" fn video_refresh(data: *const c_void, width: c_uint, height: c_uint, pitch: usize) {
+    let (width, height) = (width as usize, height as usize);
+    assert_eq!(pitch, width * 4);
+    let pixels = unsafe { std::slice::from_raw_parts(data as *const u32, width * height) };
+    HOST.with_borrow_mut(|host| host.frame = Some((pixels.to_vec(), width, height)));
+}
+
+extern "C" fn audio_sample(_left: i16, _right: i16) {
+    panic!("the core should only use audio batches");
+}
+
+extern "C" fn audio_sample_batch(data: *const i16, frames: usize) -> usize {
+    let samples = unsafe { std::slice::from_raw_parts(data, frames * 2) };
+    HOST.with_borrow_mut(|host| {
+        host.audio_frames += frames;
+        host.audio_peak = host
+            .audio_peak
+            .max(samples.iter().copied().max().unwrap_or(0));
+    });
+    frames
+}
+
+extern "C" fn input_poll() {}
+
+extern "C" fn input_state(port: c_uint, device: c_uint, _index: c_uint, id: c_uint) -> i16 {
+    let pressed = port == 0
+        && device == RETRO_DEVICE_JOYPAD
+        && HOST.with_borrow(|host| host.pressed.contains(&id));
+    pressed as i16
+}
+
+struct Core {
+    library: Library,
+}
+
+impl Core {
+    // Loads the core cargo built for the tests, next to the test executable in
+    // `target/<profile>/deps`.
+    fn load() -> Self {
+        let mut path: PathBuf = std::env::current_exe().unwrap();
+        path.set_file_name(libloading::library_filename("chip8_libretro"));
+        let library = unsafe { Library::new(&path) }
+            .unwrap_or_else(|error| panic!("Couldn't load {}: {}", path.display(), error));
+        HOST.set(Host::default());
+        let core = Self { library };
+        unsafe {
+            core.function::<extern "C" fn(extern "C" fn(c_uint, *mut c_void) -> bool)>(
+                b"retro_set_environment",
+            )(environment);
+            core.function::<extern "C" fn(extern "C" fn(*const c_void, c_uint, c_uint, usize))>(
+                b"retro_set_video_refresh",
+            )(video_refresh);
+            core.function::<extern "C" fn(extern "C" fn(i16, i16))>(b"retro_set_audio_sample")(
+                audio_sample,
+            );
+            core.function::<extern "C" fn(extern "C" fn(*const i16, usize) -> usize)>(
+                b"retro_set_audio_sample_batch",
+            )(audio_sample_batch);
+            core.function::<extern "C" fn(extern "C" fn())>(b"retro_set_input_poll")(input_poll);
+            core.function::<extern "C" fn(extern "C" fn(c_uint, c_uint, c_uint, c_uint) -> i16)>(
+                b"retro_set_input_state",
+            )(input_state);
+            core.function::<extern "C" fn()>(b"retro_init")();
+        }
+        core
+    }
+
+    unsafe fn function<T>(&self, name: &[u8]) -> Symbol<'_, T> {
+        self.library.get(name).unwrap()
+    }
+
+    fn load_game(&self, rom: &[u8]) -> bool {
+        let game = RetroGameInfo {
+            path: std::ptr::null(),
+            data: rom.as_ptr() as *const c_void,
+            size: rom.len(),
+            meta: std::ptr::null(),
+        };
+        unsafe {
+            self.function::<extern "C" fn(*const RetroGameInfo) -> bool>(b"retro_load_game")(&game)
+        }
+    }
+
+    fn run(&self) {
+        unsafe { self.function::<extern "C" fn()>(b"retro_run")() }
+    }
+
+    fn serialize(&self) -> Option<Vec<u8>> {
+        unsafe {
+            let size = self.function::<extern "C" fn() -> usize>(b"retro_serialize_size")();
+            let mut state = vec![0; size];
+            let saved = self
+                .function::<extern "C" fn(*mut c_void, usize) -> bool>(b"retro_serialize")(
+                state.as_mut_ptr() as *mut c_void,
+                size,
+            );
+            saved.then_some(state)
+        }
+    }
+
+    fn unserialize(&self, state: &[u8]) -> bool {
+        unsafe {
+            self.function::<extern "C" fn(*const c_void, usize) -> bool>(b"retro_unserialize")(
+                state.as_ptr() as *const c_void,
+                state.len(),
+            )
+        }
+    }
+
+    fn lit_pixels() -> usize {
+        HOST.with_borrow(|host| {
+            let (pixels, _, _) = host.frame.as_ref().expect("no frame was drawn");
+            pixels
+                .iter()
+                .filter(|pixel| **pixel & 0xFF_FFFF != 0)
+                .count()
+        })
+    }
+}
+
+impl Drop for Core {
+    fn drop(&mut self) {
+        unsafe {
+            self.function::<extern "C" fn()>(b"retro_unload_game")();
+            self.function::<extern "C" fn()>(b"retro_deinit")();
+        }
+    }
+}
+
+#[test]
+fn runs_a_rom_with_video_audio_and_input() {
+    let _lock = CORE_LOCK.lock().unwrap_or_else(|error| error.into_inner());
+    let core = Core::load();
+    assert!(core.load_game(&ROM));
+    assert_eq!(
+        HOST.with_borrow(|host| host.pixel_format),
+        Some(RETRO_PIXEL_FORMAT_XRGB8888)
+    );
+
+    core.run();
+    HOST.with_borrow(|host| {
+        let (_, width, height) = host.frame.as_ref().unwrap();
+        assert_eq!((*width, *height), (64, 32));
+        assert_eq!(host.audio_frames, 44100 / 60);
+        assert!(host.audio_peak > 0, "the buzzer should sound");
+    });
+    assert_eq!(Core::lit_pixels(), 0);
+
+    // A presses 5, whose digit gets drawn.
+    HOST.with_borrow_mut(|host| host.pressed.push(RETRO_DEVICE_ID_JOYPAD_A));
+    core.run();
+    assert!(Core::lit_pixels() > 0);
+}
+
+#[test]
+fn save_states_round_trip() {
+    let _lock = CORE_LOCK.lock().unwrap_or_else(|error| error.into_inner());
+    let core = Core::load();
+    assert!(core.load_game(&ROM));
+    core.run();
+    let state = core.serialize().expect("the state should fit");
+
+    HOST.with_borrow_mut(|host| host.pressed.push(RETRO_DEVICE_ID_JOYPAD_A));
+    core.run();
+    assert!(Core::lit_pixels() > 0);
+
+    assert!(core.unserialize(&state));
+    HOST.with_borrow_mut(|host| host.pressed.clear());
+    core.run();
+    assert_eq!(Core::lit_pixels(), 0, "the key press should be undone");
+    assert!(!core.unserialize(b"not a save state"));
+}
+
+#[test]
+fn memory_stays_valid_across_resets_and_save_states() {
+    let _lock = CORE_LOCK.lock().unwrap_or_else(|error| error.into_inner());
+    let core = Core::load();
+    assert!(core.load_game(&ROM));
+    let memory = unsafe {
+        let data = core.function::<extern "C" fn(c_uint) -> *mut c_void>(b"retro_get_memory_data")(
+            RETRO_MEMORY_SYSTEM_RAM,
+        ) as *mut u8;
+        let size = core.function::<extern "C" fn(c_uint) -> usize>(b"retro_get_memory_size")(
+            RETRO_MEMORY_SYSTEM_RAM,
+        );
+        std::slice::from_raw_parts_mut(data, size)
+    };
+    assert_eq!(memory[0x200..0x200 + ROM.len()], ROM);
+    core.run();
+    let state = core.serialize().expect("the state should fit");
+
+    // Each poke is undone by loading the machine again, which only shows if the pointer is live.
+    memory[0x200] = 0;
+    unsafe { core.function::<extern "C" fn()>(b"retro_reset")() };
+    assert_eq!(memory[0x200], ROM[0]);
+    memory[0x200] = 0;
+    assert!(core.unserialize(&state));
+    assert_eq!(memory[0x200], ROM[0]);
+}
+
+#[test]
+fn options_change_the_speed() {
+    let _lock = CORE_LOCK.lock().unwrap_or_else(|error| error.into_inner());
+    let core = Core::load();
+    let options = HOST.with_borrow(|host| host.options.clone());
+    let keys: Vec<&str> = options.iter().map(|(key, _)| key.as_str()).collect();
+    assert_eq!(keys, ["chip8_platform", "chip8_quirks", "chip8_speed"]);
+
+    // At 120 instructions per second (2 per frame), the digit is drawn a frame later than at the default speed.
+    HOST.with_borrow_mut(|host| {
+        host.speed = Some(c"120");
+        host.pressed.push(RETRO_DEVICE_ID_JOYPAD_A);
+    });
+    assert!(core.load_game(&ROM));
+    core.run();
+    core.run();
+    assert_eq!(Core::lit_pixels(), 0);
+    core.run();
+    assert!(Core::lit_pixels() > 0);
+
+    // Back to the default speed while the game runs, which draws the digit on the second frame.
+    assert!(core.load_game(&ROM));
+    core.run();
+    HOST.with_borrow_mut(|host| {
+        host.speed = Some(c"480");
+        host.options_updated = true;
+    });
+    core.run();
+    assert!(Core::lit_pixels() > 0);
+}
+
+#[test]
+fn rejects_roms_that_dont_fit() {
+    let _lock = CORE_LOCK.lock().unwrap_or_else(|error| error.into_inner());
+    let core = Core::load();
+    assert!(!core.load_game(&[0; 8192]));
+    assert_eq!(core.serialize(), None);
+}
@@ -0,0 +1,26 @@
+//! Runs `chip8_core` the way a `no_std` host would: the ROM comes from the caller instead of the
+//! file system and so does the seed of the random source. Only `alloc` is needed, so the host
+//! must provide a global allocator.
+#![no_std]
+
+use chip8_core::chip8::Chip8;
+use chip8_core::error::Chip8Error;
+use chip8_core::platform::Platform;
+
+/// Loads `rom` on a new machine, seeding CXNN with `seed`, e.g. from a hardware RNG.
+pub fn boot(rom: &[u8], platform: Platform, seed: u64) -> Result<Chip8, Chip8Error> {
+    let mut chip8 = Chip8::with_platform(platform, platform.default_quirks());
+    chip8.seed_random(seed);
+    chip8.load_file(rom)?;
+    Ok(chip8)
+}
+
+/// Runs the instructions of a frame and decrements the timers, as the host's 60 Hz timer
+/// interrupt would. Returns whether the buzzer should sound.
+pub fn run_frame(chip8: &mut Chip8) -> Result<bool, Chip8Error> {
+    let report = chip8.run_frame();
+    match report.error {
+        Some(error) => Err(error),
+        None => Ok(report.buzzer),
+    }
+}
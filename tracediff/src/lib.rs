@@ -0,0 +1,143 @@
+use chip8_core::trace::TraceRecord;
+use std::fmt::Write;
+
+/// The first instruction at which two traces disagree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// Index of the diverging record in both traces.
+    pub index: usize,
+    /// The fields that differ, or empty when one of the traces ends before the other one.
+    pub fields: Vec<&'static str>,
+}
+
+/// Parses a trace, skipping blank lines. Errors name the line that couldn't be parsed.
+pub fn parse_trace(text: &str) -> Result<Vec<TraceRecord>, String> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            line.parse()
+                .map_err(|error| format!("line {}: {}", index + 1, error))
+        })
+        .collect()
+}
+
+/// Compares both traces record by record, ignoring their cycle numbers.
+pub fn first_divergence(left: &[TraceRecord], right: &[TraceRecord]) -> Option<Divergence> {
+    let mismatch = left
+        .iter()
+        .zip(right)
+        .map(|(left, right)| left.differences(right))
+        .position(|fields| !fields.is_empty());
+    match mismatch {
+        Some(index) => Some(Divergence {
+            index,
+            fields: left[index].differences(&right[index]),
+        }),
+        None if left.len() != right.len() => Some(Divergence {
+            index: left.len().min(right.len()),
+            fields: Vec::new(),
+        }),
+        None => None,
+    }
+}
+
+/// Describes the divergence, followed by up to `context` records both traces agree on before it
+/// and up to `context` records of each trace after it. Lines of the left trace start with `-`,
+/// lines of the right one with `+`.
+pub fn report(
+    left: &[TraceRecord],
+    right: &[TraceRecord],
+    divergence: &Divergence,
+    context: usize,
+) -> String {
+    let index = divergence.index;
+    let mut output = String::new();
+    let summary = if !divergence.fields.is_empty() {
+        format!("{} differ", divergence.fields.join(", "))
+    } else if left.len() < right.len() {
+        String::from("the left trace ends first")
+    } else {
+        String::from("the right trace ends first")
+    };
+    writeln!(
+        output,
+        "Traces diverge at record {}: {}",
+        index + 1,
+        summary
+    )
+    .unwrap();
+
+    for record in &left[index.saturating_sub(context)..index] {
+        writeln!(output, "  {}", record).unwrap();
+    }
+    for (sign, trace) in [('-', left), ('+', right)] {
+        let end = (index + context + 1).min(trace.len());
+        for record in trace.get(index..end).unwrap_or_default() {
+            writeln!(output, "{} {}", sign, record).unwrap();
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8_core::trace::Registers;
+
+    fn trace(pcs: &[u16]) -> Vec<TraceRecord> {
+        pcs.iter()
+            .enumerate()
+            .map(|(cycle, pc)| TraceRecord {
+                cycle: cycle as u64,
+                pc: *pc,
+                opcode: 0x1200,
+                registers: Registers::default(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_the_first_differing_record() {
+        let left = trace(&[0x200, 0x202, 0x204, 0x206]);
+        let mut right = trace(&[0x200, 0x202, 0x204, 0x206]);
+        assert_eq!(first_divergence(&left, &right), None);
+
+        right[2].registers.v[3] = 1;
+        right[3].pc = 0x300;
+        let divergence = first_divergence(&left, &right).unwrap();
+        assert_eq!(
+            divergence,
+            Divergence {
+                index: 2,
+                fields: vec!["V3"],
+            }
+        );
+
+        let report = report(&left, &right, &divergence, 1);
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "Traces diverge at record 3: V3 differ");
+        assert!(lines[1].starts_with("  00000001 PC:0202"));
+        assert!(lines[2].starts_with("- 00000002 PC:0204"));
+        assert!(lines[3].starts_with("- 00000003 PC:0206"));
+        assert!(lines[4].starts_with("+ 00000002 PC:0204"));
+        assert!(lines[5].starts_with("+ 00000003 PC:0300"));
+    }
+
+    #[test]
+    fn a_shorter_trace_diverges_where_it_ends() {
+        let left = trace(&[0x200, 0x202]);
+        let right = trace(&[0x200, 0x202, 0x204]);
+        let divergence = first_divergence(&left, &right).unwrap();
+        assert_eq!(divergence.index, 2);
+        assert!(report(&left, &right, &divergence, 3)
+            .starts_with("Traces diverge at record 3: the left trace ends first"));
+
+        let text: String = left
+            .iter()
+            .map(|record| format!("{}\n\n", record))
+            .collect();
+        assert_eq!(parse_trace(&text), Ok(left));
+        assert!(parse_trace("garbage").unwrap_err().starts_with("line 1:"));
+    }
+}
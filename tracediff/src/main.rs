@@ -0,0 +1,46 @@
+use tracediff::{first_divergence, parse_trace, report};
+
+const USAGE: &str = "Usage: chip8-tracediff [left_trace] [right_trace] [--context lines]";
+const DEFAULT_CONTEXT: usize = 5;
+
+// Like diff, exits with 0 when the traces match, 1 when they diverge and 2 on errors.
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 3 {
+        println!("{}", USAGE);
+        std::process::exit(2);
+    }
+
+    let context = match (args.get(3).map(String::as_str), args.get(4)) {
+        (None, _) => DEFAULT_CONTEXT,
+        (Some("--context"), Some(lines)) => lines.parse().unwrap_or_else(|_| {
+            eprintln!("Invalid number of lines: {}", lines);
+            std::process::exit(2);
+        }),
+        _ => {
+            println!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let left = read_trace(&args[1]);
+    let right = read_trace(&args[2]);
+    match first_divergence(&left, &right) {
+        Some(divergence) => {
+            print!("{}", report(&left, &right, &divergence, context));
+            std::process::exit(1);
+        }
+        None => println!("The traces match ({} records).", left.len()),
+    }
+}
+
+fn read_trace(path: &str) -> Vec<chip8_core::trace::TraceRecord> {
+    let text = std::fs::read_to_string(path).unwrap_or_else(|error| {
+        eprintln!("Couldn't read {}: {}", path, error);
+        std::process::exit(2);
+    });
+    parse_trace(&text).unwrap_or_else(|error| {
+        eprintln!("{}: {}", path, error);
+        std::process::exit(2);
+    })
+}
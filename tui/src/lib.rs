@@ -0,0 +1,150 @@
+/// Frames a key stays pressed after the terminal reports it, when the terminal can't report key
+/// releases. Long enough to bridge the delay before the terminal starts repeating a held key.
+pub const INITIAL_HOLD_FRAMES: u8 = 30;
+/// Frames a key stays pressed after each repeat of a held key. Repeats come much faster than
+/// the first one, so a released key is noticed sooner.
+pub const REPEAT_HOLD_FRAMES: u8 = 6;
+
+/// Maps a key to the keypad, with the same layout as the desktop frontend:
+///
+/// ```text
+/// 1 2 3 C     1 2 3 4
+/// 4 5 6 D     q w e r
+/// 7 8 9 E ->  a s d f
+/// A 0 B F     z x c v
+/// ```
+pub fn map_key(key: char) -> Option<u8> {
+    match key.to_ascii_lowercase() {
+        '1' => Some(0x1),
+        '2' => Some(0x2),
+        '3' => Some(0x3),
+        '4' => Some(0xC),
+        'q' => Some(0x4),
+        'w' => Some(0x5),
+        'e' => Some(0x6),
+        'r' => Some(0xD),
+        'a' => Some(0x7),
+        's' => Some(0x8),
+        'd' => Some(0x9),
+        'f' => Some(0xE),
+        'z' => Some(0xA),
+        'x' => Some(0x0),
+        'c' => Some(0xB),
+        'v' => Some(0xF),
+        _ => None,
+    }
+}
+
+/// Emulates key releases for terminals that only report presses: a key is released once it
+/// hasn't been reported for a few frames. A held key keeps being reported by the terminal's key
+/// repeat, which keeps it pressed.
+#[derive(Clone, Debug, Default)]
+pub struct KeyHold {
+    frames_left: [u8; 16],
+}
+
+impl KeyHold {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a press (or a repeat) of `key`.
+    pub fn press(&mut self, key: u8) {
+        let frames_left = &mut self.frames_left[(key & 0xF) as usize];
+        *frames_left = if *frames_left == 0 {
+            INITIAL_HOLD_FRAMES
+        } else {
+            (*frames_left).max(REPEAT_HOLD_FRAMES)
+        };
+    }
+
+    /// Advances a frame and returns the keys that should be released now.
+    pub fn frame(&mut self) -> Vec<u8> {
+        let mut released = Vec::new();
+        for (key, frames_left) in self.frames_left.iter_mut().enumerate() {
+            if *frames_left > 0 {
+                *frames_left -= 1;
+                if *frames_left == 0 {
+                    released.push(key as u8);
+                }
+            }
+        }
+        released
+    }
+}
+
+/// Rows of `width` pixels as lines of half-block characters: each character holds two pixels,
+/// one above the other, so the image keeps its proportions in terminals whose cells are twice
+/// as tall as they are wide. Pixels of any plane are drawn lit.
+pub fn half_blocks(pixels: &[u8], width: usize) -> Vec<String> {
+    pixels
+        .chunks(width * 2)
+        .map(|rows| {
+            let (top, bottom) = rows.split_at(width);
+            top.iter()
+                .zip(bottom)
+                .map(|(top, bottom)| match (*top != 0, *bottom != 0) {
+                    (false, false) => ' ',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (true, true) => '█',
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8_core::chip8::Chip8;
+
+    #[test]
+    fn draws_two_pixels_per_character() {
+        let mut chip8 = Chip8::new();
+        // LD V0, 0x01; LD F, V0; DRW V1, V1, 5: the "1" glyph (0x20, 0x60, 0x20, 0x20, 0x70) at 0,0.
+        chip8
+            .load_file(&[0x60, 0x01, 0xF0, 0x29, 0xD1, 0x15])
+            .unwrap();
+        for _ in 0..3 {
+            chip8.tick().unwrap();
+        }
+        let (width, height) = chip8.get_resolution();
+        let lines = half_blocks(&chip8.get_display()[..width * height], width);
+        assert_eq!(lines.len(), 16);
+        assert!(lines.iter().all(|line| line.chars().count() == 64));
+        assert_eq!(lines[0].trim_end(), " ▄█");
+        assert_eq!(lines[1].trim_end(), "  █");
+        assert_eq!(lines[2].trim_end(), " ▀▀▀");
+    }
+
+    #[test]
+    fn held_keys_are_released_after_the_terminal_stops_repeating_them() {
+        let mut hold = KeyHold::new();
+        hold.press(0x5);
+        for _ in 1..INITIAL_HOLD_FRAMES {
+            assert!(hold.frame().is_empty());
+        }
+        // A repeat keeps the key pressed for a few more frames.
+        hold.press(0x5);
+        hold.press(0x5);
+        for _ in 1..REPEAT_HOLD_FRAMES {
+            assert!(hold.frame().is_empty());
+        }
+        assert_eq!(hold.frame(), vec![0x5]);
+        assert!(hold.frame().is_empty());
+    }
+
+    #[test]
+    fn maps_the_keypad_layout() {
+        let layout = "1234qwerasdfzxcv";
+        let keypad = [
+            0x1, 0x2, 0x3, 0xC, 0x4, 0x5, 0x6, 0xD, 0x7, 0x8, 0x9, 0xE, 0xA, 0x0, 0xB, 0xF,
+        ];
+        for (key, expected) in layout.chars().zip(keypad) {
+            assert_eq!(map_key(key), Some(expected));
+            assert_eq!(map_key(key.to_ascii_uppercase()), Some(expected));
+        }
+        assert_eq!(map_key('b'), None);
+    }
+}
@@ -0,0 +1,266 @@
+use chip8_core::audio::{AudioPattern, AudioSink};
+use chip8_core::chip8::{Chip8, DEFAULT_INSTRUCTIONS_PER_SECOND};
+use chip8_core::display::{Framebuffer, VideoSink};
+use chip8_core::error::Chip8Error;
+use chip8_core::input::InputSource;
+use chip8_core::platform::Platform;
+use chip8_core::quirks::Quirks;
+use chip8_core::random::SplitMix64;
+use chip8_core::runner::Runner;
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{
+    self, Event, KeyCode, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::style::Print;
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use std::io::{self, Write};
+use std::process::exit;
+use std::time::Duration;
+use tui::{half_blocks, map_key, KeyHold};
+
+const USAGE: &str = "Usage: chip8-tui [path_to_rom] [--platform chip8|schip|xochip] \
+[--quirks default|vip|chip48|schip|xochip] [--ips number] [--seed number]";
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 {
+        println!("{}", USAGE);
+        exit(2);
+    }
+
+    let mut platform = Platform::Chip8;
+    let mut quirks = None;
+    let mut instructions_per_second = DEFAULT_INSTRUCTIONS_PER_SECOND;
+    let mut seed = None;
+    let mut arguments = args[2..].iter();
+    while let Some(option) = arguments.next() {
+        let value = arguments.next();
+        match (option.as_str(), value) {
+            ("--platform", Some(name)) => {
+                platform = parse(Platform::from_name(name), "platform", name)
+            }
+            ("--quirks", Some(name)) => {
+                quirks = Some(parse(Quirks::from_name(name), "quirk profile", name))
+            }
+            ("--ips", Some(number)) => {
+                instructions_per_second =
+                    parse(number.parse().ok(), "instructions per second", number)
+            }
+            ("--seed", Some(number)) => seed = Some(parse(number.parse().ok(), "seed", number)),
+            _ => {
+                println!("{}", USAGE);
+                exit(2);
+            }
+        }
+    }
+
+    let rom = std::fs::read(&args[1]).unwrap_or_else(|error| {
+        eprintln!("Couldn't read {}: {}", args[1], error);
+        exit(2);
+    });
+    let quirks = quirks.unwrap_or_else(|| platform.default_quirks());
+    let mut chip8 = Chip8::with_platform(platform, quirks);
+    chip8.set_instructions_per_second(instructions_per_second);
+    // Games are only reproducible when asked to, otherwise every run is different.
+    let rng = seed.map_or_else(SplitMix64::from_entropy, SplitMix64::new);
+    chip8.set_random_source(Box::new(rng));
+    if let Err(error) = chip8.load_file(&rom) {
+        eprintln!("Couldn't load the ROM: {}", error);
+        exit(1);
+    }
+
+    let mut runner = Runner::new(chip8);
+    let result = Terminal::enter().and_then(|terminal| run(&mut runner, &terminal));
+    if let Err(error) = result {
+        eprintln!("Terminal error: {}", error);
+        exit(1);
+    }
+}
+
+fn parse<T>(value: Option<T>, what: &str, text: &str) -> T {
+    value.unwrap_or_else(|| {
+        eprintln!("Invalid {}: {}", what, text);
+        exit(2);
+    })
+}
+
+// Puts the terminal in raw mode on an alternate screen, and restores it when dropped, even if
+// the emulator stops because of an error.
+struct Terminal {
+    // Whether the terminal reports key releases (e.g. kitty, foot or WezTerm).
+    reports_releases: bool,
+}
+
+impl Terminal {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        let reports_releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        let terminal = Self { reports_releases };
+        let mut stdout = io::stdout();
+        execute!(stdout, EnterAlternateScreen, Hide, Clear(ClearType::All))?;
+        if reports_releases {
+            execute!(
+                stdout,
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+        }
+        Ok(terminal)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout();
+        if self.reports_releases {
+            let _ = execute!(stdout, PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(stdout, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn run(runner: &mut Runner, terminal: &Terminal) -> io::Result<()> {
+    let mut screen = Screen::default();
+    let mut keyboard = Keyboard {
+        reports_releases: terminal.reports_releases,
+        hold: KeyHold::new(),
+        failure: None,
+    };
+    // Without a debugger, only the keyboard stops the runner.
+    runner.run(&mut screen, &mut Bell { ringing: false }, &mut keyboard);
+    match keyboard.failure.or(screen.failure) {
+        Some(failure) => Err(failure),
+        None => Ok(()),
+    }
+}
+
+// Reads the keys typed since the last frame. Esc or Ctrl-C quit.
+struct Keyboard {
+    reports_releases: bool,
+    hold: KeyHold,
+    failure: Option<io::Error>,
+}
+
+impl InputSource for Keyboard {
+    fn poll(&mut self, runner: &mut Runner) -> bool {
+        self.read(&mut runner.chip8).unwrap_or_else(|failure| {
+            self.failure = Some(failure);
+            false
+        })
+    }
+}
+
+impl Keyboard {
+    fn read(&mut self, chip8: &mut Chip8) -> io::Result<bool> {
+        while event::poll(Duration::ZERO)? {
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            let is_ctrl_c =
+                key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+            if key.code == KeyCode::Esc || is_ctrl_c {
+                return Ok(false);
+            }
+            let KeyCode::Char(character) = key.code else {
+                continue;
+            };
+            let Some(keypad_key) = map_key(character) else {
+                continue;
+            };
+            if key.kind == KeyEventKind::Release {
+                chip8.key_down(Some(keypad_key), false);
+            } else {
+                chip8.key_down(Some(keypad_key), true);
+                if !self.reports_releases {
+                    self.hold.press(keypad_key);
+                }
+            }
+        }
+        for key in self.hold.frame() {
+            chip8.key_down(Some(key), false);
+        }
+        Ok(true)
+    }
+}
+
+// A bell can't be held, so it rings once whenever the buzzer starts. It's written along with
+// the next screen.
+struct Bell {
+    ringing: bool,
+}
+
+impl AudioSink for Bell {
+    fn set_buzzer(&mut self, on: bool, _pattern: Option<AudioPattern>) {
+        if on && !self.ringing {
+            // A broken terminal is reported by the screen, which writes to it right after.
+            let _ = queue!(io::stdout(), Print('\x07'));
+        }
+        self.ringing = on;
+    }
+}
+
+// Draws the screen and a status line below it. Once writing to the terminal fails, nothing
+// is drawn anymore and the failure is reported when quitting.
+#[derive(Default)]
+struct Screen {
+    last_screen: Vec<String>,
+    last_status: String,
+    error: Option<Chip8Error>,
+    failure: Option<io::Error>,
+}
+
+impl VideoSink for Screen {
+    fn present(&mut self, frame: &Framebuffer) {
+        // Unchanged frames only need the status line, in case the error changed.
+        let screen = frame
+            .is_dirty()
+            .then(|| half_blocks(frame.pixels, frame.width));
+        if self.failure.is_none() {
+            self.failure = self.draw(screen).err();
+        }
+    }
+
+    fn show_error(&mut self, error: Option<&Chip8Error>) {
+        self.error = error.copied();
+    }
+}
+
+impl Screen {
+    fn draw(&mut self, screen: Option<Vec<String>>) -> io::Result<()> {
+        let mut stdout = io::stdout();
+        let status = match &self.error {
+            Some(error) => format!("Error: {}. Press Esc to quit.", error),
+            None => String::from("Press Esc to quit."),
+        };
+        // Only what changed is drawn again, as terminals over SSH are slow to redraw.
+        if let Some(screen) = screen {
+            // Switching between low and high resolution changes the size of the image.
+            if screen.len() != self.last_screen.len() {
+                queue!(stdout, Clear(ClearType::All))?;
+                self.last_screen.clear();
+                self.last_status.clear();
+            }
+            for (row, (line, last)) in screen.iter().zip(&self.last_screen).enumerate() {
+                if line != last {
+                    queue!(stdout, MoveTo(0, row as u16), Print(line))?;
+                }
+            }
+            for (row, line) in screen.iter().enumerate().skip(self.last_screen.len()) {
+                queue!(stdout, MoveTo(0, row as u16), Print(line))?;
+            }
+            self.last_screen = screen;
+        }
+        if status != self.last_status {
+            queue!(
+                stdout,
+                MoveTo(0, self.last_screen.len() as u16),
+                Clear(ClearType::CurrentLine),
+                Print(&status)
+            )?;
+        }
+        self.last_status = status;
+        stdout.flush()
+    }
+}
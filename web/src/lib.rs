@@ -0,0 +1,128 @@
+//! Bindings that let a web page run the emulator. `www/index.js` draws the framebuffer to a
+//! canvas and plays the buzzer with WebAudio.
+
+use chip8_core::audio::{AudioPattern, AudioSink};
+use chip8_core::chip8::{Chip8, HIRES_SCREEN_HEIGHT, HIRES_SCREEN_WIDTH};
+use chip8_core::display::{Framebuffer, Palette, VideoSink};
+use chip8_core::platform::Platform;
+use chip8_core::runner::Runner;
+use wasm_bindgen::prelude::*;
+
+const BYTES_PER_PIXEL: usize = 4;
+
+#[wasm_bindgen]
+pub struct Emulator {
+    // The page presses the keys on the machine directly, so the runner has no input source.
+    runner: Runner,
+    seed: u64,
+    screen: Screen,
+    buzzer: Buzzer,
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    /// Creates an emulator whose CXNN instruction draws from `seed`, e.g. from
+    /// `crypto.getRandomValues`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(seed: u64) -> Emulator {
+        let mut chip8 = Chip8::new();
+        chip8.seed_random(seed);
+        let mut emulator = Emulator {
+            runner: Runner::new(chip8),
+            seed,
+            screen: Screen {
+                palette: Palette::default(),
+                framebuffer: vec![0; HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT * BYTES_PER_PIXEL],
+            },
+            buzzer: Buzzer(false),
+        };
+        emulator.render();
+        emulator
+    }
+
+    /// Resets the machine and loads `rom` for `platform` (`chip8`, `schip` or `xochip`), with
+    /// the quirks ROMs for that platform usually expect. The random source restarts from the
+    /// seed.
+    pub fn load_rom(&mut self, rom: &[u8], platform: &str) -> Result<(), String> {
+        let platform = Platform::from_name(platform)
+            .ok_or_else(|| format!("unknown platform {}", platform))?;
+        let mut chip8 = Chip8::with_platform(platform, platform.default_quirks());
+        chip8.seed_random(self.seed);
+        chip8.load_file(rom).map_err(|error| error.to_string())?;
+        self.runner.load(chip8);
+        self.render();
+        Ok(())
+    }
+
+    /// Runs the instructions of a frame, decrements the timers and updates the framebuffer.
+    /// Returns whether the buzzer should sound, or the error that stopped the ROM.
+    pub fn run_frame(&mut self) -> Result<bool, String> {
+        self.runner
+            .run_frame(&mut self.screen, &mut self.buzzer, &mut ());
+        match self.runner.error() {
+            Some(error) => Err(error.to_string()),
+            None => Ok(self.buzzer.0),
+        }
+    }
+
+    /// Presses the key from 0x0 to 0xF.
+    pub fn key_down(&mut self, key: u8) {
+        self.runner.chip8.key_down(Some(key), true);
+    }
+
+    pub fn key_up(&mut self, key: u8) {
+        self.runner.chip8.key_down(Some(key), false);
+    }
+
+    /// Address of the framebuffer in the wasm memory: `width() * height()` RGBA pixels, row by
+    /// row, ready to be wrapped in an `ImageData`.
+    pub fn framebuffer(&self) -> *const u8 {
+        self.screen.framebuffer.as_ptr()
+    }
+
+    /// Width of the active resolution, in pixels.
+    pub fn width(&self) -> usize {
+        self.runner.chip8.get_resolution().0
+    }
+
+    /// Height of the active resolution, in pixels.
+    pub fn height(&self) -> usize {
+        self.runner.chip8.get_resolution().1
+    }
+}
+
+impl Emulator {
+    /// The framebuffer as a slice, `width() * height() * 4` bytes long.
+    pub fn pixels(&self) -> &[u8] {
+        let (width, height) = self.runner.chip8.get_resolution();
+        &self.screen.framebuffer[..width * height * BYTES_PER_PIXEL]
+    }
+
+    // Draws the machine as it is, e.g. right after loading a ROM.
+    fn render(&mut self) {
+        self.screen.present(&self.runner.chip8.get_framebuffer());
+    }
+}
+
+struct Screen {
+    palette: Palette,
+    // RGBA pixels of the active resolution, read by the page straight from the wasm memory.
+    framebuffer: Vec<u8>,
+}
+
+impl VideoSink for Screen {
+    fn present(&mut self, frame: &Framebuffer) {
+        frame.paint_dirty_rows(&self.palette, &mut self.framebuffer, |r, g, b| {
+            [r, g, b, 0xFF]
+        });
+    }
+}
+
+// The page plays its own tone, so only whether the buzzer sounds is kept.
+struct Buzzer(bool);
+
+impl AudioSink for Buzzer {
+    fn set_buzzer(&mut self, on: bool, _pattern: Option<AudioPattern>) {
+        self.0 = on;
+    }
+}
@@ -0,0 +1,63 @@
+//! Runs in a browser or Node.js with `wasm-pack test --node web` (or `--headless --firefox`).
+//! On other targets, they run as regular tests with `cargo test -p web`.
+
+use wasm_bindgen_test::*;
+use web::Emulator;
+
+// LD V0, 0x0A; LD F, V0; DRW V0, V0, 5 (an "A" at 10,10); LD ST, V0; JP 0x208.
+const ROM: [u8; 10] = [0x60, 0x0A, 0xF0, 0x29, 0xD0, 0x05, 0xF0, 0x18, 0x12, 0x08];
+
+fn lit_pixels(emulator: &Emulator) -> Vec<(usize, usize)> {
+    let width = emulator.width();
+    emulator
+        .pixels()
+        .chunks_exact(4)
+        .enumerate()
+        .filter(|(_, color)| color[..3] != [0, 0, 0])
+        .map(|(index, _)| (index % width, index / width))
+        .collect()
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+fn runs_frames_and_renders_the_framebuffer() {
+    let mut emulator = Emulator::new(0);
+    emulator.load_rom(&ROM, "chip8").unwrap();
+    assert_eq!((emulator.width(), emulator.height()), (64, 32));
+    assert!(lit_pixels(&emulator).is_empty());
+
+    assert!(emulator.run_frame().unwrap(), "the buzzer should sound");
+    let pixels = lit_pixels(&emulator);
+    // The top row of the "A" glyph is 0xF0.
+    assert_eq!(&pixels[..4], &[(10, 10), (11, 10), (12, 10), (13, 10)]);
+    assert_eq!(emulator.pixels().len(), 64 * 32 * 4);
+    assert_eq!(emulator.framebuffer(), emulator.pixels().as_ptr());
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+fn reports_errors_of_the_rom() {
+    let mut emulator = Emulator::new(0);
+    assert!(emulator.load_rom(&ROM, "nes").is_err());
+    assert!(emulator.load_rom(&vec![0; 8192], "chip8").is_err());
+
+    // 00EE with nothing on the stack.
+    emulator.load_rom(&[0x00, 0xEE], "chip8").unwrap();
+    assert_eq!(emulator.run_frame(), Err(String::from("stack underflow")));
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+fn waits_for_keys() {
+    // LD V0, K; LD F, V0; DRW V1, V1, 5 (the digit pressed, at 0,0); JP 0x206.
+    let mut emulator = Emulator::new(0);
+    emulator
+        .load_rom(&[0xF0, 0x0A, 0xF0, 0x29, 0xD1, 0x15, 0x12, 0x06], "schip")
+        .unwrap();
+    emulator.run_frame().unwrap();
+    assert!(lit_pixels(&emulator).is_empty());
+
+    emulator.key_down(0x1);
+    emulator.run_frame().unwrap();
+    emulator.key_up(0x1);
+    // The "1" glyph is 0x20, 0x60, 0x20, 0x20, 0x70.
+    let pixels = lit_pixels(&emulator);
+    assert_eq!(&pixels[..3], &[(2, 0), (1, 1), (2, 1)]);
+}
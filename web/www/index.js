@@ -0,0 +1,119 @@
+// Build the bindings into `pkg` first, see the README.
+import init, { Emulator } from "./pkg/web.js";
+
+// The same layout as the desktop frontend, by physical key so it works on any keyboard layout.
+const KEYS = {
+  Digit1: 0x1, Digit2: 0x2, Digit3: 0x3, Digit4: 0xc,
+  KeyQ: 0x4, KeyW: 0x5, KeyE: 0x6, KeyR: 0xd,
+  KeyA: 0x7, KeyS: 0x8, KeyD: 0x9, KeyF: 0xe,
+  KeyZ: 0xa, KeyX: 0x0, KeyC: 0xb, KeyV: 0xf,
+};
+
+const wasm = await init();
+const seed = crypto.getRandomValues(new BigUint64Array(1))[0];
+const emulator = new Emulator(seed);
+const canvas = document.getElementById("screen");
+const context = canvas.getContext("2d");
+const error = document.getElementById("error");
+let running = false;
+
+// Browsers only allow audio after a user gesture, so the buzzer is created on the first key.
+let buzzer = null;
+function startBuzzer() {
+  if (buzzer) {
+    return;
+  }
+  const audio = new AudioContext();
+  const oscillator = audio.createOscillator();
+  const gain = audio.createGain();
+  oscillator.type = "square";
+  oscillator.frequency.value = 440;
+  gain.gain.value = 0;
+  oscillator.connect(gain).connect(audio.destination);
+  oscillator.start();
+  buzzer = gain;
+}
+
+function draw() {
+  const width = emulator.width();
+  const height = emulator.height();
+  if (canvas.width !== width || canvas.height !== height) {
+    canvas.width = width;
+    canvas.height = height;
+  }
+  // The framebuffer lives in the wasm memory, so it's wrapped instead of copied.
+  const pixels = new Uint8ClampedArray(wasm.memory.buffer, emulator.framebuffer(), width * height * 4);
+  context.putImageData(new ImageData(pixels, width, height), 0, 0);
+}
+
+// Frames run at 60 Hz whatever the refresh rate of the display is.
+const FRAME_INTERVAL = 1000 / 60;
+let lastTime = null;
+let lag = 0;
+
+function runFrame() {
+  try {
+    const beep = emulator.run_frame();
+    if (buzzer) {
+      buzzer.gain.value = beep ? 0.1 : 0;
+    }
+  } catch (message) {
+    running = false;
+    error.textContent = `Error: ${message}`;
+    if (buzzer) {
+      buzzer.gain.value = 0;
+    }
+  }
+}
+
+function frame(time) {
+  if (!running) {
+    return;
+  }
+  // Don't try to catch up after the tab was in the background.
+  lag = Math.min(lag + time - (lastTime ?? time), 10 * FRAME_INTERVAL);
+  lastTime = time;
+  while (running && lag >= FRAME_INTERVAL) {
+    runFrame();
+    lag -= FRAME_INTERVAL;
+  }
+  draw();
+  requestAnimationFrame(frame);
+}
+
+document.getElementById("rom").addEventListener("change", async (event) => {
+  const file = event.target.files[0];
+  if (!file) {
+    return;
+  }
+  const rom = new Uint8Array(await file.arrayBuffer());
+  const platform = document.getElementById("platform").value;
+  try {
+    emulator.load_rom(rom, platform);
+  } catch (message) {
+    error.textContent = `Couldn't load the ROM: ${message}`;
+    return;
+  }
+  error.textContent = "";
+  if (!running) {
+    running = true;
+    lastTime = null;
+    requestAnimationFrame(frame);
+  }
+});
+
+document.addEventListener("keydown", (event) => {
+  startBuzzer();
+  if (event.code in KEYS) {
+    emulator.key_down(KEYS[event.code]);
+    event.preventDefault();
+  }
+});
+
+document.addEventListener("keyup", (event) => {
+  if (event.code in KEYS) {
+    emulator.key_up(KEYS[event.code]);
+  }
+});
+
+draw();